@@ -0,0 +1,82 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Contracts whose creation bytecode is embedded in the binary, and the mocks used by the tests
+const CONTRACTS: &[&str] = &[
+    "GasConsumer",
+    "MockPBHEntryPoint",
+    "MockSmartAccount",
+    "Multicall3",
+    "PBHVectors",
+];
+
+fn main() {
+    println!("cargo:rerun-if-changed=contracts");
+    println!("cargo:rerun-if-changed=foundry.toml");
+    println!("cargo:rerun-if-env-changed=GASTEST_NO_CONTRACTS");
+
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    // Building without the contracts has to be asked for, the binary can't deploy GasConsumer then
+    let allow_missing = env::var("GASTEST_NO_CONTRACTS").is_ok_and(|value| value == "1");
+
+    // Artifacts and the compiler cache go to OUT_DIR, keeping the source tree clean
+    let artifacts_dir = out_dir.join("forge-out");
+    let forge = Command::new("forge")
+        .arg("build")
+        .arg("--out")
+        .arg(&artifacts_dir)
+        .arg("--cache-path")
+        .arg(out_dir.join("forge-cache"))
+        .current_dir(&manifest_dir)
+        .status();
+    if !matches!(forge, Ok(status) if status.success()) {
+        missing(
+            allow_missing,
+            "forge build failed or forge is not installed, no contract bytecode is embedded",
+        );
+    }
+
+    for contract in CONTRACTS {
+        let artifact = artifacts_dir
+            .join(format!("{contract}.sol"))
+            .join(format!("{contract}.json"));
+        let bytecode = read_bytecode(&artifact).unwrap_or_else(|| {
+            missing(
+                allow_missing,
+                &format!("no artifact for {contract}, its bytecode will not be embedded"),
+            );
+            String::new()
+        });
+        fs::write(out_dir.join(format!("{contract}.bin")), bytecode).unwrap();
+
+        // Selectors of the compiled contract, used to check the Rust bindings against it
+        let methods = read_method_identifiers(&artifact).unwrap_or_else(|| "{}".to_string());
+        fs::write(out_dir.join(format!("{contract}.methods.json")), methods).unwrap();
+    }
+}
+
+// Fails the build over missing contracts, or only warns with GASTEST_NO_CONTRACTS=1
+fn missing(allow_missing: bool, message: &str) {
+    if !allow_missing {
+        panic!("{message}. Install foundry, or set GASTEST_NO_CONTRACTS=1 to build without the contracts");
+    }
+    println!("cargo:warning={message}");
+}
+
+// Reads `bytecode.object` from a forge artifact
+fn read_bytecode(artifact: &Path) -> Option<String> {
+    let content = fs::read_to_string(artifact).ok()?;
+    let json: serde_json::Value = serde_json::from_str(&content).ok()?;
+    let object = json["bytecode"]["object"].as_str()?;
+    Some(object.trim_start_matches("0x").to_string())
+}
+
+// Reads `methodIdentifiers` (signature to selector) from a forge artifact
+fn read_method_identifiers(artifact: &Path) -> Option<String> {
+    let content = fs::read_to_string(artifact).ok()?;
+    let json: serde_json::Value = serde_json::from_str(&content).ok()?;
+    Some(json.get("methodIdentifiers")?.to_string())
+}
@@ -2,6 +2,16 @@
 pragma solidity ^0.8.0;
 
 contract GasConsumer {
+    // Emitted once per iteration by emitLogs
+    event Consumed(address indexed sender, uint256 indexed index, bytes32 data);
+
+    // Next unused slot in `slots`, so that every storeCold write hits a fresh slot
+    uint256 public nextSlot;
+    mapping(uint256 => uint256) public slots;
+
+    // Slot rewritten by storeWarm
+    uint256 public warmSlot;
+
     // A simple function that consumes gas based on the number of iterations
     function consumeGas(address _address, uint256 _iterations) public {
         // Store the address in memory to make the function do something with the parameter
@@ -17,4 +27,56 @@ contract GasConsumer {
         // The function doesn't need to return anything
         // The gas consumption is the main purpose
     }
+
+    // Writes `_count` storage slots that have never been written (cold, zero to non-zero)
+    function storeCold(uint256 _count) public {
+        uint256 start = nextSlot;
+        for (uint256 i = 0; i < _count; i++) {
+            slots[start + i] = i + 1;
+        }
+        nextSlot = start + _count;
+    }
+
+    // Writes the same storage slot `_count` times, all writes after the first are warm
+    function storeWarm(uint256 _count) public {
+        for (uint256 i = 0; i < _count; i++) {
+            warmSlot = i + 1;
+        }
+    }
+
+    // Expands memory to `_words` 32 byte words
+    function expandMemory(uint256 _words) public pure returns (uint256) {
+        uint256[] memory buffer = new uint256[](_words);
+        if (_words > 0) {
+            buffer[_words - 1] = 1;
+        }
+        return buffer.length;
+    }
+
+    // Accepts arbitrary calldata, the cost is dominated by the calldata itself
+    function consumeCalldata(bytes calldata _data) public pure returns (uint256) {
+        return _data.length;
+    }
+
+    // Emits `_count` events
+    function emitLogs(uint256 _count) public {
+        for (uint256 i = 0; i < _count; i++) {
+            emit Consumed(msg.sender, i, keccak256(abi.encodePacked(i)));
+        }
+    }
+
+    // Calls the ecrecover precompile `_count` times
+    function callEcrecover(uint256 _count) public pure returns (address signer) {
+        for (uint256 i = 0; i < _count; i++) {
+            bytes32 hash = keccak256(abi.encodePacked(i));
+            signer = ecrecover(hash, 27, hash, hash);
+        }
+    }
+
+    // Calls the SHA256 precompile `_count` times
+    function callSha256(uint256 _count) public pure returns (bytes32 digest) {
+        for (uint256 i = 0; i < _count; i++) {
+            digest = sha256(abi.encodePacked(digest, i));
+        }
+    }
 }
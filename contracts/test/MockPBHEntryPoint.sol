@@ -0,0 +1,212 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+import {Multicall3} from "./Multicall3.sol";
+import {PBHEncoding} from "./PBHEncoding.sol";
+
+// Mock of the World Chain PBH entry point for tests against a local anvil.
+// It checks the external nullifier, nullifier reuse and the World ID root like the real
+// entry point, but does not verify Semaphore proofs. Mock proofs, whose last six coordinates
+// are zero, carry the signal hash and external nullifier they were made for instead, and
+// those are checked against the ones the entry point computes.
+contract MockPBHEntryPoint {
+    struct PBHPayload {
+        uint256 root;
+        uint256 pbhExternalNullifier;
+        uint256 nullifierHash;
+        uint256[8] proof;
+    }
+
+    struct PackedUserOperation {
+        address sender;
+        uint256 nonce;
+        bytes initCode;
+        bytes callData;
+        bytes32 accountGasLimits;
+        uint256 preVerificationGas;
+        bytes32 gasFees;
+        bytes paymasterAndData;
+        bytes signature;
+    }
+
+    struct UserOpsPerAggregator {
+        PackedUserOperation[] userOps;
+        address aggregator;
+        bytes signature;
+    }
+
+    error InvalidNullifier(uint256 nullifierHash, uint256 signalHash);
+    error InvalidRoot(uint256 root);
+    error InvalidExternalNullifierVersion(uint8 version);
+    error InvalidExternalNullifierPeriod(uint8 month, uint16 year);
+    error InvalidPbhNonce(uint16 pbhNonce);
+    error InvalidUserOpNonce(uint256 nonce);
+    error ProofInvalid();
+
+    event PBH(address indexed sender, uint256 indexed nullifierHash, uint256 signalHash);
+
+    uint8 public constant EXTERNAL_NULLIFIER_VERSION = 1;
+
+    Multicall3 public immutable multicall3;
+    uint16 public numPbhPerMonth;
+    mapping(uint256 => bool) public nullifierHashes;
+
+    // Roots are accepted like by the World ID contract: the latest root, and earlier ones until
+    // ROOT_HISTORY_EXPIRY after they were replaced. Any root is accepted until one is added.
+    uint256 public constant ROOT_HISTORY_EXPIRY = 1 hours;
+    uint256 public latestRoot;
+    mapping(uint256 => uint128) public rootHistory;
+    bool public checkRoots;
+
+    // ERC-4337 nonce sequences by sender and key
+    mapping(address => mapping(uint192 => uint64)) internal sequences;
+
+    constructor(uint16 _numPbhPerMonth, Multicall3 _multicall3) {
+        numPbhPerMonth = _numPbhPerMonth;
+        multicall3 = _multicall3;
+    }
+
+    function addRoot(uint256 root) external {
+        if (checkRoots && root != latestRoot) {
+            rootHistory[latestRoot] = uint128(block.timestamp);
+        }
+        latestRoot = root;
+        checkRoots = true;
+    }
+
+    // The mock is its own World ID contract
+    function worldId() external view returns (address) {
+        return address(this);
+    }
+
+    function validRoots(uint256 root) public view returns (bool) {
+        if (!checkRoots || root == latestRoot) {
+            return true;
+        }
+        uint128 replacedAt = rootHistory[root];
+        return replacedAt != 0 && block.timestamp - replacedAt <= ROOT_HISTORY_EXPIRY;
+    }
+
+    function requireValidRoot(uint256 root) external view {
+        if (!validRoots(root)) {
+            revert InvalidRoot(root);
+        }
+    }
+
+    function pbhMulticall(Multicall3.Call3[] calldata calls, PBHPayload calldata payload)
+        external
+        returns (Multicall3.Result[] memory returnData)
+    {
+        uint256 signalHash = PBHEncoding.multicallSignalHash(msg.sender, calls);
+        _verifyPbh(signalHash, payload);
+        nullifierHashes[payload.nullifierHash] = true;
+
+        returnData = multicall3.aggregate3(calls);
+        emit PBH(msg.sender, payload.nullifierHash, signalHash);
+    }
+
+    function handleAggregatedOps(UserOpsPerAggregator[] calldata opsPerAggregator, address payable) external {
+        for (uint256 i = 0; i < opsPerAggregator.length; i++) {
+            PackedUserOperation[] calldata userOps = opsPerAggregator[i].userOps;
+            PBHPayload[] memory payloads = abi.decode(opsPerAggregator[i].signature, (PBHPayload[]));
+            require(payloads.length == userOps.length, "payload count mismatch");
+
+            for (uint256 j = 0; j < userOps.length; j++) {
+                PackedUserOperation calldata op = userOps[j];
+                uint256 signalHash = PBHEncoding.userOpSignalHash(op.sender, op.nonce, op.callData);
+                _verifyPbh(signalHash, payloads[j]);
+                _useNonce(op.sender, op.nonce);
+                nullifierHashes[payloads[j].nullifierHash] = true;
+
+                (bool success, bytes memory ret) = op.sender.call(op.callData);
+                if (!success) {
+                    assembly {
+                        revert(add(ret, 32), mload(ret))
+                    }
+                }
+                emit PBH(op.sender, payloads[j].nullifierHash, signalHash);
+            }
+        }
+    }
+
+    function getNonce(address sender, uint192 key) external view returns (uint256 nonce) {
+        return sequences[sender][key] | (uint256(key) << 64);
+    }
+
+    function getUserOpHash(PackedUserOperation calldata userOp) external view returns (bytes32) {
+        bytes32 opHash = keccak256(
+            abi.encode(
+                userOp.sender,
+                userOp.nonce,
+                keccak256(userOp.initCode),
+                keccak256(userOp.callData),
+                userOp.accountGasLimits,
+                userOp.preVerificationGas,
+                userOp.gasFees,
+                keccak256(userOp.paymasterAndData)
+            )
+        );
+        return keccak256(abi.encode(opHash, address(this), block.chainid));
+    }
+
+    function _verifyPbh(uint256 signalHash, PBHPayload memory payload) internal view {
+        if (nullifierHashes[payload.nullifierHash]) {
+            revert InvalidNullifier(payload.nullifierHash, signalHash);
+        }
+        if (!validRoots(payload.root)) {
+            revert InvalidRoot(payload.root);
+        }
+        _verifyExternalNullifier(payload.pbhExternalNullifier);
+        if (_isMockProof(payload.proof)) {
+            if (payload.proof[0] != signalHash || payload.proof[1] != payload.pbhExternalNullifier) {
+                revert ProofInvalid();
+            }
+        }
+    }
+
+    function _isMockProof(uint256[8] memory proof) internal pure returns (bool) {
+        for (uint256 i = 2; i < 8; i++) {
+            if (proof[i] != 0) {
+                return false;
+            }
+        }
+        return true;
+    }
+
+    function _verifyExternalNullifier(uint256 externalNullifier) internal view {
+        (uint8 version, uint16 pbhNonce, uint8 month, uint16 year) =
+            PBHEncoding.decodeExternalNullifier(externalNullifier);
+
+        if (version != EXTERNAL_NULLIFIER_VERSION) {
+            revert InvalidExternalNullifierVersion(version);
+        }
+        (uint16 currentYear, uint8 currentMonth) = _yearMonth(block.timestamp);
+        if (year != currentYear || month != currentMonth) {
+            revert InvalidExternalNullifierPeriod(month, year);
+        }
+        if (pbhNonce >= numPbhPerMonth) {
+            revert InvalidPbhNonce(pbhNonce);
+        }
+    }
+
+    function _useNonce(address sender, uint256 nonce) internal {
+        uint192 key = uint192(nonce >> 64);
+        if (uint64(nonce) != sequences[sender][key]) {
+            revert InvalidUserOpNonce(nonce);
+        }
+        sequences[sender][key]++;
+    }
+
+    // UTC year and month of a timestamp (days to civil date)
+    function _yearMonth(uint256 timestamp) internal pure returns (uint16 year, uint8 month) {
+        uint256 z = timestamp / 86400 + 719468;
+        uint256 era = z / 146097;
+        uint256 doe = z - era * 146097;
+        uint256 yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        uint256 doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        uint256 mp = (5 * doy + 2) / 153;
+        uint256 m = mp < 10 ? mp + 3 : mp - 9;
+        uint256 y = yoe + era * 400 + (m <= 2 ? 1 : 0);
+        return (uint16(y), uint8(m));
+    }
+}
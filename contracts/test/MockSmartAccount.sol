@@ -0,0 +1,16 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+// Smart account that executes whatever the entry point asks it to, for user operation tests
+contract MockSmartAccount {
+    function execute(address dest, uint256 value, bytes calldata func) external {
+        (bool success, bytes memory ret) = dest.call{value: value}(func);
+        if (!success) {
+            assembly {
+                revert(add(ret, 32), mload(ret))
+            }
+        }
+    }
+
+    receive() external payable {}
+}
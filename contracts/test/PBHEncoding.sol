@@ -0,0 +1,42 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+import {Multicall3} from "./Multicall3.sol";
+
+// Encodings the World Chain PBH entry point uses for signals and external nullifiers
+library PBHEncoding {
+    // Signal of a `pbhMulticall`: `hashToField(abi.encode(msg.sender, calls))`
+    function multicallSignalHash(address sender, Multicall3.Call3[] calldata calls) internal pure returns (uint256) {
+        return hashToField(abi.encode(sender, calls));
+    }
+
+    // Signal of a PBH user operation: `hashToField(abi.encodePacked(sender, nonce, callData))`
+    function userOpSignalHash(address sender, uint256 nonce, bytes calldata callData) internal pure returns (uint256) {
+        return hashToField(abi.encodePacked(sender, nonce, callData));
+    }
+
+    // Same as semaphore's hashToField: keccak256 shifted right by a byte
+    function hashToField(bytes memory value) internal pure returns (uint256) {
+        return uint256(keccak256(value)) >> 8;
+    }
+
+    // `year << 32 | month << 24 | pbhNonce << 8 | version`
+    function encodeExternalNullifier(uint8 version, uint16 pbhNonce, uint8 month, uint16 year)
+        internal
+        pure
+        returns (uint256)
+    {
+        return (uint256(year) << 32) | (uint256(month) << 24) | (uint256(pbhNonce) << 8) | uint256(version);
+    }
+
+    function decodeExternalNullifier(uint256 externalNullifier)
+        internal
+        pure
+        returns (uint8 version, uint16 pbhNonce, uint8 month, uint16 year)
+    {
+        version = uint8(externalNullifier);
+        pbhNonce = uint16(externalNullifier >> 8);
+        month = uint8(externalNullifier >> 24);
+        year = uint16(externalNullifier >> 32);
+    }
+}
@@ -0,0 +1,28 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+import {Multicall3} from "./Multicall3.sol";
+import {PBHEncoding} from "./PBHEncoding.sol";
+
+// Exposes the PBH encodings so the Rust encoders can be checked against them
+contract PBHVectors {
+    function multicallSignalHash(address sender, Multicall3.Call3[] calldata calls) external pure returns (uint256) {
+        return PBHEncoding.multicallSignalHash(sender, calls);
+    }
+
+    function userOpSignalHash(address sender, uint256 nonce, bytes calldata callData) external pure returns (uint256) {
+        return PBHEncoding.userOpSignalHash(sender, nonce, callData);
+    }
+
+    function hashToField(bytes calldata value) external pure returns (uint256) {
+        return PBHEncoding.hashToField(value);
+    }
+
+    function encodeExternalNullifier(uint8 version, uint16 pbhNonce, uint8 month, uint16 year)
+        external
+        pure
+        returns (uint256)
+    {
+        return PBHEncoding.encodeExternalNullifier(version, pbhNonce, month, year);
+    }
+}
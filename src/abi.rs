@@ -0,0 +1,67 @@
+use alloy_dyn_abi::{DynSolValue, JsonAbiExt, Specifier};
+use alloy_json_abi::Function;
+use alloy_primitives::{hex, Bytes};
+use eyre::Result;
+
+/// Parses a human readable signature such as `transfer(address,uint256)`, optionally followed
+/// by `returns (...)`
+pub fn parse_function(signature: &str) -> Result<Function> {
+    Function::parse(signature)
+        .map_err(|err| eyre::eyre!("Invalid function signature `{}`: {}", signature, err))
+}
+
+/// Calldata of `function` with arguments given as strings, like `cast calldata`
+pub fn encode_call(function: &Function, args: &[String]) -> Result<Bytes> {
+    if args.len() != function.inputs.len() {
+        return Err(eyre::eyre!(
+            "{} takes {} argument(s), got {}",
+            function.signature(),
+            function.inputs.len(),
+            args.len()
+        ));
+    }
+
+    let values = function
+        .inputs
+        .iter()
+        .zip(args)
+        .map(|(param, arg)| {
+            param
+                .resolve()?
+                .coerce_str(arg)
+                .map_err(|err| eyre::eyre!("Invalid {} argument `{}`: {}", param.ty, arg, err))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(function.abi_encode_input(&values)?.into())
+}
+
+/// Decodes the return data of `function`, `None` if its signature has no outputs
+pub fn decode_output(function: &Function, data: &[u8]) -> Result<Option<Vec<DynSolValue>>> {
+    if function.outputs.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(function.abi_decode_output(data, true)?))
+}
+
+/// Formats a decoded value the way it would be written as an argument
+pub fn format_value(value: &DynSolValue) -> String {
+    let join = |values: &[DynSolValue]| {
+        values
+            .iter()
+            .map(format_value)
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    match value {
+        DynSolValue::Bool(value) => value.to_string(),
+        DynSolValue::Int(value, _) => value.to_string(),
+        DynSolValue::Uint(value, _) => value.to_string(),
+        DynSolValue::FixedBytes(word, size) => hex::encode_prefixed(&word[..*size]),
+        DynSolValue::Address(address) => address.to_string(),
+        DynSolValue::Bytes(bytes) => hex::encode_prefixed(bytes),
+        DynSolValue::String(string) => format!("{:?}", string),
+        DynSolValue::Array(values) | DynSolValue::FixedArray(values) => format!("[{}]", join(values)),
+        DynSolValue::Tuple(values) => format!("({})", join(values)),
+        other => format!("{:?}", other),
+    }
+}
@@ -0,0 +1,386 @@
+use alloy_primitives::U256;
+use clap::ValueEnum;
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::client::GasTestClient;
+use crate::identity::IdentityScheduler;
+use crate::transaction::single_call_multicall;
+use crate::workload::Workload;
+use crate::world_id::WorldID;
+
+/// Percentage a case may move by when no threshold is given
+pub const DEFAULT_MAX_PERCENT_DELTA: f64 = 1.0;
+
+/// A measured configuration: a workload of some size sent as one kind of transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BaselineCase {
+    pub workload: Workload,
+    pub iterations: u64,
+    pub tx_type: BaselineTxType,
+}
+
+/// How a case is sent. User operations need a smart account and an aggregator, so baselines
+/// only cover direct and `pbhMulticall` transactions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BaselineTxType {
+    Direct,
+    Pbh,
+}
+
+/// Parses `<workload>:<iterations>:<direct|pbh>`
+impl FromStr for BaselineCase {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let [workload, iterations, tx_type] = parts.as_slice() else {
+            return Err(eyre::eyre!(
+                "Expected <workload>:<iterations>:<direct|pbh>, got `{}`",
+                s
+            ));
+        };
+        let tx_type = match *tx_type {
+            "direct" => BaselineTxType::Direct,
+            "pbh" => BaselineTxType::Pbh,
+            other => {
+                return Err(eyre::eyre!(
+                    "Unknown transaction type `{}`, use direct or pbh",
+                    other
+                ))
+            }
+        };
+        Ok(Self {
+            workload: <Workload as ValueEnum>::from_str(workload, true)
+                .map_err(|err| eyre::eyre!(err))?,
+            iterations: iterations.parse()?,
+            tx_type,
+        })
+    }
+}
+
+impl fmt::Display for BaselineCase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tx_type = match self.tx_type {
+            BaselineTxType::Direct => "direct",
+            BaselineTxType::Pbh => "pbh",
+        };
+        write!(
+            f,
+            "{}:{}:{}",
+            workload_name(self.workload),
+            self.iterations,
+            tx_type
+        )
+    }
+}
+
+fn workload_name(workload: Workload) -> String {
+    workload
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_else(|| format!("{:?}", workload))
+}
+
+/// Every workload at a small size, sent directly and through PBH
+pub fn default_cases() -> Vec<BaselineCase> {
+    let sizes = [
+        (Workload::Keccak, 100),
+        (Workload::StorageCold, 10),
+        (Workload::StorageWarm, 10),
+        (Workload::Memory, 100),
+        (Workload::Calldata, 1000),
+        (Workload::Logs, 10),
+        (Workload::Ecrecover, 5),
+        (Workload::Sha256, 10),
+    ];
+    sizes
+        .into_iter()
+        .flat_map(|(workload, iterations)| {
+            [BaselineTxType::Direct, BaselineTxType::Pbh].map(|tx_type| BaselineCase {
+                workload,
+                iterations,
+                tx_type,
+            })
+        })
+        .collect()
+}
+
+/// Gas used by a case
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Measurement {
+    #[serde(flatten)]
+    pub case: BaselineCase,
+    pub gas_used: u64,
+}
+
+/// Gas measurements stored by `baseline record`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Baseline {
+    /// RFC 3339 time of the recording
+    pub recorded_at: String,
+    /// Network profile it was recorded on, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+    pub measurements: Vec<Measurement>,
+}
+
+impl Baseline {
+    pub fn new(network: Option<String>, measurements: Vec<Measurement>) -> Self {
+        Self {
+            recorded_at: chrono::Utc::now().to_rfc3339(),
+            network,
+            measurements,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| eyre::eyre!("Failed to read baseline {}: {}", path.display(), err))?;
+        serde_json::from_str(&content)
+            .map_err(|err| eyre::eyre!("Invalid baseline {}: {}", path.display(), err))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)? + "\n")?;
+        Ok(())
+    }
+
+    pub fn cases(&self) -> Vec<BaselineCase> {
+        self.measurements
+            .iter()
+            .map(|measurement| measurement.case)
+            .collect()
+    }
+}
+
+/// How far a measurement may move from the baseline. A row fails when it moves by more than
+/// any threshold that is set.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Thresholds {
+    pub max_gas_delta: Option<u64>,
+    pub max_percent_delta: Option<f64>,
+}
+
+impl Thresholds {
+    /// Thresholds from the command line, the default percentage if none is given
+    pub fn new(max_gas_delta: Option<u64>, max_percent_delta: Option<f64>) -> Self {
+        let max_percent_delta = match (max_gas_delta, max_percent_delta) {
+            (None, None) => Some(DEFAULT_MAX_PERCENT_DELTA),
+            (_, percent) => percent,
+        };
+        Self {
+            max_gas_delta,
+            max_percent_delta,
+        }
+    }
+}
+
+/// A baseline value next to its new measurement
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub name: String,
+    pub baseline: u64,
+    pub measured: u64,
+}
+
+impl Comparison {
+    pub fn delta(&self) -> i64 {
+        self.measured as i64 - self.baseline as i64
+    }
+
+    /// Change relative to the baseline in percent
+    pub fn percent(&self) -> f64 {
+        if self.baseline == 0 {
+            return if self.measured == 0 {
+                0.0
+            } else {
+                f64::INFINITY
+            };
+        }
+        self.delta() as f64 * 100.0 / self.baseline as f64
+    }
+
+    pub fn exceeds(&self, thresholds: &Thresholds) -> bool {
+        let over_gas = thresholds
+            .max_gas_delta
+            .is_some_and(|max| self.delta().unsigned_abs() > max);
+        let over_percent = thresholds
+            .max_percent_delta
+            .is_some_and(|max| self.percent().abs() > max);
+        over_gas || over_percent
+    }
+}
+
+/// Compares every baseline case with its measurement. Cases measured both directly and through
+/// PBH also get a row for the PBH overhead, the gas PBH adds to the direct call.
+pub fn compare(baseline: &[Measurement], measured: &[Measurement]) -> Result<Vec<Comparison>> {
+    let gas = |measurements: &[Measurement], case: &BaselineCase| {
+        measurements
+            .iter()
+            .find(|measurement| measurement.case == *case)
+            .map(|measurement| measurement.gas_used)
+    };
+
+    let mut comparisons = Vec::new();
+    for expected in baseline {
+        let measured_gas = gas(measured, &expected.case)
+            .ok_or_else(|| eyre::eyre!("Case {} was not measured", expected.case))?;
+        comparisons.push(Comparison {
+            name: expected.case.to_string(),
+            baseline: expected.gas_used,
+            measured: measured_gas,
+        });
+
+        if expected.case.tx_type != BaselineTxType::Pbh {
+            continue;
+        }
+        let direct = BaselineCase {
+            tx_type: BaselineTxType::Direct,
+            ..expected.case
+        };
+        if let (Some(baseline_direct), Some(measured_direct)) =
+            (gas(baseline, &direct), gas(measured, &direct))
+        {
+            comparisons.push(Comparison {
+                name: format!(
+                    "{}:{} pbh overhead",
+                    workload_name(direct.workload),
+                    direct.iterations
+                ),
+                baseline: expected.gas_used.saturating_sub(baseline_direct),
+                measured: measured_gas.saturating_sub(measured_direct),
+            });
+        }
+    }
+    Ok(comparisons)
+}
+
+/// Measurements compared with a recorded baseline
+#[derive(Debug, Clone, PartialEq)]
+pub struct BaselineCheck {
+    pub comparisons: Vec<Comparison>,
+    pub thresholds: Thresholds,
+}
+
+impl BaselineCheck {
+    pub fn new(
+        recorded: &Baseline,
+        measured: &[Measurement],
+        thresholds: Thresholds,
+    ) -> Result<Self> {
+        Ok(Self {
+            comparisons: compare(&recorded.measurements, measured)?,
+            thresholds,
+        })
+    }
+
+    /// Rows that moved beyond a threshold
+    pub fn failed(&self) -> usize {
+        self.comparisons
+            .iter()
+            .filter(|comparison| comparison.exceeds(&self.thresholds))
+            .count()
+    }
+
+    pub fn table(&self) -> String {
+        diff_table(&self.comparisons, &self.thresholds)
+    }
+}
+
+/// Table of comparisons with a status column, `FAIL` for rows over a threshold
+pub fn diff_table(comparisons: &[Comparison], thresholds: &Thresholds) -> String {
+    let width = comparisons
+        .iter()
+        .map(|comparison| comparison.name.len())
+        .max()
+        .unwrap_or(0)
+        .max("Case".len());
+
+    let mut table = format!(
+        "{:<width$}  {:>10}  {:>10}  {:>8}  {:>8}  Status\n",
+        "Case", "Baseline", "Measured", "Delta", "Change"
+    );
+    for comparison in comparisons {
+        let status = if comparison.exceeds(thresholds) {
+            "FAIL"
+        } else {
+            "ok"
+        };
+        table.push_str(&format!(
+            "{:<width$}  {:>10}  {:>10}  {:>+8}  {:>+7.2}%  {}\n",
+            comparison.name,
+            comparison.baseline,
+            comparison.measured,
+            comparison.delta(),
+            comparison.percent(),
+            status
+        ));
+    }
+    table
+}
+
+/// Sends every case once and measures its gas. PBH cases spread over `identities` by their
+/// remaining PBH quota.
+pub async fn measure(
+    client: &GasTestClient,
+    identities: Vec<WorldID>,
+    cases: &[BaselineCase],
+    timeout: Duration,
+) -> Result<Vec<Measurement>> {
+    let mut scheduler = if identities.is_empty() {
+        None
+    } else {
+        Some(
+            IdentityScheduler::load(
+                client.provider(),
+                client.network().pbh_entry_point,
+                identities,
+            )
+            .await?,
+        )
+    };
+    let contract_address = client.network().contract_address;
+    let mut measurements = Vec::with_capacity(cases.len());
+    for case in cases {
+        let calldata = case.workload.calldata(contract_address, case.iterations);
+        let sent = match case.tx_type {
+            BaselineTxType::Direct => {
+                client
+                    .send_call(contract_address, calldata, U256::ZERO)
+                    .await?
+            }
+            BaselineTxType::Pbh => {
+                let scheduler = scheduler
+                    .as_mut()
+                    .ok_or_else(|| eyre::eyre!("PBH cases need a world_id"))?;
+                let (identity, pbh_nonce) = scheduler.next().ok_or_else(|| {
+                    eyre::eyre!("Every identity used up its PBH quota for this month")
+                })?;
+                let calls = single_call_multicall(contract_address, calldata);
+                client
+                    .send_pbh_as(scheduler.world_id(identity), calls, pbh_nonce)
+                    .await?
+            }
+        };
+
+        // Waiting keeps every case in its own, otherwise identical, state
+        let receipt = client.wait_for_receipt(sent.tx_hash, timeout).await?;
+        if !receipt.status() {
+            return Err(eyre::eyre!("Case {} reverted in {:?}", case, sent.tx_hash));
+        }
+        measurements.push(Measurement {
+            case: *case,
+            gas_used: receipt.gas_used,
+        });
+    }
+    Ok(measurements)
+}
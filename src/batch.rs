@@ -0,0 +1,151 @@
+use alloy_primitives::{Bytes, B256};
+use eyre::Result;
+
+use crate::bindings::IMulticall3::Call3;
+use crate::client::{GasTestClient, SentTransaction, UserOpOptions, UserOpSubmission};
+use crate::identity::IdentityScheduler;
+use crate::inclusion::InclusionTracker;
+
+/// How the PBH transactions of a batch are proven and sent
+#[derive(Debug, Clone)]
+struct PbhBatch {
+    scheduler: IdentityScheduler,
+    /// Identity and PBH nonce of every transaction, in send order
+    plan: Vec<(usize, u16)>,
+    /// Calls of the PBH multicall
+    calls: Vec<Call3>,
+    /// Sends the workload as user operations instead of PBH multicalls
+    user_op: Option<UserOpOptions>,
+}
+
+/// The workload transactions of a run, sent directly or as PBH transactions
+#[derive(Debug, Clone)]
+pub struct Batch {
+    calldata: Bytes,
+    count: u64,
+    pbh: Option<PbhBatch>,
+}
+
+/// What a batch sent
+#[derive(Debug, Clone)]
+pub enum Submission {
+    Transaction(SentTransaction),
+    /// User operation accepted by a bundler, with its hash
+    UserOp(B256),
+}
+
+/// One send of a batch
+#[derive(Debug, Clone)]
+pub struct BatchSend {
+    /// Identity and PBH nonce it was proven with, for PBH sends
+    pub pbh: Option<(usize, u16)>,
+    pub submission: Submission,
+}
+
+impl BatchSend {
+    pub fn transaction(&self) -> Option<&SentTransaction> {
+        match &self.submission {
+            Submission::Transaction(sent) => Some(sent),
+            Submission::UserOp(_) => None,
+        }
+    }
+}
+
+impl Batch {
+    /// `count` direct calls of the contract with `calldata`
+    pub fn direct(calldata: Bytes, count: u64) -> Self {
+        Self {
+            calldata,
+            count,
+            pbh: None,
+        }
+    }
+
+    /// `count` PBH transactions calling `calls`, or user operations calling the contract with
+    /// `calldata`. The identity and PBH nonce of every transaction are planned up front, from
+    /// `first_nonce` if there is one, so the proofs of the multicalls can be generated while
+    /// earlier transactions are sent.
+    pub fn pbh(
+        client: &GasTestClient,
+        calldata: Bytes,
+        count: u64,
+        mut scheduler: IdentityScheduler,
+        first_nonce: Option<u16>,
+        calls: Vec<Call3>,
+        user_op: Option<UserOpOptions>,
+    ) -> Result<Self> {
+        let plan = scheduler.plan(count, first_nonce)?;
+        if user_op.is_none() {
+            for (identity, pbh_nonce) in &plan {
+                client.prefetch_pbh_as(scheduler.world_id(*identity), &calls, *pbh_nonce)?;
+            }
+        }
+        Ok(Self {
+            calldata,
+            count,
+            pbh: Some(PbhBatch {
+                scheduler,
+                plan,
+                calls,
+                user_op,
+            }),
+        })
+    }
+
+    /// Scheduler of the identities, for PBH batches
+    pub fn scheduler(&self) -> Option<&IdentityScheduler> {
+        self.pbh.as_ref().map(|pbh| &pbh.scheduler)
+    }
+
+    /// Sends every transaction in order, watching the ones sent as transactions with `tracker`
+    pub async fn send(
+        &self,
+        client: &GasTestClient,
+        mut tracker: Option<&mut InclusionTracker>,
+    ) -> Result<Vec<BatchSend>> {
+        let mut sends = Vec::with_capacity(self.count as usize);
+        for index in 0..self.count as usize {
+            let send = match &self.pbh {
+                None => BatchSend {
+                    pbh: None,
+                    submission: Submission::Transaction(
+                        client.send_direct(self.calldata.clone()).await?,
+                    ),
+                },
+                Some(pbh) => {
+                    let (identity, pbh_nonce) = pbh.plan[index];
+                    let world_id = pbh.scheduler.world_id(identity);
+                    let submission = match &pbh.user_op {
+                        None => Submission::Transaction(
+                            client
+                                .send_pbh_as(world_id, pbh.calls.clone(), pbh_nonce)
+                                .await?,
+                        ),
+                        Some(options) => match client
+                            .send_pbh_user_op_as(
+                                world_id,
+                                self.calldata.clone(),
+                                pbh_nonce,
+                                options,
+                            )
+                            .await?
+                        {
+                            UserOpSubmission::Bundler(op_hash) => Submission::UserOp(op_hash),
+                            UserOpSubmission::Transaction(sent) => Submission::Transaction(sent),
+                        },
+                    };
+                    BatchSend {
+                        pbh: Some((identity, pbh_nonce)),
+                        submission,
+                    }
+                }
+            };
+
+            if let (Some(tracker), Some(sent)) = (tracker.as_deref_mut(), send.transaction()) {
+                tracker.watch(sent.tx_hash, sent.sent_at);
+            }
+            sends.push(send);
+        }
+        Ok(sends)
+    }
+}
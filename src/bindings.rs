@@ -1,54 +1,134 @@
+use alloy_primitives::{address, Address};
 use alloy_sol_types::sol;
 
+/// Multicall3, deployed at the same address on every chain
+pub const MULTICALL3_ADDRESS: Address = address!("cA11bde05977b3631167028862bE2a173976CA11");
+
 sol! {
+    interface IMulticall3 {
+        #[derive(Debug, Default)]
+        struct Call3 {
+            address target;
+            bool allowFailure;
+            bytes callData;
+        }
+
+        #[derive(Debug, Default)]
+        struct Result {
+            bool success;
+            bytes returnData;
+        }
+
+        function aggregate3(Call3[] calldata calls) external payable returns (Result[] memory returnData);
+    }
+
+    /// ERC-4337 v0.7 packed user operation
+    #[derive(Debug, Default)]
+    struct PackedUserOperation {
+        address sender;
+        uint256 nonce;
+        bytes initCode;
+        bytes callData;
+        bytes32 accountGasLimits;
+        uint256 preVerificationGas;
+        bytes32 gasFees;
+        bytes paymasterAndData;
+        bytes signature;
+    }
+
+    #[derive(Debug, Default)]
+    struct UserOpsPerAggregator {
+        PackedUserOperation[] userOps;
+        address aggregator;
+        bytes signature;
+    }
+
+    /// PBH payload as decoded by the PBH signature aggregator
+    #[derive(Debug, Default)]
+    struct PBHPayload {
+        uint256 root;
+        uint256 pbhExternalNullifier;
+        uint256 nullifierHash;
+        uint256[8] proof;
+    }
+
     interface IPBHEntryPoint {
+        error InvalidNullifier(uint256 nullifierHash, uint256 signalHash);
+        error InvalidRoot(uint256 root);
+        error InvalidExternalNullifierVersion(uint8 version);
+        error InvalidExternalNullifierPeriod(uint8 month, uint16 year);
+        error InvalidPbhNonce(uint16 pbhNonce);
+        error ProofInvalid();
+
         function pbhMulticall(
-            tuple(address, bytes, bool)[] memory calls,
-            tuple(uint256, uint256, tuple(uint8, uint8, uint8, uint16), tuple(uint256[8],)) memory payload
-        ) external;
+            IMulticall3.Call3[] calldata calls,
+            PBHPayload calldata payload
+        ) external returns (IMulticall3.Result[] memory returnData);
         function numPbhPerMonth() external view returns (uint16);
         function nullifierHashes(uint256) external view returns (bool);
+        function worldId() external view returns (address);
     }
-}
 
-// Simplified implementation for IPBHEntryPointInstance
-pub struct IPBHEntryPointInstance<P> {
-    address: alloy_primitives::Address,
-    provider: P,
-}
+    /// World ID contract the PBH entry point verifies proofs with
+    interface IWorldID {
+        function requireValidRoot(uint256 root) external view;
+    }
 
-impl<P> IPBHEntryPointInstance<P> {
-    pub fn new(address: alloy_primitives::Address, provider: P) -> Self {
-        Self { address, provider }
+    interface IEntryPoint {
+        function handleAggregatedOps(
+            UserOpsPerAggregator[] calldata opsPerAggregator,
+            address beneficiary
+        ) external;
+        function getNonce(address sender, uint192 key) external view returns (uint256 nonce);
+        function getUserOpHash(PackedUserOperation calldata userOp) external view returns (bytes32);
     }
-    
-    pub fn numPbhPerMonth(&self) -> NumPbhPerMonthResult {
-        // For simplicity, return a hardcoded value
-        NumPbhPerMonthResult { _0: 65535 }
+
+    interface ISmartAccount {
+        function execute(address dest, uint256 value, bytes calldata func) external;
     }
-    
-    pub fn nullifierHashes(&self, _hash: alloy_primitives::U256) -> NullifierHashesResult {
-        // For simplicity, always return false (nonce not used)
-        NullifierHashesResult { _0: false }
+
+    /// OP stack L1 fee oracle predeploy
+    interface IGasPriceOracle {
+        function getL1Fee(bytes memory _data) external view returns (uint256);
+        function getL1GasUsed(bytes memory _data) external view returns (uint256);
+        function l1BaseFee() external view returns (uint256);
+        function blobBaseFee() external view returns (uint256);
+        function baseFeeScalar() external view returns (uint32);
+        function blobBaseFeeScalar() external view returns (uint32);
     }
 }
 
-pub struct NumPbhPerMonthResult {
-    pub _0: u16,
-}
+impl From<&world_chain_builder_pbh::payload::PBHPayload> for PBHPayload {
+    fn from(payload: &world_chain_builder_pbh::payload::PBHPayload) -> Self {
+        use world_chain_builder_pbh::external_nullifier::EncodedExternalNullifier;
 
-impl NumPbhPerMonthResult {
-    pub async fn call(&self) -> eyre::Result<&Self> {
-        Ok(self)
+        Self {
+            root: payload.root,
+            pbhExternalNullifier: EncodedExternalNullifier::from(payload.external_nullifier).0,
+            nullifierHash: payload.nullifier_hash,
+            proof: payload.proof.0.flatten(),
+        }
     }
 }
 
-pub struct NullifierHashesResult {
-    pub _0: bool,
-}
+sol! {
+    /// GasConsumer ABI, mirrors `contracts/GasConsumer.sol`
+    #[sol(rpc)]
+    #[derive(Debug)]
+    contract GasConsumer {
+        event Consumed(address indexed sender, uint256 indexed index, bytes32 data);
+
+        function nextSlot() external view returns (uint256);
+        function slots(uint256) external view returns (uint256);
+        function warmSlot() external view returns (uint256);
 
-impl NullifierHashesResult {
-    pub async fn call(&self) -> eyre::Result<&Self> {
-        Ok(self)
+        function consumeGas(address _address, uint256 _iterations) external;
+        function storeCold(uint256 _count) external;
+        function storeWarm(uint256 _count) external;
+        function expandMemory(uint256 _words) external pure returns (uint256);
+        function consumeCalldata(bytes calldata _data) external pure returns (uint256);
+        function emitLogs(uint256 _count) external;
+        function callEcrecover(uint256 _count) external pure returns (address signer);
+        function callSha256(uint256 _count) external pure returns (bytes32 digest);
     }
 }
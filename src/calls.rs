@@ -0,0 +1,188 @@
+use alloy_json_abi::Function;
+use alloy_primitives::{hex, Address, Bytes, TxHash};
+use alloy_provider::Provider;
+use alloy_sol_types::SolCall;
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::abi;
+use crate::bindings::{IMulticall3, IMulticall3::Call3, IPBHEntryPoint};
+use crate::rpc;
+
+/// A call of a PBH multicall, from a calls file or `--call`
+///
+/// The calldata is either encoded from `signature` and `args` or given as is in `calldata`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct CallSpec {
+    pub target: Address,
+    /// Function signature such as `transfer(address,uint256)`, optionally with `returns (...)`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub calldata: Option<Bytes>,
+    /// Whether the multicall goes on when the call reverts
+    #[serde(default)]
+    pub allow_failure: bool,
+}
+
+impl CallSpec {
+    /// Function of the signature, if the call has one
+    pub fn function(&self) -> Result<Option<Function>> {
+        self.signature.as_deref().map(abi::parse_function).transpose()
+    }
+
+    pub fn calldata(&self) -> Result<Bytes> {
+        match (&self.signature, &self.calldata) {
+            (Some(_), Some(_)) => Err(eyre::eyre!(
+                "Call to {} has both a signature and calldata",
+                self.target
+            )),
+            (Some(_), None) => {
+                let function = self.function()?.expect("signature is set");
+                abi::encode_call(&function, &self.args)
+            }
+            (None, Some(calldata)) => Ok(calldata.clone()),
+            (None, None) => Ok(Bytes::new()),
+        }
+    }
+
+    /// The call as passed to `pbhMulticall`. It has no value, the entry point forwards calls to
+    /// Multicall3's `aggregate3`, which can't send ETH with them.
+    pub fn to_call3(&self) -> Result<Call3> {
+        Ok(Call3 {
+            target: self.target,
+            allowFailure: self.allow_failure,
+            callData: self.calldata()?,
+        })
+    }
+
+    /// How the call is shown in results: the signature, or the selector of raw calldata
+    pub fn label(&self) -> String {
+        match (&self.signature, &self.calldata) {
+            (Some(signature), _) => signature.clone(),
+            (None, Some(calldata)) if calldata.len() >= 4 => hex::encode_prefixed(&calldata[..4]),
+            _ => "(no calldata)".to_string(),
+        }
+    }
+}
+
+/// Parses `[allowFailure] <target> <signature> [args...]` or `[allowFailure] <target> <calldata>`
+impl FromStr for CallSpec {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.split_whitespace().peekable();
+        let allow_failure = parts.next_if_eq(&"allowFailure").is_some();
+        let target = parts
+            .next()
+            .ok_or_else(|| eyre::eyre!("Empty call"))?
+            .parse::<Address>()?;
+
+        let mut spec = Self {
+            target,
+            signature: None,
+            args: Vec::new(),
+            calldata: None,
+            allow_failure,
+        };
+        match parts.next() {
+            Some(function) if function.contains('(') => {
+                // Signatures with `returns (...)` contain spaces, so arguments start after them
+                let rest: Vec<&str> = std::iter::once(function).chain(parts).collect();
+                let end = signature_end(&rest);
+                spec.signature = Some(rest[..end].join(" "));
+                spec.args = rest[end..].iter().map(|arg| arg.to_string()).collect();
+            }
+            Some(calldata) => {
+                spec.calldata = Some(calldata.parse()?);
+                if parts.next().is_some() {
+                    return Err(eyre::eyre!("Arguments are only allowed after a signature"));
+                }
+            }
+            None => {}
+        }
+        Ok(spec)
+    }
+}
+
+/// Number of words making up the signature at the start of `words`
+fn signature_end(words: &[&str]) -> usize {
+    let mut depth = 0i32;
+    for (i, word) in words.iter().enumerate() {
+        depth += word.matches('(').count() as i32 - word.matches(')').count() as i32;
+        let returns_follows = words.get(i + 1) == Some(&"returns");
+        if depth == 0 && !returns_follows && *word != "returns" {
+            return i + 1;
+        }
+    }
+    words.len()
+}
+
+/// Reads calls from a JSON file holding an array of calls
+pub fn load_calls(path: &Path) -> Result<Vec<CallSpec>> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|err| eyre::eyre!("Failed to read calls file {}: {}", path.display(), err))?;
+    serde_json::from_str(&contents)
+        .map_err(|err| eyre::eyre!("Invalid calls file {}: {}", path.display(), err))
+}
+
+/// Multicall3 results of an included `pbhMulticall`
+#[derive(Debug, Clone)]
+pub struct MulticallResults {
+    pub results: Vec<IMulticall3::Result>,
+    /// Whether the results come from replaying the transaction on its parent block, which
+    /// doesn't see the transactions before it in the block and can differ from what happened
+    pub replayed: bool,
+}
+
+/// Multicall3 results of an included `pbhMulticall`
+///
+/// Return data isn't part of the receipt, so it comes from `rpc::transaction_output`.
+pub async fn pbh_multicall_results(
+    provider: &Arc<dyn Provider>,
+    tx_hash: TxHash,
+) -> Result<MulticallResults> {
+    let output = rpc::transaction_output(provider, tx_hash).await?;
+    if output.error.is_some() {
+        return Err(eyre::eyre!(
+            "pbhMulticall {:?} reverted: {}",
+            tx_hash,
+            output.revert_reason()
+        ));
+    }
+
+    Ok(MulticallResults {
+        results: IPBHEntryPoint::pbhMulticallCall::abi_decode_returns(&output.output, true)?
+            .returnData,
+        replayed: output.replayed,
+    })
+}
+
+/// Outcome of one call of a multicall, with its return values if the signature declares them
+pub fn describe_result(call: &CallSpec, result: &IMulticall3::Result) -> String {
+    if !result.success {
+        if result.returnData.is_empty() {
+            return "failed".to_string();
+        }
+        return format!("failed, revert data {}", result.returnData);
+    }
+    let function = match call.function() {
+        Ok(Some(function)) => function,
+        _ if result.returnData.is_empty() => return "succeeded".to_string(),
+        _ => return format!("succeeded, returned {}", result.returnData),
+    };
+    match abi::decode_output(&function, &result.returnData) {
+        Ok(Some(values)) => {
+            let values: Vec<String> = values.iter().map(abi::format_value).collect();
+            format!("succeeded, returned ({})", values.join(", "))
+        }
+        Ok(None) => "succeeded".to_string(),
+        Err(err) => format!("succeeded, return data {} doesn't decode: {}", result.returnData, err),
+    }
+}
@@ -0,0 +1,769 @@
+use alloy_consensus::{Transaction, TxEnvelope};
+use alloy_network::eip2718::Encodable2718;
+use alloy_primitives::{aliases::U192, Address, Bytes, TxHash, B256, U256};
+use alloy_provider::Provider;
+use alloy_rpc_types_eth::{TransactionInput, TransactionReceipt, TransactionRequest};
+use alloy_signer_local::PrivateKeySigner;
+use clap::ValueEnum;
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::bindings::IMulticall3::Call3;
+use crate::conditional::{
+    send_raw_transaction_conditional, wait_for_conditional, ConditionalBounds, ConditionalOutcome,
+    TransactionConditional,
+};
+use crate::config::Network;
+use crate::deploy::{self, Deployment};
+use crate::inclusion::{Inclusion, InclusionTracker};
+use crate::l1_fee::{self, CostReport, L1FeeEstimate};
+use crate::proof::{MockProofProvider, ProofPool, ProofProvider};
+use crate::results::ResultsRecorder;
+use crate::rpc;
+use crate::send_error::{SendError, SendErrorKind};
+use crate::transaction::{multicall_signal_hash, GasTestTransactionBuilder};
+use crate::userop::{self, UserOpGas};
+use crate::world_id::WorldID;
+use world_chain_builder_pbh::payload::PBHPayload;
+
+/// Interval between receipt polls in `wait_for_receipt`
+const RECEIPT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Time `deploy` waits for the deployment to be included
+const DEPLOYMENT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Fee and gas settings applied to every transaction
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FeeSettings {
+    /// Max fee per gas in Gwei
+    pub gas_fee: Option<f64>,
+    /// Max priority fee per gas in Gwei
+    pub priority_gas_fee: Option<f64>,
+    pub gas_limit: Option<u64>,
+}
+
+/// Kind of transaction sent by the client
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TxType {
+    Direct,
+    Pbh,
+    PbhUserOp,
+}
+
+/// A transaction accepted by the RPC
+#[derive(Debug, Clone)]
+pub struct SentTransaction {
+    pub tx_hash: TxHash,
+    pub tx_type: TxType,
+    pub nonce: u64,
+    pub sent_at: Instant,
+    /// L1 data fee quoted by the GasPriceOracle before sending, when L1 fees are reported
+    pub l1_fee_estimate: Option<L1FeeEstimate>,
+    /// Times the transaction was signed again with a fresh nonce before the RPC took it
+    pub retries: u32,
+    /// Conditions it was sent with through `eth_sendRawTransactionConditional`
+    pub conditional: Option<TransactionConditional>,
+}
+
+/// Contract deployed by `GasTestClient::deploy_code`
+#[derive(Debug, Clone, Copy)]
+pub struct DeployedContract {
+    pub address: Address,
+    /// Deployment transaction, none when the contract was already at its CREATE2 address
+    pub tx_hash: Option<TxHash>,
+}
+
+/// Gas estimate of a call, with the fees the client would pay for it
+#[derive(Debug, Clone, Copy)]
+pub struct GasEstimate {
+    pub gas: u64,
+    pub max_fee_per_gas: u128,
+    pub max_priority_fee_per_gas: u128,
+}
+
+impl GasEstimate {
+    /// Upper bound of the execution cost in wei
+    pub fn max_cost(&self) -> U256 {
+        U256::from(self.gas) * U256::from(self.max_fee_per_gas)
+    }
+}
+
+/// Result of executing a call with `eth_call`
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    pub success: bool,
+    pub output: Bytes,
+    pub error: Option<String>,
+}
+
+/// Settings of a PBH user operation
+#[derive(Debug, Clone)]
+pub struct UserOpOptions {
+    pub smart_account: Address,
+    pub aggregator: Address,
+    pub entry_point: Address,
+    /// Bundler RPC URL. Without it, `handleAggregatedOps` is sent directly.
+    pub bundler_url: Option<String>,
+    pub nonce_key: U192,
+}
+
+/// How a PBH user operation was submitted
+#[derive(Debug, Clone)]
+pub enum UserOpSubmission {
+    /// Accepted by a bundler, with the user operation hash
+    Bundler(B256),
+    /// Sent as a `handleAggregatedOps` transaction
+    Transaction(SentTransaction),
+}
+
+/// What became of a batch of sent transactions, from `GasTestClient::settle`
+#[derive(Debug, Clone, Default)]
+pub struct SettledTransactions {
+    /// Inclusions seen by the inclusion tracker, when there is one
+    pub inclusions: Vec<Inclusion>,
+    /// Transactions the inclusion tracker didn't see included within the timeout
+    pub pending: usize,
+    /// Outcome of every transaction, in send order, when they were sent with conditions
+    pub conditional: Option<Vec<ConditionalOutcome>>,
+    /// Receipt of every transaction included within the timeout, in send order
+    pub receipts: Vec<Option<TransactionReceipt>>,
+    /// Cost of every included transaction, in send order, when L1 fees are reported
+    pub costs: Option<Vec<Option<CostReport>>>,
+}
+
+/// Client that sends GasConsumer workloads as direct and PBH transactions
+///
+/// Owns the provider, signer, network settings, World ID and proof provider used for every
+/// transaction. Proofs come from a `MockProofProvider` unless another one is set.
+pub struct GasTestClient {
+    provider: Arc<dyn Provider>,
+    signer: PrivateKeySigner,
+    network: Network,
+    world_id: Option<WorldID>,
+    prover: Arc<dyn ProofProvider>,
+    proof_pool: Option<Arc<ProofPool>>,
+    fees: FeeSettings,
+    l1_fees: bool,
+    max_retries: u32,
+    conditional: Option<ConditionalBounds>,
+    known_accounts: Vec<Address>,
+    results: Option<Arc<ResultsRecorder>>,
+}
+
+impl GasTestClient {
+    /// Connects to the network RPC
+    pub async fn connect(
+        network: Network,
+        signer: PrivateKeySigner,
+        world_id: Option<WorldID>,
+        fees: FeeSettings,
+    ) -> Result<Self> {
+        let provider = rpc::connect(&network.rpc_address).await?;
+        Ok(Self {
+            provider,
+            signer,
+            network,
+            world_id,
+            prover: Arc::new(MockProofProvider::default()),
+            proof_pool: None,
+            fees,
+            l1_fees: false,
+            max_retries: 0,
+            conditional: None,
+            known_accounts: Vec::new(),
+            results: None,
+        })
+    }
+
+    /// Quotes the OP stack L1 data fee of every transaction before sending it
+    pub fn with_l1_fees(self, l1_fees: bool) -> Self {
+        Self { l1_fees, ..self }
+    }
+
+    /// Retries sends rejected for a stale account nonce, see `send_raw`
+    pub fn with_max_retries(self, max_retries: u32) -> Self {
+        Self { max_retries, ..self }
+    }
+
+    /// Sends every transaction with `eth_sendRawTransactionConditional`, valid within `bounds`
+    pub fn with_conditional(self, conditional: Option<ConditionalBounds>) -> Self {
+        Self { conditional, ..self }
+    }
+
+    /// Also requires the storage roots of `known_accounts` to stay unchanged until inclusion.
+    /// Only accounts no other pending transaction writes to are worth pinning: PBH transactions
+    /// all write to the entry point, so pinning it drops every one after the first of a block.
+    pub fn with_known_accounts(self, known_accounts: Vec<Address>) -> Self {
+        Self {
+            known_accounts,
+            ..self
+        }
+    }
+
+    /// Records every sent transaction, and the receipts and inclusions the client sees of them
+    pub fn with_results(self, results: Option<Arc<ResultsRecorder>>) -> Self {
+        Self { results, ..self }
+    }
+
+    /// Sets the source of the Semaphore proofs in PBH payloads
+    pub fn with_prover(self, prover: Arc<dyn ProofProvider>) -> Self {
+        let pooled = self.proof_pool.is_some();
+        Self {
+            prover,
+            proof_pool: None,
+            ..self
+        }
+        .with_proof_pool(pooled)
+    }
+
+    /// Wraps the prover in a `ProofPool`, so payloads can be proven ahead with `prefetch_pbh`
+    pub fn with_proof_pool(self, pooled: bool) -> Self {
+        if !pooled {
+            return self;
+        }
+        let pool = Arc::new(ProofPool::new(self.prover.clone()));
+        Self {
+            prover: pool.clone(),
+            proof_pool: Some(pool),
+            ..self
+        }
+    }
+
+    pub fn provider(&self) -> &Arc<dyn Provider> {
+        &self.provider
+    }
+
+    pub fn address(&self) -> Address {
+        self.signer.address()
+    }
+
+    pub fn network(&self) -> &Network {
+        &self.network
+    }
+
+    pub fn world_id(&self) -> Result<&WorldID> {
+        self.world_id
+            .as_ref()
+            .ok_or_else(|| eyre::eyre!("PBH transactions require a World ID"))
+    }
+
+    pub fn prover(&self) -> &Arc<dyn ProofProvider> {
+        &self.prover
+    }
+
+    pub fn proof_pool(&self) -> Option<&Arc<ProofPool>> {
+        self.proof_pool.as_ref()
+    }
+
+    pub fn fees(&self) -> &FeeSettings {
+        &self.fees
+    }
+
+    /// Subscribes to new heads when connected over a pubsub transport
+    pub async fn inclusion_tracker(&self) -> Result<Option<InclusionTracker>> {
+        if !rpc::is_pubsub(&self.network.rpc_address) {
+            return Ok(None);
+        }
+        Ok(Some(InclusionTracker::subscribe(self.provider.clone()).await?))
+    }
+
+    /// Transaction builder with the configured fees and gas limit, without a nonce
+    fn fee_builder(&self) -> GasTestTransactionBuilder {
+        let tx_builder =
+            GasTestTransactionBuilder::new(self.fees.gas_fee, self.fees.priority_gas_fee, None);
+        match self.fees.gas_limit {
+            Some(gas_limit) => tx_builder.gas_limit(gas_limit),
+            None => tx_builder,
+        }
+    }
+
+    /// Transaction builder with the configured fees and the signer's next nonce
+    pub async fn tx_builder(&self) -> Result<GasTestTransactionBuilder> {
+        // Count pending transactions so consecutive sends get consecutive nonces
+        let account_nonce = self
+            .provider
+            .get_transaction_count(self.address())
+            .pending()
+            .await?;
+        Ok(self.fee_builder().nonce(account_nonce))
+    }
+
+    /// Builds a signed transaction calling the GasConsumer contract directly
+    pub async fn build_direct(&self, calldata: Bytes) -> Result<TxEnvelope> {
+        self.tx_builder()
+            .await?
+            .to(self.network.contract_address)
+            .input(TransactionInput::new(calldata))
+            .build(self.signer.clone())
+            .await
+    }
+
+    /// Builds a signed transaction calling any contract. Without a configured gas limit, the
+    /// gas estimate of the call is used instead of the GasConsumer default.
+    pub async fn build_call(&self, to: Address, calldata: Bytes, value: U256) -> Result<TxEnvelope> {
+        let mut tx_builder = self
+            .tx_builder()
+            .await?
+            .to(to)
+            .input(TransactionInput::new(calldata.clone()))
+            .value(value);
+        if self.fees.gas_limit.is_none() {
+            let estimate = self.estimate(to, calldata, value).await?;
+            tx_builder = tx_builder.gas_limit(estimate.gas);
+        }
+        tx_builder.build(self.signer.clone()).await
+    }
+
+    /// Builds a signed `pbhMulticall` transaction to the PBH entry point
+    pub async fn build_pbh(&self, calls: Vec<Call3>, pbh_nonce: u16) -> Result<TxEnvelope> {
+        self.build_pbh_as(self.world_id()?, calls, pbh_nonce).await
+    }
+
+    /// Same as `build_pbh`, proving with another identity than the client's
+    pub async fn build_pbh_as(
+        &self,
+        world_id: &WorldID,
+        calls: Vec<Call3>,
+        pbh_nonce: u16,
+    ) -> Result<TxEnvelope> {
+        self.tx_builder()
+            .await?
+            .to(self.network.pbh_entry_point)
+            .with_pbh_multicall(
+                world_id,
+                self.prover.as_ref(),
+                pbh_nonce,
+                self.address(),
+                calls,
+            )
+            .await?
+            .build(self.signer.clone())
+            .await
+    }
+
+    /// Builds a signed `pbhMulticall` transaction with a payload built by the caller
+    pub async fn build_pbh_with_payload(
+        &self,
+        calls: Vec<Call3>,
+        pbh_payload: &PBHPayload,
+    ) -> Result<TxEnvelope> {
+        self.tx_builder()
+            .await?
+            .to(self.network.pbh_entry_point)
+            .with_pbh_payload(calls, pbh_payload)
+            .build(self.signer.clone())
+            .await
+    }
+
+    /// Starts proving the payload of a later `send_pbh` with the same calls and PBH nonce.
+    /// Requires the client's proof pool, see `with_proof_pool`.
+    pub fn prefetch_pbh(&self, calls: &[Call3], pbh_nonce: u16) -> Result<()> {
+        self.prefetch_pbh_as(self.world_id()?, calls, pbh_nonce)
+    }
+
+    /// Same as `prefetch_pbh`, proving with another identity than the client's
+    pub fn prefetch_pbh_as(&self, world_id: &WorldID, calls: &[Call3], pbh_nonce: u16) -> Result<()> {
+        let pool = self
+            .proof_pool
+            .as_ref()
+            .ok_or_else(|| eyre::eyre!("Prefetching proofs requires a proof pool"))?;
+        pool.prefetch(
+            world_id,
+            pbh_nonce,
+            multicall_signal_hash(self.address(), calls),
+        )
+    }
+
+    /// Sends a transaction calling the GasConsumer contract directly
+    pub async fn send_direct(&self, calldata: Bytes) -> Result<SentTransaction> {
+        let tx = self.build_direct(calldata).await?;
+        self.send_raw(&tx, TxType::Direct).await
+    }
+
+    /// Sends a transaction calling any contract
+    pub async fn send_call(&self, to: Address, calldata: Bytes, value: U256) -> Result<SentTransaction> {
+        let tx = self.build_call(to, calldata, value).await?;
+        self.send_raw(&tx, TxType::Direct).await
+    }
+
+    /// Sends a `pbhMulticall` transaction to the PBH entry point
+    pub async fn send_pbh(&self, calls: Vec<Call3>, pbh_nonce: u16) -> Result<SentTransaction> {
+        self.send_pbh_as(self.world_id()?, calls, pbh_nonce).await
+    }
+
+    /// Same as `send_pbh`, proving with another identity than the client's
+    pub async fn send_pbh_as(
+        &self,
+        world_id: &WorldID,
+        calls: Vec<Call3>,
+        pbh_nonce: u16,
+    ) -> Result<SentTransaction> {
+        let tx = self.build_pbh_as(world_id, calls, pbh_nonce).await?;
+        self.send_raw(&tx, TxType::Pbh).await
+    }
+
+    /// Sends a PBH user operation in which the smart account calls the GasConsumer contract
+    pub async fn send_pbh_user_op(
+        &self,
+        calldata: Bytes,
+        pbh_nonce: u16,
+        options: &UserOpOptions,
+    ) -> Result<UserOpSubmission> {
+        self.send_pbh_user_op_as(self.world_id()?, calldata, pbh_nonce, options)
+            .await
+    }
+
+    /// Same as `send_pbh_user_op`, proving with another identity than the client's
+    pub async fn send_pbh_user_op_as(
+        &self,
+        world_id: &WorldID,
+        calldata: Bytes,
+        pbh_nonce: u16,
+        options: &UserOpOptions,
+    ) -> Result<UserOpSubmission> {
+        let gas = UserOpGas::new(self.fees.gas_fee, self.fees.priority_gas_fee);
+        let call_data =
+            userop::execute_calldata(self.network.contract_address, U256::ZERO, calldata);
+        let (op, payload) = userop::build_pbh_user_op(
+            &self.provider,
+            world_id,
+            self.prover.as_ref(),
+            pbh_nonce,
+            options.entry_point,
+            options.smart_account,
+            options.nonce_key,
+            call_data,
+            gas,
+            &self.signer,
+        )
+        .await?;
+
+        // Hand the operation to a bundler if one is configured
+        if let Some(bundler_url) = options.bundler_url.as_deref() {
+            let op_hash =
+                userop::send_to_bundler(bundler_url, options.entry_point, &op, &gas, options.aggregator)
+                    .await?;
+            return Ok(UserOpSubmission::Bundler(op_hash));
+        }
+
+        // Otherwise bundle it ourselves through handleAggregatedOps
+        let calldata =
+            userop::handle_aggregated_ops_calldata(vec![(op, payload)], options.aggregator, self.address());
+        let tx = self
+            .tx_builder()
+            .await?
+            .to(self.network.pbh_entry_point)
+            .gas_limit(self.fees.gas_limit.unwrap_or(1_000_000))
+            .input(TransactionInput::new(calldata))
+            .build(self.signer.clone())
+            .await?;
+        Ok(UserOpSubmission::Transaction(
+            self.send_raw(&tx, TxType::PbhUserOp).await?,
+        ))
+    }
+
+    /// Sends a signed transaction
+    ///
+    /// RPC errors are returned as `SendError`. The ones that only need a fresh account nonce are
+    /// retried up to `max_retries` times, with the same transaction signed again unless the node
+    /// already has it. With conditional bounds set, conditions are derived from the current block
+    /// and the known accounts before the first attempt. Accepted transactions are recorded in the
+    /// results database when a recorder is set.
+    pub async fn send_raw(&self, tx: &TxEnvelope, tx_type: TxType) -> Result<SentTransaction> {
+        let l1_fee_estimate = if self.l1_fees {
+            Some(self.estimate_l1_fee(tx).await?)
+        } else {
+            None
+        };
+
+        let conditional = match self.conditional {
+            Some(bounds) => Some(
+                TransactionConditional::derive(&self.provider, bounds, &self.known_accounts).await?,
+            ),
+            None => None,
+        };
+
+        let mut tx = tx.clone();
+        let mut retries = 0;
+        loop {
+            let sent_at = Instant::now();
+            let sent = |tx_hash| {
+                let sent = SentTransaction {
+                    tx_hash,
+                    tx_type,
+                    nonce: tx.nonce(),
+                    sent_at,
+                    l1_fee_estimate,
+                    retries,
+                    conditional: conditional.clone(),
+                };
+                self.record(|results| results.record_sent(&tx, &sent));
+                sent
+            };
+            let encoded = tx.encoded_2718();
+            let result = match &conditional {
+                Some(conditional) => {
+                    send_raw_transaction_conditional(&self.provider, encoded.into(), conditional).await
+                }
+                None => self
+                    .provider
+                    .send_raw_transaction(&encoded)
+                    .await
+                    .map(|pending_tx| *pending_tx.tx_hash()),
+            };
+            let err = match result {
+                Ok(tx_hash) => return Ok(sent(tx_hash)),
+                Err(err) => err,
+            };
+
+            let Some(send_error) = SendError::from_rpc(&err) else {
+                return Err(err.into());
+            };
+            if send_error.kind == SendErrorKind::AlreadyKnown {
+                return Ok(sent(*tx.tx_hash()));
+            }
+            if !send_error.kind.is_retryable() {
+                return Err(send_error.into());
+            }
+            // The nonce may be taken by this very transaction, e.g. when an earlier attempt got
+            // through after timing out. Signing it again would send it twice.
+            if self
+                .provider
+                .get_transaction_by_hash(*tx.tx_hash())
+                .await?
+                .is_some()
+            {
+                return Ok(sent(*tx.tx_hash()));
+            }
+            if retries >= self.max_retries {
+                return Err(send_error.into());
+            }
+            retries += 1;
+            tx = self.resign(&tx).await?;
+        }
+    }
+
+    /// Waits until a transaction sent with conditions is included or dropped
+    pub async fn wait_for_conditional(
+        &self,
+        sent: &SentTransaction,
+        timeout: Duration,
+    ) -> Result<ConditionalOutcome> {
+        let conditional = sent
+            .conditional
+            .as_ref()
+            .ok_or_else(|| eyre::eyre!("Transaction {:?} was sent without conditions", sent.tx_hash))?;
+        let outcome =
+            wait_for_conditional(&self.provider, sent.tx_hash, conditional, timeout).await?;
+        if let ConditionalOutcome::Included(receipt) = &outcome {
+            self.record(|results| results.record_receipt(receipt));
+        }
+        Ok(outcome)
+    }
+
+    /// Waits for sent transactions under one timeout and gathers what became of them: their
+    /// inclusions if `tracker` watched them, the outcome of their conditions if they were sent
+    /// with some, their receipts and, when L1 fees are reported, their costs
+    pub async fn settle(
+        &self,
+        mut tracker: Option<InclusionTracker>,
+        sent_txs: &[SentTransaction],
+        timeout: Duration,
+    ) -> Result<SettledTransactions> {
+        let mut settled = SettledTransactions::default();
+        if let Some(tracker) = tracker.as_mut() {
+            settled.inclusions = tracker.wait(timeout).await?;
+            self.record_inclusions(&settled.inclusions);
+            settled.pending = tracker.pending();
+        }
+
+        if self.conditional.is_some() {
+            let mut outcomes = Vec::with_capacity(sent_txs.len());
+            for sent in sent_txs {
+                let outcome = self.wait_for_conditional(sent, timeout).await?;
+                settled.receipts.push(match &outcome {
+                    ConditionalOutcome::Included(receipt) => Some(receipt.clone()),
+                    _ => None,
+                });
+                outcomes.push(outcome);
+            }
+            settled.conditional = Some(outcomes);
+        } else {
+            // One deadline for all of them, so unincluded transactions don't add up their timeouts
+            let deadline = Instant::now() + timeout;
+            for sent in sent_txs {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                settled
+                    .receipts
+                    .push(self.wait_for_receipt(sent.tx_hash, remaining).await.ok());
+            }
+        }
+
+        if self.l1_fees {
+            let mut costs = Vec::with_capacity(settled.receipts.len());
+            for receipt in &settled.receipts {
+                costs.push(match receipt {
+                    Some(receipt) => Some(self.cost_report(receipt).await?),
+                    None => None,
+                });
+            }
+            settled.costs = Some(costs);
+        }
+        Ok(settled)
+    }
+
+    /// Records the inclusion timings of sent transactions
+    pub fn record_inclusions(&self, inclusions: &[Inclusion]) {
+        for inclusion in inclusions {
+            self.record(|results| results.record_inclusion(inclusion));
+        }
+    }
+
+    /// Failing to record results doesn't fail the send, it only leaves a gap in the database
+    fn record(&self, record: impl FnOnce(&ResultsRecorder) -> Result<()>) {
+        if let Some(results) = &self.results {
+            if let Err(err) = record(results) {
+                tracing::warn!(
+                    "Failed to record results in {}: {}",
+                    results.path().display(),
+                    err
+                );
+            }
+        }
+    }
+
+    /// Signs a transaction again with the next free account nonce
+    async fn resign(&self, tx: &TxEnvelope) -> Result<TxEnvelope> {
+        let pending_nonce = self
+            .provider
+            .get_transaction_count(self.address())
+            .pending()
+            .await?;
+        let tx_builder = GasTestTransactionBuilder {
+            tx: TransactionRequest::from(tx.clone()),
+            provider: None,
+        };
+        tx_builder
+            .nonce(pending_nonce.max(tx.nonce() + 1))
+            .build(self.signer.clone())
+            .await
+    }
+
+    /// Quotes the OP stack L1 data fee of a signed transaction from the GasPriceOracle. The oracle
+    /// is given the transaction without its signature and adds the size of one itself.
+    pub async fn estimate_l1_fee(&self, tx: &TxEnvelope) -> Result<L1FeeEstimate> {
+        l1_fee::estimate_l1_fee(&self.provider, &l1_fee::unsigned_encoding(tx)?).await
+    }
+
+    /// Splits the cost of an included transaction into L2 execution and L1 data
+    pub async fn cost_report(&self, receipt: &TransactionReceipt) -> Result<CostReport> {
+        let l1 = l1_fee::op_receipt_fields(&self.provider, receipt.transaction_hash).await?;
+        self.record(|results| results.record_l1_fee(receipt.transaction_hash, &l1));
+        Ok(CostReport::new(receipt, l1))
+    }
+
+    /// Estimates the gas of a call from the signer
+    pub async fn estimate(&self, to: Address, input: Bytes, value: U256) -> Result<GasEstimate> {
+        let tx = self.fee_builder().tx;
+        let request = self.call_request(to, input, value);
+        let gas = self.provider.estimate_gas(&request).await?;
+
+        Ok(GasEstimate {
+            gas,
+            max_fee_per_gas: tx.max_fee_per_gas.unwrap_or_default(),
+            max_priority_fee_per_gas: tx.max_priority_fee_per_gas.unwrap_or_default(),
+        })
+    }
+
+    /// Executes a call from the signer with `eth_call` without sending it
+    pub async fn simulate(&self, to: Address, input: Bytes, value: U256) -> Result<Simulation> {
+        let request = self.call_request(to, input, value);
+        Ok(match self.provider.call(&request).await {
+            Ok(output) => Simulation {
+                success: true,
+                output,
+                error: None,
+            },
+            Err(err) => Simulation {
+                success: false,
+                output: Bytes::new(),
+                error: Some(err.to_string()),
+            },
+        })
+    }
+
+    /// Waits for the receipt of a sent transaction
+    pub async fn wait_for_receipt(
+        &self,
+        tx_hash: TxHash,
+        timeout: Duration,
+    ) -> Result<TransactionReceipt> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(receipt) = self.provider.get_transaction_receipt(tx_hash).await? {
+                self.record(|results| results.record_receipt(&receipt));
+                return Ok(receipt);
+            }
+            if Instant::now() >= deadline {
+                return Err(eyre::eyre!(
+                    "Transaction {:?} not included within {}s",
+                    tx_hash,
+                    timeout.as_secs()
+                ));
+            }
+            tokio::time::sleep(RECEIPT_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Deploys the GasConsumer contract, with CREATE2 when a salt is given
+    pub async fn deploy(&self, salt: Option<B256>) -> Result<DeployedContract> {
+        self.deploy_code(deploy::gas_consumer_bytecode()?, salt).await
+    }
+
+    /// Deploys any init code, with CREATE2 when a salt is given. The deployment goes through
+    /// `send_raw` like every other transaction.
+    pub async fn deploy_code(
+        &self,
+        init_code: Bytes,
+        salt: Option<B256>,
+    ) -> Result<DeployedContract> {
+        let tx_builder = self.tx_builder().await?;
+        let tx = match deploy::deployment(
+            &self.provider,
+            self.signer.clone(),
+            tx_builder,
+            &init_code,
+            salt,
+        )
+        .await?
+        {
+            Deployment::Deployed(address) => {
+                return Ok(DeployedContract {
+                    address,
+                    tx_hash: None,
+                })
+            }
+            Deployment::Transaction(tx) => tx,
+        };
+
+        let sent = self.send_raw(&tx, TxType::Direct).await?;
+        tracing::info!("Deployment sent: {:?}", sent.tx_hash);
+        let receipt = self.wait_for_receipt(sent.tx_hash, DEPLOYMENT_TIMEOUT).await?;
+        Ok(DeployedContract {
+            address: deploy::deployed_address(salt, &init_code, &receipt)?,
+            tx_hash: Some(sent.tx_hash),
+        })
+    }
+
+    fn call_request(&self, to: Address, input: Bytes, value: U256) -> TransactionRequest {
+        TransactionRequest::default()
+            .from(self.address())
+            .to(to)
+            .input(TransactionInput::new(input))
+            .value(value)
+    }
+}
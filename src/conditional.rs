@@ -0,0 +1,200 @@
+use alloy_primitives::{Address, Bytes, TxHash, B256, U64};
+use alloy_provider::Provider;
+use alloy_rpc_types_eth::TransactionReceipt;
+use alloy_transport::TransportResult;
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::Instant;
+
+/// Interval between checks in `wait_for_conditional`
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Expected storage of a known account, either the whole storage root or single slots
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum AccountStorage {
+    RootHash(B256),
+    Slots(BTreeMap<B256, B256>),
+}
+
+/// Conditions of `eth_sendRawTransactionConditional`. The transaction is only included while
+/// all of them hold and is dropped once one fails.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionConditional {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub known_accounts: BTreeMap<Address, AccountStorage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_number_min: Option<U64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_number_max: Option<U64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp_min: Option<U64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp_max: Option<U64>,
+}
+
+/// How far ahead the conditions of a transaction reach
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConditionalBounds {
+    /// Blocks after the current one the transaction may be included in
+    pub blocks: u64,
+    /// Seconds from now the transaction may be included for
+    pub seconds: u64,
+}
+
+impl TransactionConditional {
+    /// Conditions from the current block and time to `bounds`, requiring the storage roots of
+    /// `known_accounts` to stay as they are now
+    pub async fn derive(
+        provider: &Arc<dyn Provider>,
+        bounds: ConditionalBounds,
+        known_accounts: &[Address],
+    ) -> Result<Self> {
+        let block_number = provider.get_block_number().await?;
+        let now = unix_time()?;
+        let mut storage = BTreeMap::new();
+        for &address in known_accounts {
+            let root = storage_root(provider, address).await?;
+            storage.insert(address, AccountStorage::RootHash(root));
+        }
+
+        Ok(Self {
+            known_accounts: storage,
+            block_number_min: Some(U64::from(block_number)),
+            block_number_max: Some(U64::from(block_number + bounds.blocks)),
+            timestamp_min: None,
+            timestamp_max: Some(U64::from(now + bounds.seconds)),
+        })
+    }
+
+    /// First condition that no longer holds, if any
+    pub async fn check(&self, provider: &Arc<dyn Provider>) -> Result<Option<ConditionFailure>> {
+        if let Some(max) = self.block_number_max {
+            let current = provider.get_block_number().await?;
+            if current > max.to::<u64>() {
+                return Ok(Some(ConditionFailure::BlockNumber {
+                    max: max.to(),
+                    current,
+                }));
+            }
+        }
+        if let Some(max) = self.timestamp_max {
+            let current = unix_time()?;
+            if current > max.to::<u64>() {
+                return Ok(Some(ConditionFailure::Timestamp {
+                    max: max.to(),
+                    current,
+                }));
+            }
+        }
+        for (&address, storage) in &self.known_accounts {
+            // Only storage roots are derived, single slots are left to the node
+            if let AccountStorage::RootHash(expected) = storage {
+                let current = storage_root(provider, address).await?;
+                if current != *expected {
+                    return Ok(Some(ConditionFailure::StorageRoot {
+                        address,
+                        expected: *expected,
+                        current,
+                    }));
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Condition that failed before a conditional transaction was included
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConditionFailure {
+    BlockNumber { max: u64, current: u64 },
+    Timestamp { max: u64, current: u64 },
+    StorageRoot {
+        address: Address,
+        expected: B256,
+        current: B256,
+    },
+}
+
+impl fmt::Display for ConditionFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BlockNumber { max, current } => {
+                write!(f, "block {} is past blockNumberMax {}", current, max)
+            }
+            Self::Timestamp { max, current } => {
+                write!(f, "time {} is past timestampMax {}", current, max)
+            }
+            Self::StorageRoot {
+                address,
+                expected,
+                current,
+            } => write!(
+                f,
+                "storage root of {} changed from {} to {}",
+                address, expected, current
+            ),
+        }
+    }
+}
+
+/// What became of a conditional transaction
+#[derive(Debug, Clone)]
+pub enum ConditionalOutcome {
+    Included(TransactionReceipt),
+    /// Not included and one of its conditions failed, so it can't be anymore
+    Dropped(ConditionFailure),
+    /// Neither included nor dropped within the timeout
+    Pending,
+}
+
+/// Sends a signed, EIP-2718 encoded transaction with `eth_sendRawTransactionConditional`
+pub async fn send_raw_transaction_conditional(
+    provider: &Arc<dyn Provider>,
+    encoded_tx: Bytes,
+    conditional: &TransactionConditional,
+) -> TransportResult<TxHash> {
+    provider
+        .raw_request("eth_sendRawTransactionConditional".into(), (encoded_tx, conditional))
+        .await
+}
+
+/// Waits until a conditional transaction is included or one of its conditions fails
+pub async fn wait_for_conditional(
+    provider: &Arc<dyn Provider>,
+    tx_hash: TxHash,
+    conditional: &TransactionConditional,
+    timeout: Duration,
+) -> Result<ConditionalOutcome> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(receipt) = provider.get_transaction_receipt(tx_hash).await? {
+            return Ok(ConditionalOutcome::Included(receipt));
+        }
+        if let Some(failure) = conditional.check(provider).await? {
+            // The including block may have landed between the two requests
+            if let Some(receipt) = provider.get_transaction_receipt(tx_hash).await? {
+                return Ok(ConditionalOutcome::Included(receipt));
+            }
+            return Ok(ConditionalOutcome::Dropped(failure));
+        }
+        if Instant::now() >= deadline {
+            return Ok(ConditionalOutcome::Pending);
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Storage root of an account from `eth_getProof`
+pub async fn storage_root(provider: &Arc<dyn Provider>, address: Address) -> Result<B256> {
+    Ok(provider.get_proof(address, Vec::new()).await?.storage_hash)
+}
+
+fn unix_time() -> Result<u64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
@@ -0,0 +1,412 @@
+use alloy_primitives::{address, Address};
+use eyre::Result;
+use reqwest::Url;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::{value, DocumentMut};
+
+use crate::world_id::WorldID;
+
+/// PBH entry point used when no layer sets one
+pub const DEFAULT_PBH_ENTRY_POINT: Address = address!("6e37bAB9d23bd8Bdb42b773C58ae43C6De43A590");
+
+/// Prefix of the environment variables overriding the config file
+pub const ENV_PREFIX: &str = "GASTEST_";
+
+/// Where a configuration value came from. Later layers take precedence: defaults, then the
+/// config file and its network profile, then `GASTEST_*` variables, then command line flags.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    Default,
+    File,
+    /// Network profile of the config file
+    Network(String),
+    Env,
+    Cli,
+}
+
+/// A configuration value with the layer that set it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Setting<T> {
+    pub value: T,
+    pub source: ConfigSource,
+}
+
+/// Values of one configuration layer, unset ones fall through to the layer below
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct ConfigLayer {
+    pub contract_address: Option<String>,
+    pub world_id: Option<String>,
+    /// Additional identities PBH transactions are spread over
+    pub world_ids: Option<Vec<String>>,
+    pub rpc_address: Option<String>,
+    pub pbh_entry_point: Option<String>,
+    /// Named network profiles, selected with `--network`. Only read from the config file.
+    #[serde(default)]
+    pub networks: HashMap<String, NetworkProfile>,
+}
+
+/// Per-network overrides of the top level configuration
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct NetworkProfile {
+    pub contract_address: Option<String>,
+    pub rpc_address: Option<String>,
+    pub pbh_entry_point: Option<String>,
+}
+
+impl ConfigLayer {
+    /// Reads the layer of a TOML config file, `None` if the file doesn't exist
+    pub fn from_file(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(path)?;
+        let layer = toml::from_str(&content)
+            .map_err(|err| eyre::eyre!("Invalid config file {}: {}", path.display(), err))?;
+        Ok(Some(layer))
+    }
+
+    /// Reads the `GASTEST_*` variables of the process environment
+    pub fn from_env() -> Self {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    /// Reads `GASTEST_*` variables with `var`. `GASTEST_WORLD_IDS` is a comma separated list.
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        let get = |key: &str| var(&env_var(key)).filter(|value| !value.is_empty());
+        Self {
+            contract_address: get("contract_address"),
+            world_id: get("world_id"),
+            world_ids: get("world_ids").map(|secrets| {
+                secrets
+                    .split(',')
+                    .map(|secret| secret.trim().to_string())
+                    .filter(|secret| !secret.is_empty())
+                    .collect()
+            }),
+            rpc_address: get("rpc_address"),
+            pbh_entry_point: get("pbh_entry_point"),
+            networks: HashMap::new(),
+        }
+    }
+
+    fn defaults() -> Self {
+        Self {
+            pbh_entry_point: Some(DEFAULT_PBH_ENTRY_POINT.to_string()),
+            ..Self::default()
+        }
+    }
+}
+
+impl From<NetworkProfile> for ConfigLayer {
+    fn from(profile: NetworkProfile) -> Self {
+        Self {
+            contract_address: profile.contract_address,
+            rpc_address: profile.rpc_address,
+            pbh_entry_point: profile.pbh_entry_point,
+            ..Self::default()
+        }
+    }
+}
+
+/// Configuration resolved from all layers and validated
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Path of the config file, whether or not it exists
+    pub path: PathBuf,
+    pub file_found: bool,
+    /// Name of the selected network profile, if any
+    pub network: Option<String>,
+    pub rpc_address: Option<Setting<String>>,
+    pub contract_address: Option<Setting<Address>>,
+    pub pbh_entry_point: Setting<Address>,
+    pub world_id: Option<Setting<String>>,
+    pub world_ids: Option<Setting<Vec<String>>>,
+}
+
+/// Resolved settings of the network the tool runs against
+#[derive(Debug, Clone)]
+pub struct Network {
+    /// Name of the selected network profile, if any
+    pub name: Option<String>,
+    pub rpc_address: String,
+    pub contract_address: Address,
+    pub pbh_entry_point: Address,
+}
+
+impl Config {
+    /// Resolves the configuration from the defaults, the config file at `path` if it exists
+    /// with its `network` profile, the `GASTEST_*` variables in `env` and the flags in `cli`
+    pub fn load(
+        path: &Path,
+        network: Option<&str>,
+        env: ConfigLayer,
+        cli: ConfigLayer,
+    ) -> Result<Self> {
+        let mut file = ConfigLayer::from_file(path)?;
+        let profile = match network {
+            Some(name) => Some(
+                file.as_mut()
+                    .and_then(|file| file.networks.remove(name))
+                    .ok_or_else(|| eyre::eyre!("Unknown network profile: {}", name))?,
+            ),
+            None => None,
+        };
+
+        let mut layers = vec![(ConfigSource::Default, ConfigLayer::defaults())];
+        if let Some(file) = &file {
+            layers.push((ConfigSource::File, file.clone()));
+        }
+        if let (Some(name), Some(profile)) = (network, profile) {
+            layers.push((ConfigSource::Network(name.to_string()), profile.into()));
+        }
+        layers.push((ConfigSource::Env, env));
+        layers.push((ConfigSource::Cli, cli));
+
+        let contract_address = resolve(&layers, |layer| layer.contract_address.clone())
+            .map(|setting| parse_address("contract_address", setting))
+            .transpose()?;
+        let pbh_entry_point = resolve(&layers, |layer| layer.pbh_entry_point.clone())
+            .map(|setting| parse_address("pbh_entry_point", setting))
+            .transpose()?
+            .expect("the defaults set pbh_entry_point");
+        let rpc_address = resolve(&layers, |layer| layer.rpc_address.clone())
+            .map(|setting| check_url("rpc_address", setting))
+            .transpose()?;
+        let world_id = resolve(&layers, |layer| layer.world_id.clone());
+        let world_ids = resolve(&layers, |layer| layer.world_ids.clone());
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            file_found: file.is_some(),
+            network: network.map(str::to_string),
+            rpc_address,
+            contract_address,
+            pbh_entry_point,
+            world_id,
+            world_ids,
+        })
+    }
+
+    pub fn rpc_address(&self) -> Result<&str> {
+        self.rpc_address
+            .as_ref()
+            .map(|setting| setting.value.as_str())
+            .ok_or_else(|| missing("rpc_address", Some("--provider-uri")))
+    }
+
+    pub fn contract_address(&self) -> Result<Address> {
+        self.contract_address
+            .as_ref()
+            .map(|setting| setting.value)
+            .ok_or_else(|| missing("contract_address", Some("--contract-address")))
+    }
+
+    /// Secret of the main identity
+    pub fn world_id(&self) -> Result<&str> {
+        self.world_id
+            .as_ref()
+            .map(|setting| setting.value.as_str())
+            .ok_or_else(|| missing("world_id", None))
+    }
+
+    /// All configured identities, `world_id` first
+    pub fn identities(&self) -> Result<Vec<WorldID>> {
+        let world_id = self.world_id()?;
+        let others = self.world_ids.iter().flat_map(|setting| &setting.value);
+        std::iter::once(world_id)
+            .chain(others.map(String::as_str))
+            .map(WorldID::new)
+            .collect()
+    }
+
+    /// Resolves the network settings
+    pub fn network(&self) -> Result<Network> {
+        Ok(Network {
+            name: self.network.clone(),
+            rpc_address: self.rpc_address()?.to_string(),
+            contract_address: self.contract_address()?,
+            pbh_entry_point: self.pbh_entry_point.value,
+        })
+    }
+
+    /// Every key with its effective value and source, as printed by `config show`.
+    /// Secrets are shown as their identity commitments.
+    pub fn entries(&self) -> Vec<(&'static str, String, String)> {
+        let commitment = |secret: &str| match WorldID::new(secret) {
+            Ok(world_id) => world_id.identity().commitment().to_string(),
+            Err(_) => "(invalid)".to_string(),
+        };
+        let entry = |key: &'static str, value: Option<(String, &ConfigSource)>| match value {
+            Some((value, source)) => (key, value, source.describe(key)),
+            None => (key, "(unset)".to_string(), "-".to_string()),
+        };
+
+        vec![
+            entry(
+                "rpc_address",
+                self.rpc_address
+                    .as_ref()
+                    .map(|s| (s.value.clone(), &s.source)),
+            ),
+            entry(
+                "contract_address",
+                self.contract_address
+                    .as_ref()
+                    .map(|s| (s.value.to_string(), &s.source)),
+            ),
+            entry(
+                "pbh_entry_point",
+                Some((
+                    self.pbh_entry_point.value.to_string(),
+                    &self.pbh_entry_point.source,
+                )),
+            ),
+            entry(
+                "world_id",
+                self.world_id
+                    .as_ref()
+                    .map(|s| (format!("commitment {}", commitment(&s.value)), &s.source)),
+            ),
+            entry(
+                "world_ids",
+                self.world_ids.as_ref().map(|s| {
+                    let commitments: Vec<String> =
+                        s.value.iter().map(|secret| commitment(secret)).collect();
+                    (
+                        format!("commitments [{}]", commitments.join(", ")),
+                        &s.source,
+                    )
+                }),
+            ),
+        ]
+    }
+}
+
+impl ConfigSource {
+    /// Where `key` was set, e.g. the environment variable or flag name
+    pub fn describe(&self, key: &str) -> String {
+        match self {
+            Self::Default => "default".to_string(),
+            Self::File => "config file".to_string(),
+            Self::Network(name) => format!("config file, network {}", name),
+            Self::Env => env_var(key),
+            Self::Cli => cli_flag(key).unwrap_or("command line").to_string(),
+        }
+    }
+}
+
+/// Environment variable overriding `key`
+pub fn env_var(key: &str) -> String {
+    format!("{}{}", ENV_PREFIX, key.to_ascii_uppercase())
+}
+
+/// Command line flag overriding `key`, if there is one
+fn cli_flag(key: &str) -> Option<&'static str> {
+    match key {
+        "rpc_address" => Some("--provider-uri"),
+        "contract_address" => Some("--contract-address"),
+        "pbh_entry_point" => Some("--pbh-entry-point"),
+        _ => None,
+    }
+}
+
+/// Value of the highest layer that sets it
+fn resolve<T>(
+    layers: &[(ConfigSource, ConfigLayer)],
+    get: impl Fn(&ConfigLayer) -> Option<T>,
+) -> Option<Setting<T>> {
+    layers.iter().rev().find_map(|(source, layer)| {
+        get(layer).map(|value| Setting {
+            value,
+            source: source.clone(),
+        })
+    })
+}
+
+fn parse_address(key: &str, setting: Setting<String>) -> Result<Setting<Address>> {
+    let value = setting.value.parse::<Address>().map_err(|err| {
+        eyre::eyre!(
+            "Invalid {} `{}` from {}: {}",
+            key,
+            setting.value,
+            setting.source.describe(key),
+            err
+        )
+    })?;
+    Ok(Setting {
+        value,
+        source: setting.source,
+    })
+}
+
+/// Checks that an RPC URL parses and has a scheme the provider can connect with
+fn check_url(key: &str, setting: Setting<String>) -> Result<Setting<String>> {
+    let invalid = |reason: String| {
+        eyre::eyre!(
+            "Invalid {} `{}` from {}: {}",
+            key,
+            setting.value,
+            setting.source.describe(key),
+            reason
+        )
+    };
+    let url = Url::parse(&setting.value).map_err(|err| invalid(err.to_string()))?;
+    if !matches!(url.scheme(), "http" | "https" | "ws" | "wss") {
+        return Err(invalid(format!("unsupported scheme {}", url.scheme())));
+    }
+    Ok(setting)
+}
+
+fn missing(key: &str, flag: Option<&str>) -> eyre::Report {
+    match flag {
+        Some(flag) => eyre::eyre!(
+            "{} is not set, set it in the config file, with {} or with {}",
+            key,
+            env_var(key),
+            flag
+        ),
+        None => eyre::eyre!(
+            "{} is not set, set it in the config file or with {}",
+            key,
+            env_var(key)
+        ),
+    }
+}
+
+/// Writes the World ID secret into the configuration file, creating it if needed.
+/// Comments and formatting are preserved.
+pub fn write_world_id(path: &Path, secret: &str) -> Result<()> {
+    let content = if path.exists() {
+        fs::read_to_string(path)?
+    } else {
+        String::new()
+    };
+    let mut doc = content.parse::<DocumentMut>()?;
+    doc["world_id"] = value(secret);
+
+    fs::write(path, doc.to_string())?;
+    Ok(())
+}
+
+/// Writes a contract address back into the configuration file, under the selected
+/// network profile if there is one, creating the file if needed. Comments and formatting
+/// are preserved.
+pub fn write_contract_address(path: &Path, network: Option<&str>, address: Address) -> Result<()> {
+    let content = if path.exists() {
+        fs::read_to_string(path)?
+    } else {
+        String::new()
+    };
+    let mut doc = content.parse::<DocumentMut>()?;
+
+    match network {
+        Some(network) => doc["networks"][network]["contract_address"] = value(address.to_string()),
+        None => doc["contract_address"] = value(address.to_string()),
+    }
+
+    fs::write(path, doc.to_string())?;
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use alloy_consensus::TxEnvelope;
+use alloy_primitives::{address, Address, Bytes, B256};
+use alloy_provider::Provider;
+use alloy_rpc_types_eth::{TransactionInput, TransactionReceipt};
+use alloy_signer_local::PrivateKeySigner;
+use eyre::Result;
+use std::sync::Arc;
+
+use crate::transaction::GasTestTransactionBuilder;
+
+/// GasConsumer creation bytecode, compiled by `build.rs`
+const GAS_CONSUMER_BYTECODE: &str = include_str!(concat!(env!("OUT_DIR"), "/GasConsumer.bin"));
+
+/// Deterministic deployment proxy, predeployed on anvil and most EVM chains
+pub const CREATE2_DEPLOYER: Address = address!("4e59b44847b379578588920cA78FbF26c0B4956C");
+
+/// Returns the embedded GasConsumer creation bytecode
+pub fn gas_consumer_bytecode() -> Result<Bytes> {
+    if GAS_CONSUMER_BYTECODE.is_empty() {
+        return Err(eyre::eyre!(
+            "GasConsumer bytecode was not embedded at build time, install foundry and rebuild"
+        ));
+    }
+
+    Ok(hex::decode(GAS_CONSUMER_BYTECODE.trim())?.into())
+}
+
+/// Address at which `init_code` is deployed through the CREATE2 deployer with `salt`
+pub fn create2_address(salt: B256, init_code: &Bytes) -> Address {
+    CREATE2_DEPLOYER.create2_from_code(salt, init_code)
+}
+
+/// Deployment of some init code, either already on chain or still to be sent
+#[derive(Debug, Clone)]
+pub enum Deployment {
+    /// Already deployed at its CREATE2 address
+    Deployed(Address),
+    /// Signed deployment transaction
+    Transaction(TxEnvelope),
+}
+
+/// Signs the deployment of `init_code`, through the deterministic deployer when a salt is given.
+/// CREATE2 deployments already on chain aren't sent again.
+pub async fn deployment(
+    provider: &Arc<dyn Provider>,
+    signer: PrivateKeySigner,
+    tx_builder: GasTestTransactionBuilder,
+    init_code: &Bytes,
+    salt: Option<B256>,
+) -> Result<Deployment> {
+    let tx_builder = match salt {
+        Some(salt) => {
+            let expected = create2_address(salt, init_code);
+            if !provider.get_code_at(expected).await?.is_empty() {
+                return Ok(Deployment::Deployed(expected));
+            }
+
+            let mut input = salt.to_vec();
+            input.extend_from_slice(init_code);
+            tx_builder
+                .to(CREATE2_DEPLOYER)
+                .input(TransactionInput::new(input.into()))
+        }
+        None => tx_builder.deploy_code(init_code.clone()),
+    };
+
+    // Deployment costs far more than the default gas limit, so estimate it
+    let mut request = tx_builder.tx.clone();
+    request.gas = None;
+    request.from = Some(signer.address());
+    let gas_limit = provider.estimate_gas(&request).await?;
+    let tx = tx_builder.gas_limit(gas_limit * 12 / 10).build(signer).await?;
+    Ok(Deployment::Transaction(tx))
+}
+
+/// Address of the contract deployed by the transaction of `receipt`
+pub fn deployed_address(
+    salt: Option<B256>,
+    init_code: &Bytes,
+    receipt: &TransactionReceipt,
+) -> Result<Address> {
+    if !receipt.status() {
+        return Err(eyre::eyre!("Deployment reverted: {:?}", receipt.transaction_hash));
+    }
+
+    match salt {
+        Some(salt) => Ok(create2_address(salt, init_code)),
+        None => receipt
+            .contract_address
+            .ok_or_else(|| eyre::eyre!("Deployment receipt has no contract address")),
+    }
+}
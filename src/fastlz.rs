@@ -0,0 +1,84 @@
+/// Size of `input` compressed with FastLZ (level 1), without producing the compressed bytes
+///
+/// Port of `FlzCompressLen` in op-geth, which matches `LibZip.flzCompress` used by the
+/// GasPriceOracle, so sizes agree byte for byte with what the chain charges for.
+pub fn compress_len(input: &[u8]) -> u32 {
+    let mut n: u32 = 0;
+    let mut ht = vec![0u32; 8192];
+
+    let u24 = |i: u32| -> u32 {
+        let i = i as usize;
+        input[i] as u32 | ((input[i + 1] as u32) << 8) | ((input[i + 2] as u32) << 16)
+    };
+    let cmp = |p: u32, q: u32, e: u32| -> u32 {
+        let mut l = 0;
+        let mut e = e - q;
+        while l < e {
+            if input[(p + l) as usize] != input[(q + l) as usize] {
+                e = 0;
+            }
+            l += 1;
+        }
+        l
+    };
+    let literals = |r: u32, n: &mut u32| {
+        *n += 0x21 * (r / 0x20);
+        let r = r % 0x20;
+        if r != 0 {
+            *n += r + 1;
+        }
+    };
+    let match_len = |l: u32, n: &mut u32| {
+        let l = l - 1;
+        *n += 3 * (l / 262);
+        *n += if l % 262 >= 6 { 3 } else { 2 };
+    };
+    let hash = |v: u32| -> usize { ((2654435769u32.wrapping_mul(v) >> 19) & 0x1fff) as usize };
+
+    let mut a: u32 = 0;
+    let ip_limit = if input.len() < 13 {
+        0
+    } else {
+        input.len() as u32 - 13
+    };
+
+    let mut ip = a + 2;
+    while ip < ip_limit {
+        let mut r;
+        loop {
+            let s = u24(ip);
+            let h = hash(s);
+            r = ht[h];
+            ht[h] = ip;
+            let d = ip.wrapping_sub(r);
+            if ip >= ip_limit {
+                break;
+            }
+            ip += 1;
+            if d <= 0x1fff && s == u24(r) {
+                break;
+            }
+        }
+        if ip >= ip_limit {
+            break;
+        }
+
+        ip -= 1;
+        if ip > a {
+            literals(ip - a, &mut n);
+        }
+        let l = cmp(r + 3, ip + 3, ip_limit + 9);
+        match_len(l, &mut n);
+
+        // Hash the two positions after the match
+        ip += l;
+        for _ in 0..2 {
+            ht[hash(u24(ip))] = ip;
+            ip += 1;
+        }
+        a = ip;
+    }
+
+    literals(input.len() as u32 - a, &mut n);
+    n
+}
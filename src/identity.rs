@@ -0,0 +1,229 @@
+use alloy_primitives::Address;
+use alloy_provider::Provider;
+use alloy_sol_types::SolCall;
+use eyre::Result;
+use semaphore_rs::Field;
+use std::cmp::Reverse;
+use std::sync::Arc;
+
+use crate::bindings::{IMulticall3, IMulticall3::Call3, IPBHEntryPoint, MULTICALL3_ADDRESS};
+use crate::rpc;
+use crate::world_id::WorldID;
+
+/// Nullifier hashes looked up per `aggregate3` call by `pbh_quota`
+pub const QUOTA_BATCH_SIZE: usize = 256;
+
+/// PBH quota of an identity for the current month
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PbhQuota {
+    /// `numPbhPerMonth` of the entry point
+    pub limit: u16,
+    /// PBH nonces whose nullifier hash is already spent
+    pub used: Vec<u16>,
+}
+
+impl PbhQuota {
+    pub fn remaining(&self) -> u16 {
+        (0..self.limit)
+            .filter(|nonce| !self.used.contains(nonce))
+            .count() as u16
+    }
+
+    /// Lowest PBH nonce that is still unused
+    pub fn next_nonce(&self) -> Option<u16> {
+        (0..self.limit).find(|nonce| !self.used.contains(nonce))
+    }
+}
+
+/// Reads the PBH quota of `world_id` for the current month from the entry point
+pub async fn pbh_quota(
+    provider: &Arc<dyn Provider>,
+    pbh_entry_point: Address,
+    world_id: &WorldID,
+) -> Result<PbhQuota> {
+    let limit = rpc::call(provider, pbh_entry_point, IPBHEntryPoint::numPbhPerMonthCall {})
+        .await?
+        ._0;
+
+    // Nullifier hashes are looked up in batches, so a large numPbhPerMonth stays within the
+    // RPC's eth_call gas cap
+    let mut used = Vec::new();
+    let nonces: Vec<u16> = (0..limit).collect();
+    for batch in nonces.chunks(QUOTA_BATCH_SIZE) {
+        let calls = batch
+            .iter()
+            .map(|&pbh_nonce| {
+                let (_, _, nullifier_hash) = world_id.pbh_ext_nullifier(pbh_nonce);
+                Call3 {
+                    target: pbh_entry_point,
+                    allowFailure: false,
+                    callData: IPBHEntryPoint::nullifierHashesCall { _0: nullifier_hash }
+                        .abi_encode()
+                        .into(),
+                }
+            })
+            .collect();
+        let results = rpc::call(
+            provider,
+            MULTICALL3_ADDRESS,
+            IMulticall3::aggregate3Call { calls },
+        )
+        .await?
+        .returnData;
+
+        for (&pbh_nonce, result) in batch.iter().zip(results) {
+            let spent =
+                IPBHEntryPoint::nullifierHashesCall::abi_decode_returns(&result.returnData, true)?
+                    ._0;
+            if spent {
+                used.push(pbh_nonce);
+            }
+        }
+    }
+
+    Ok(PbhQuota { limit, used })
+}
+
+/// PBH nonces of an identity, as read from the entry point plus the ones sent since
+#[derive(Debug, Clone)]
+pub struct NonceLedger {
+    pub world_id: WorldID,
+    pub quota: PbhQuota,
+    /// PBH transactions sent with this identity during the run
+    pub sent: u16,
+}
+
+impl NonceLedger {
+    fn record(&mut self, pbh_nonce: u16) {
+        if !self.quota.used.contains(&pbh_nonce) {
+            self.quota.used.push(pbh_nonce);
+        }
+        self.sent += 1;
+    }
+}
+
+/// PBH usage of an identity at the end of a run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdentityUsage {
+    pub commitment: Field,
+    pub sent: u16,
+    pub remaining: u16,
+    pub limit: u16,
+}
+
+/// Spreads PBH transactions over several identities
+///
+/// Every identity only gets `numPbhPerMonth` PBH transactions, so each transaction goes to the
+/// identity with the most quota left, using its lowest unused PBH nonce.
+#[derive(Debug, Clone)]
+pub struct IdentityScheduler {
+    ledgers: Vec<NonceLedger>,
+}
+
+impl IdentityScheduler {
+    /// Reads the quota of every identity from the entry point
+    pub async fn load(
+        provider: &Arc<dyn Provider>,
+        pbh_entry_point: Address,
+        world_ids: Vec<WorldID>,
+    ) -> Result<Self> {
+        let mut ledgers = Vec::with_capacity(world_ids.len());
+        for world_id in world_ids {
+            let quota = pbh_quota(provider, pbh_entry_point, &world_id).await?;
+            ledgers.push(NonceLedger {
+                world_id,
+                quota,
+                sent: 0,
+            });
+        }
+        Ok(Self { ledgers })
+    }
+
+    pub fn from_ledgers(ledgers: Vec<NonceLedger>) -> Self {
+        Self { ledgers }
+    }
+
+    /// Picks the identity with the most quota left and reserves its next PBH nonce.
+    /// Returns the index of the identity and the nonce, or `None` once every quota is used up.
+    pub fn next(&mut self) -> Option<(usize, u16)> {
+        // Ties go to the identity listed first
+        let (index, _) = self
+            .ledgers
+            .iter()
+            .enumerate()
+            .filter(|(_, ledger)| ledger.quota.remaining() > 0)
+            .max_by_key(|(index, ledger)| (ledger.quota.remaining(), Reverse(*index)))?;
+        let pbh_nonce = self.ledgers[index].quota.next_nonce()?;
+        self.ledgers[index].record(pbh_nonce);
+        Some((index, pbh_nonce))
+    }
+
+    /// Reserves the identity and PBH nonce of `count` transactions. With a first nonce, they all
+    /// go to the first identity with consecutive nonces, which must fit in a u16 and stay below
+    /// `numPbhPerMonth`. Nothing is reserved when they don't.
+    pub fn plan(&mut self, count: u64, first_nonce: Option<u16>) -> Result<Vec<(usize, u16)>> {
+        let Some(first_nonce) = first_nonce else {
+            return (0..count)
+                .map(|_| {
+                    self.next().ok_or_else(|| {
+                        eyre::eyre!("Every identity used up its PBH quota for this month")
+                    })
+                })
+                .collect();
+        };
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+        let last_nonce = u16::try_from(count - 1)
+            .ok()
+            .and_then(|offset| first_nonce.checked_add(offset))
+            .ok_or_else(|| {
+                eyre::eyre!(
+                    "PBH nonce {} + {} does not fit in a u16",
+                    first_nonce,
+                    count - 1
+                )
+            })?;
+        let limit = self.limit(0);
+        if last_nonce >= limit {
+            return Err(eyre::eyre!(
+                "PBH nonce {} is not below numPbhPerMonth ({}) of the entry point",
+                last_nonce,
+                limit
+            ));
+        }
+        let plan: Vec<_> = (first_nonce..=last_nonce)
+            .map(|pbh_nonce| (0, pbh_nonce))
+            .collect();
+        for (index, pbh_nonce) in &plan {
+            self.record(*index, *pbh_nonce);
+        }
+        Ok(plan)
+    }
+
+    /// Records a PBH nonce chosen outside of the scheduler
+    pub fn record(&mut self, index: usize, pbh_nonce: u16) {
+        self.ledgers[index].record(pbh_nonce);
+    }
+
+    pub fn world_id(&self, index: usize) -> &WorldID {
+        &self.ledgers[index].world_id
+    }
+
+    /// `numPbhPerMonth` of the entry point, as read for an identity
+    pub fn limit(&self, index: usize) -> u16 {
+        self.ledgers[index].quota.limit
+    }
+
+    pub fn usage(&self) -> Vec<IdentityUsage> {
+        self.ledgers
+            .iter()
+            .map(|ledger| IdentityUsage {
+                commitment: ledger.world_id.identity().commitment(),
+                sent: ledger.sent,
+                remaining: ledger.quota.remaining(),
+                limit: ledger.quota.limit,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,127 @@
+use alloy_primitives::TxHash;
+use alloy_provider::Provider;
+use eyre::Result;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+/// A new block head, stamped with the local time it was received
+#[derive(Debug, Clone, Copy)]
+struct NewHead {
+    number: u64,
+    received_at: Instant,
+}
+
+/// A transaction that was sent and is waiting to be included
+#[derive(Debug, Clone, Copy)]
+struct PendingTx {
+    sent_at: Instant,
+    sent_block: u64,
+}
+
+/// Inclusion result for a tracked transaction
+#[derive(Debug, Clone)]
+pub struct Inclusion {
+    pub tx_hash: TxHash,
+    pub block_number: u64,
+    /// Number of blocks between the head at send time and the including block
+    pub blocks_waited: u64,
+    /// Wall clock time between sending the transaction and receiving the including head
+    pub latency: Duration,
+}
+
+/// Tracks inclusion of sent transactions by subscribing to `newHeads`
+///
+/// The subscription is opened before any transaction is sent, so the including block
+/// can't be missed. Each head is timestamped as soon as it arrives, which gives
+/// inclusion latencies at a finer precision than the block time.
+pub struct InclusionTracker {
+    provider: Arc<dyn Provider>,
+    heads: mpsc::UnboundedReceiver<NewHead>,
+    latest_block: u64,
+    pending: HashMap<TxHash, PendingTx>,
+}
+
+impl InclusionTracker {
+    /// Subscribes to new heads. The provider must use a pubsub transport.
+    pub async fn subscribe(provider: Arc<dyn Provider>) -> Result<Self> {
+        let mut subscription = provider.subscribe_blocks().await?;
+        let latest_block = provider.get_block_number().await?;
+
+        let (sender, heads) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok(header) = subscription.recv().await {
+                let head = NewHead {
+                    number: header.number,
+                    received_at: Instant::now(),
+                };
+                if sender.send(head).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            provider,
+            heads,
+            latest_block,
+            pending: HashMap::new(),
+        })
+    }
+
+    /// Starts tracking a transaction that was just sent
+    pub fn watch(&mut self, tx_hash: TxHash, sent_at: Instant) {
+        self.pending.insert(
+            tx_hash,
+            PendingTx {
+                sent_at,
+                sent_block: self.latest_block,
+            },
+        );
+    }
+
+    /// Number of transactions that have not been included yet
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Waits until every watched transaction is included or the timeout expires.
+    /// Returns the inclusions in the order they were observed.
+    pub async fn wait(&mut self, timeout: Duration) -> Result<Vec<Inclusion>> {
+        let deadline = Instant::now() + timeout;
+        let mut inclusions = Vec::new();
+
+        while !self.pending.is_empty() {
+            let head = match tokio::time::timeout_at(deadline, self.heads.recv()).await {
+                Ok(Some(head)) => head,
+                Ok(None) => return Err(eyre::eyre!("newHeads subscription closed")),
+                Err(_) => break,
+            };
+            self.latest_block = head.number;
+
+            let hashes: Vec<TxHash> = self.pending.keys().copied().collect();
+            for tx_hash in hashes {
+                let Some(receipt) = self.provider.get_transaction_receipt(tx_hash).await? else {
+                    continue;
+                };
+                let Some(block_number) = receipt.block_number else {
+                    continue;
+                };
+                let Some(pending) = self.pending.remove(&tx_hash) else {
+                    continue;
+                };
+
+                inclusions.push(Inclusion {
+                    tx_hash,
+                    block_number,
+                    blocks_waited: block_number.saturating_sub(pending.sent_block),
+                    latency: head.received_at.saturating_duration_since(pending.sent_at),
+                });
+            }
+        }
+
+        Ok(inclusions)
+    }
+}
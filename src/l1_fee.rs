@@ -0,0 +1,198 @@
+use alloy_consensus::{SignableTransaction, TxEnvelope};
+use alloy_network::eip2718::Encodable2718;
+use alloy_primitives::{address, Address, Bytes, TxHash, U256};
+use alloy_provider::Provider;
+use alloy_rpc_types_eth::TransactionReceipt;
+use eyre::Result;
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::bindings::IGasPriceOracle;
+use crate::fastlz;
+use crate::rpc;
+
+/// Address of the GasPriceOracle predeploy on OP stack chains
+pub const GAS_PRICE_ORACLE: Address = address!("420000000000000000000000000000000000000F");
+
+/// L1 data fee of a transaction, from the GasPriceOracle or the Fjord cost function
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct L1FeeEstimate {
+    pub l1_fee: U256,
+    pub l1_gas_used: U256,
+}
+
+/// EIP-2718 encoding of a transaction without its signature, as the GasPriceOracle expects it.
+/// Fails for transaction types without a known unsigned encoding.
+pub fn unsigned_encoding(tx: &TxEnvelope) -> Result<Bytes> {
+    let encoded = if let Some(signed) = tx.as_eip1559() {
+        signed.tx().encoded_for_signing()
+    } else if let Some(signed) = tx.as_legacy() {
+        signed.tx().encoded_for_signing()
+    } else if let Some(signed) = tx.as_eip2930() {
+        signed.tx().encoded_for_signing()
+    } else if let Some(signed) = tx.as_eip4844() {
+        signed.tx().encoded_for_signing()
+    } else {
+        return Err(eyre::eyre!(
+            "No unsigned encoding for transaction type {:?} to quote the L1 fee of",
+            tx.tx_type()
+        ));
+    };
+    Ok(encoded.into())
+}
+
+/// Asks the GasPriceOracle what posting the transaction to L1 costs. `unsigned_tx` is the
+/// output of `unsigned_encoding`, the oracle adds the size of a signature itself.
+pub async fn estimate_l1_fee(provider: &Arc<dyn Provider>, unsigned_tx: &Bytes) -> Result<L1FeeEstimate> {
+    let l1_fee = rpc::call(
+        provider,
+        GAS_PRICE_ORACLE,
+        IGasPriceOracle::getL1FeeCall {
+            _data: unsigned_tx.clone(),
+        },
+    )
+    .await?
+    ._0;
+    let l1_gas_used = rpc::call(
+        provider,
+        GAS_PRICE_ORACLE,
+        IGasPriceOracle::getL1GasUsedCall {
+            _data: unsigned_tx.clone(),
+        },
+    )
+    .await?
+    ._0;
+
+    Ok(L1FeeEstimate { l1_fee, l1_gas_used })
+}
+
+/// Bytes the GasPriceOracle adds to the compressed size of an unsigned transaction for its signature
+pub const SIGNATURE_OVERHEAD: u32 = 68;
+
+/// Intercept of the Fjord linear regression, scaled by 1e6
+pub const FJORD_INTERCEPT: i64 = -42_585_600;
+
+/// FastLZ coefficient of the Fjord linear regression, scaled by 1e6
+pub const FJORD_FASTLZ_COEF: i64 = 836_500;
+
+/// Smallest estimated size of a transaction in bytes
+pub const FJORD_MIN_TRANSACTION_SIZE: i64 = 100;
+
+/// L1 fee parameters of the Fjord cost function
+///
+/// On chain they're read from the GasPriceOracle, here they can also be set by hand to
+/// estimate the L1 data fee without an RPC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FjordParams {
+    pub l1_base_fee: U256,
+    pub l1_blob_base_fee: U256,
+    pub base_fee_scalar: u32,
+    pub blob_base_fee_scalar: u32,
+}
+
+impl FjordParams {
+    /// Reads the current parameters from the GasPriceOracle
+    pub async fn from_oracle(provider: &Arc<dyn Provider>) -> Result<Self> {
+        Ok(Self {
+            l1_base_fee: rpc::call(provider, GAS_PRICE_ORACLE, IGasPriceOracle::l1BaseFeeCall {})
+                .await?
+                ._0,
+            l1_blob_base_fee: rpc::call(provider, GAS_PRICE_ORACLE, IGasPriceOracle::blobBaseFeeCall {})
+                .await?
+                ._0,
+            base_fee_scalar: rpc::call(provider, GAS_PRICE_ORACLE, IGasPriceOracle::baseFeeScalarCall {})
+                .await?
+                ._0,
+            blob_base_fee_scalar: rpc::call(
+                provider,
+                GAS_PRICE_ORACLE,
+                IGasPriceOracle::blobBaseFeeScalarCall {},
+            )
+            .await?
+            ._0,
+        })
+    }
+
+    /// L1 data fee of a transaction whose EIP-2718 encoding compresses to `fastlz_size` bytes
+    pub fn l1_fee(&self, fastlz_size: u32) -> L1FeeEstimate {
+        let estimated_size = fjord_estimated_size(fastlz_size);
+        let fee_scaled = U256::from(self.base_fee_scalar) * U256::from(16) * self.l1_base_fee
+            + U256::from(self.blob_base_fee_scalar) * self.l1_blob_base_fee;
+
+        L1FeeEstimate {
+            l1_fee: estimated_size * fee_scaled / U256::from(1_000_000_000_000u64),
+            l1_gas_used: estimated_size * U256::from(16) / U256::from(1_000_000),
+        }
+    }
+
+    /// L1 data fee of a signed transaction, as charged in its receipt
+    pub fn estimate_signed(&self, tx: &TxEnvelope) -> L1FeeEstimate {
+        self.l1_fee(fastlz::compress_len(&tx.encoded_2718()))
+    }
+
+    /// L1 data fee of an unsigned transaction, as returned by `GasPriceOracle.getL1Fee`
+    pub fn estimate_unsigned(&self, unsigned_tx: &[u8]) -> L1FeeEstimate {
+        self.l1_fee(fastlz::compress_len(unsigned_tx) + SIGNATURE_OVERHEAD)
+    }
+}
+
+/// Brotli compressed size predicted from the FastLZ size, scaled by 1e6
+pub fn fjord_estimated_size(fastlz_size: u32) -> U256 {
+    let estimated_size = FJORD_INTERCEPT + FJORD_FASTLZ_COEF * fastlz_size as i64;
+    U256::from(estimated_size.max(FJORD_MIN_TRANSACTION_SIZE * 1_000_000))
+}
+
+/// L1 fields OP stack nodes add to transaction receipts. All of them are missing on other chains.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpReceiptFields {
+    pub l1_fee: Option<U256>,
+    pub l1_gas_used: Option<U256>,
+    pub l1_gas_price: Option<U256>,
+    pub l1_base_fee_scalar: Option<U256>,
+    pub l1_blob_base_fee: Option<U256>,
+    pub l1_blob_base_fee_scalar: Option<U256>,
+}
+
+/// Reads the OP stack L1 fields of a receipt
+pub async fn op_receipt_fields(provider: &Arc<dyn Provider>, tx_hash: TxHash) -> Result<OpReceiptFields> {
+    // The Ethereum receipt type drops unknown fields, so the receipt is requested again as raw JSON
+    let fields: Option<OpReceiptFields> = provider
+        .raw_request("eth_getTransactionReceipt".into(), (tx_hash,))
+        .await?;
+    fields.ok_or_else(|| eyre::eyre!("No receipt for transaction {:?}", tx_hash))
+}
+
+/// Cost of an included transaction, split into L2 execution and L1 data
+#[derive(Debug, Clone)]
+pub struct CostReport {
+    pub tx_hash: TxHash,
+    pub gas_used: u64,
+    pub effective_gas_price: u128,
+    pub l1: OpReceiptFields,
+}
+
+impl CostReport {
+    pub fn new(receipt: &TransactionReceipt, l1: OpReceiptFields) -> Self {
+        Self {
+            tx_hash: receipt.transaction_hash,
+            gas_used: receipt.gas_used,
+            effective_gas_price: receipt.effective_gas_price,
+            l1,
+        }
+    }
+
+    /// L2 execution cost in wei
+    pub fn execution_cost(&self) -> U256 {
+        U256::from(self.gas_used) * U256::from(self.effective_gas_price)
+    }
+
+    /// L1 data cost in wei, zero outside of OP stack chains
+    pub fn l1_cost(&self) -> U256 {
+        self.l1.l1_fee.unwrap_or_default()
+    }
+
+    pub fn total_cost(&self) -> U256 {
+        self.execution_cost() + self.l1_cost()
+    }
+}
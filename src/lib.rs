@@ -1,5 +1,27 @@
 pub const INCLUSION_PROOF_URL: &str = "https://signup-orb-ethereum.stage-crypto.worldcoin.dev";
 
+pub mod abi;
+pub mod baseline;
+pub mod batch;
 pub mod bindings;
+pub mod calls;
+pub mod client;
+pub mod conditional;
+pub mod config;
+pub mod deploy;
+pub mod fastlz;
+pub mod identity;
+pub mod inclusion;
+pub mod l1_fee;
+pub mod pbh_fuzz;
+pub mod proof;
+pub mod proof_cache;
+pub mod results;
+pub mod rpc;
+pub mod scenario;
+pub mod send_error;
+pub mod sequencer;
 pub mod transaction;
+pub mod userop;
+pub mod workload;
 pub mod world_id;
@@ -1,99 +1,436 @@
-use alloy_network::{eip2718::Encodable2718};
-use alloy_primitives::{Address, Bytes, U256};
-use alloy_provider::{Provider, ProviderBuilder};
+use alloy_network::eip2718::Encodable2718;
+use alloy_primitives::{aliases::U192, Address, B256, U256};
 use alloy_rpc_types_eth::TransactionInput;
 use alloy_signer_local::PrivateKeySigner;
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 use eyre::Result;
-use reqwest::Url;
-use serde::Deserialize;
+use gas_test::abi::{decode_output, encode_call, format_value, parse_function};
+use gas_test::baseline::{self, Baseline, BaselineCase, BaselineCheck, BaselineTxType, Thresholds};
+use gas_test::batch::{Batch, BatchSend, Submission};
+use gas_test::calls::{describe_result, load_calls, pbh_multicall_results, CallSpec};
+use gas_test::client::{
+    FeeSettings, GasTestClient, SentTransaction, SettledTransactions, TxType, UserOpOptions,
+};
+use gas_test::conditional::{ConditionalBounds, ConditionalOutcome};
+use gas_test::config::{self, Config, ConfigLayer, Network};
+use gas_test::fastlz;
+use gas_test::identity::{pbh_quota, IdentityScheduler};
+use gas_test::l1_fee::{fjord_estimated_size, FjordParams, L1FeeEstimate};
+use gas_test::pbh_fuzz::{FuzzOutcome, PbhFault, PbhFuzzer};
+use gas_test::proof::{
+    LocalProver, MockProofProvider, ProofProvider, RandomProofProvider, RemoteProver,
+};
+use gas_test::proof_cache::{InclusionProofCache, WorldIdRoots};
+use gas_test::sequencer::{self, MockSequencer};
+use gas_test::results::{self, GroupBy, ResultsDb, ResultsFilter, ResultsRecorder, Run};
+use gas_test::rpc;
+use gas_test::scenario::{Scenario, ScenarioRunner, StepResult};
+use gas_test::transaction::{single_call_multicall, GasTestTransactionBuilder};
+use gas_test::userop;
+use gas_test::workload::Workload;
+use gas_test::world_id::WorldID;
 use std::env;
-use std::fs;
-use std::path::Path;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::str::FromStr;
-use tiny_keccak::{Keccak, Hasher};
-
-mod bindings;
-mod transaction;
-mod world_id;
-
-use transaction::{GasTestTransactionBuilder, consume_gas_multicall};
-use world_id::WorldID;
-
-// Configuration from TOML file
-#[derive(Deserialize, Debug)]
-struct Config {
-    contract_address: String,
-    world_id: String,
-    rpc_address: String,
+use std::time::Duration;
+
+// PBH submission modes
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum PbhMode {
+    /// `pbhMulticall` on the PBH entry point
+    Multicall,
+    /// ERC-4337 user operation aggregated by the PBH signature aggregator
+    Userop,
+}
+
+// Sources of the Semaphore proofs in PBH payloads
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Prover {
+    /// Placeholder proof carrying its signal hash, for entry points that don't verify proofs
+    Mock,
+    /// Inclusion proof from the sequencer, Groth16 proof generated locally
+    Local,
+    /// Remote proving service
+    Remote,
+}
+
+// Subcommands
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Deploy GasConsumer and write its address into the config file
+    Deploy {
+        /// Deploy with CREATE2 through the deterministic deployer using this salt
+        #[clap(long)]
+        salt: Option<B256>,
+    },
+    /// Serve inclusion proofs from a local World ID tree, like the signup sequencer
+    MockSequencer {
+        /// Address to listen on
+        #[clap(long, default_value = "127.0.0.1:8080")]
+        listen: SocketAddr,
+
+        /// Depth of the Semaphore Merkle tree
+        #[clap(long, default_value_t = sequencer::DEFAULT_TREE_DEPTH, value_parser = sequencer::parse_depth)]
+        depth: usize,
+    },
+    /// Estimate the L1 data fee of the transaction without an RPC, with the Fjord cost function
+    L1Cost {
+        /// Number of iterations for gas consumption
+        #[clap(long)]
+        iterations: u64,
+
+        /// Kind of execution cost to generate
+        #[clap(long, value_enum, default_value = "keccak")]
+        workload: Workload,
+
+        /// Estimate a `pbhMulticall` instead of a direct call. The proof and root are random
+        /// field elements, which compress like real ones.
+        #[clap(long)]
+        use_pbh: bool,
+
+        /// L1 base fee in wei
+        #[clap(long, default_value = "1000000000")]
+        l1_base_fee: U256,
+
+        /// L1 blob base fee in wei
+        #[clap(long, default_value = "1")]
+        l1_blob_base_fee: U256,
+
+        /// Base fee scalar of the GasPriceOracle
+        #[clap(long, default_value = "1368")]
+        base_fee_scalar: u32,
+
+        /// Blob base fee scalar of the GasPriceOracle
+        #[clap(long, default_value = "810949")]
+        blob_base_fee_scalar: u32,
+    },
+    /// Send deliberately broken PBH transactions and check each is rejected for the expected reason
+    PbhFuzz {
+        /// Faults to try, all of them by default
+        #[clap(long, value_enum)]
+        fault: Vec<PbhFault>,
+
+        /// Simulate with eth_call instead of sending. Only the entry point's checks run then,
+        /// not the ones of the builder's transaction pool.
+        #[clap(long)]
+        simulate: bool,
+
+        /// With --simulate, let reused-nullifier send a valid PBH transaction first when the
+        /// identity hasn't spent a nullifier this month
+        #[clap(long)]
+        spend_nullifier: bool,
+
+        /// Number of iterations of the GasConsumer call in the multicall
+        #[clap(long, default_value = "10")]
+        iterations: u64,
+    },
+    /// Send direct calls of any contract function, encoded from its signature
+    Send {
+        /// Contract to call
+        #[clap(long)]
+        to: Address,
+
+        /// Function signature such as `transfer(address,uint256)`, optionally with
+        /// `returns (...)` to decode the simulated return values
+        #[clap(long)]
+        sig: String,
+
+        /// Arguments of the function, in the order of the signature
+        #[clap(long, num_args = 0.., allow_negative_numbers = true)]
+        args: Vec<String>,
+
+        /// ETH sent with each call, in wei
+        #[clap(long, default_value = "0")]
+        value: U256,
+
+        /// Only simulate and estimate the call, without sending it
+        #[clap(long)]
+        simulate: bool,
+    },
+    /// Manage the World ID identity in the config file
+    Identity {
+        #[clap(subcommand)]
+        command: IdentityCommand,
+    },
+    /// Run the steps of a TOML scenario file, exiting nonzero if an assertion fails
+    Run {
+        /// Path of the scenario file
+        scenario: String,
+    },
+    /// Inspect the configuration
+    Config {
+        #[clap(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Query and compare the transactions recorded in the results database
+    Results {
+        #[clap(subcommand)]
+        command: ResultsCommand,
+    },
+    /// Record gas baselines and check for regressions against them
+    Baseline {
+        #[clap(subcommand)]
+        command: BaselineCommand,
+    },
+}
+
+// Results subcommands
+#[derive(Subcommand, Debug)]
+enum ResultsCommand {
+    /// Aggregate the recorded transactions, filtered by --network and the flags below
+    Query {
+        /// Only transactions of this run
+        #[clap(long)]
+        run: Option<i64>,
+
+        /// Only transactions of this type
+        #[clap(long, value_enum)]
+        tx_type: Option<TxType>,
+
+        /// Only runs of this workload
+        #[clap(long, value_enum)]
+        workload: Option<Workload>,
+
+        /// Only runs with this number of iterations
+        #[clap(long)]
+        iterations: Option<u64>,
+
+        /// Only transactions sent at or after this RFC 3339 time
+        #[clap(long)]
+        since: Option<chrono::DateTime<chrono::Utc>>,
+
+        /// What the aggregates are grouped by
+        #[clap(long, value_enum, default_value = "run")]
+        group_by: GroupBy,
+
+        /// List the transactions instead of aggregating them
+        #[clap(long)]
+        transactions: bool,
+    },
+    /// Compare the aggregates of two runs
+    Diff {
+        /// ID of the first run
+        run_a: i64,
+
+        /// ID of the run compared with it
+        run_b: i64,
+    },
+}
+
+// Baseline subcommands
+#[derive(Subcommand, Debug)]
+enum BaselineCommand {
+    /// Measure the gas of each case and write it to the baseline file
+    Record {
+        /// Baseline file to write
+        #[clap(long, default_value = "gas-baseline.json")]
+        file: String,
+
+        /// Case to measure as `<workload>:<iterations>:<direct|pbh>`, every workload by default
+        #[clap(long)]
+        case: Vec<BaselineCase>,
+    },
+    /// Measure the cases of the baseline file again, exiting nonzero if any moved too far
+    Check {
+        /// Baseline file to compare with
+        #[clap(long, default_value = "gas-baseline.json")]
+        file: String,
+
+        /// Gas a case may move by
+        #[clap(long)]
+        max_gas_delta: Option<u64>,
+
+        /// Percentage a case may move by, 1% if no threshold is given
+        #[clap(long)]
+        max_percent_delta: Option<f64>,
+    },
+}
+
+// Config subcommands
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Print the effective configuration and where each value comes from
+    Show,
+}
+
+// Identity subcommands
+#[derive(Subcommand, Debug)]
+enum IdentityCommand {
+    /// Generate a random identity and print its secret
+    New {
+        /// Write the secret to `world_id` in the config file
+        #[clap(long)]
+        write: bool,
+    },
+    /// Print the identity commitment and the PBH quota left this month
+    Show,
+    /// Register the identity with a mock sequencer
+    Insert {
+        /// URL of the mock sequencer
+        #[clap(long, default_value = "http://127.0.0.1:8080")]
+        mock_sequencer: String,
+    },
 }
 
 // Command line arguments
 #[derive(Parser, Debug)]
-#[clap(author, version, about)]
+#[clap(author, version, about, subcommand_negates_reqs = true)]
 struct Args {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
     /// Number of iterations for gas consumption
-    #[clap(long)]
-    iterations: u64,
+    #[clap(long, required_unless_present_any = ["calls", "calls_file"])]
+    iterations: Option<u64>,
+
+    /// Number of transactions to send
+    #[clap(long, global = true, default_value = "1")]
+    count: u64,
+
+    /// Kind of execution cost to generate
+    #[clap(long, value_enum, default_value = "keccak")]
+    workload: Workload,
+
+    /// Gas limit of the transaction
+    #[clap(long, global = true)]
+    gas_limit: Option<u64>,
     
-    /// RPC provider URI (overrides config file)
-    #[clap(long)]
+    /// RPC provider URI (overrides config file and GASTEST_RPC_ADDRESS)
+    #[clap(long, global = true)]
     provider_uri: Option<String>,
+
+    /// GasConsumer contract address (overrides config file and GASTEST_CONTRACT_ADDRESS)
+    #[clap(long, global = true)]
+    contract_address: Option<String>,
     
-    /// PBH Entry Point contract address
-    #[clap(long, default_value = "0x6e37bAB9d23bd8Bdb42b773C58ae43C6De43A590")]
-    pbh_entry_point: String,
+    /// PBH Entry Point contract address (overrides config file and GASTEST_PBH_ENTRY_POINT,
+    /// defaults to 0x6e37bAB9d23bd8Bdb42b773C58ae43C6De43A590)
+    #[clap(long, global = true)]
+    pbh_entry_point: Option<String>,
     
     /// Gas fee in Gwei
-    #[clap(long)]
+    #[clap(long, global = true)]
     gas_fee: Option<f64>,
     
     /// Priority gas fee in Gwei
-    #[clap(long)]
+    #[clap(long, global = true)]
     priority_gas_fee: Option<f64>,
     
     /// Path to configuration file
-    #[clap(long, default_value = "config.toml")]
+    #[clap(long, global = true, default_value = "config.toml")]
     config_file: String,
+
+    /// Network profile from the config file
+    #[clap(long, global = true)]
+    network: Option<String>,
     
     /// Use PBH transaction instead of direct transaction
     #[clap(long)]
     use_pbh: bool,
     
-    /// PBH nonce (only used with --use-pbh)
+    /// PBH nonce of the first transaction, counting up from there with the first identity
+    /// (only used with --use-pbh). Without it, the identity with the most quota left and its
+    /// next unused nonce are picked for every transaction.
+    #[clap(long)]
+    pbh_nonce: Option<u16>,
+
+    /// How the PBH payload is submitted (only used with --use-pbh)
+    #[clap(long, value_enum, default_value = "multicall")]
+    pbh_mode: PbhMode,
+
+    /// Call of the PBH multicall instead of the workload, as `<target> <signature> [args...]`
+    /// or `<target> <calldata>`, prefixed with `allowFailure` for a call that may revert. Can be
+    /// repeated (only used with --pbh-mode multicall)
+    #[clap(long = "call", requires = "use_pbh")]
+    calls: Vec<CallSpec>,
+
+    /// JSON file with an array of calls of the PBH multicall, appended to the --call ones
+    #[clap(long, requires = "use_pbh")]
+    calls_file: Option<String>,
+
+    /// Smart account sending the PBH user operation (only used with --pbh-mode userop)
+    #[clap(long)]
+    smart_account: Option<String>,
+
+    /// PBH signature aggregator address (only used with --pbh-mode userop)
+    #[clap(long)]
+    pbh_signature_aggregator: Option<String>,
+
+    /// ERC-4337 EntryPoint address (only used with --pbh-mode userop)
+    #[clap(long, default_value_t = userop::ENTRY_POINT_V07)]
+    entry_point: Address,
+
+    /// Bundler RPC URL. Without it, handleAggregatedOps is called directly on the PBH entry point
+    #[clap(long)]
+    bundler_url: Option<String>,
+
+    /// Nonce key of the user operation (only used with --pbh-mode userop)
     #[clap(long, default_value = "0")]
-    pbh_nonce: u16,
-}
+    userop_nonce_key: u64,
 
+    /// Source of the Semaphore proofs (only used with --use-pbh)
+    #[clap(long, value_enum, default_value = "mock")]
+    prover: Prover,
 
-pub fn consume_gas_calldata(address: &Address, iterations: U256) -> Bytes {
-    // Calculate function selector for consumeGas(address,uint256)
-    let mut keccak = Keccak::v256();
-    let mut hash = [0u8; 32];
-    keccak.update(b"consumeGas(address,uint256)");
-    keccak.finalize(&mut hash);
-    
-    // Take first 4 bytes of the hash as function selector
-    let function_selector = &hash[0..4];
-    
-    // Create buffer for the calldata
-    let mut calldata = Vec::with_capacity(4 + 32 + 32); // 4 bytes for selector + 32 bytes for address + 32 bytes for iterations
-    
-    // Add function selector
-    calldata.extend_from_slice(function_selector);
-    
-    // Add address parameter (padded to 32 bytes)
-    let mut address_bytes = [0u8; 32];
-    address_bytes[12..32].copy_from_slice(address.as_slice());
-    calldata.extend_from_slice(&address_bytes);
-    
-    // Add iterations parameter (padded to 32 bytes)
-    let mut iterations_bytes = [0u8; 32];
-    iterations_bytes.copy_from_slice(&iterations.to_be_bytes::<32>());
-    calldata.extend_from_slice(&iterations_bytes);
-    
-    calldata.into() // Convert Vec<u8> to Bytes
+    /// Sequencer serving inclusion proofs (only used with --prover local)
+    #[clap(long, default_value = gas_test::INCLUSION_PROOF_URL)]
+    sequencer_url: String,
+
+    /// Number of proofs generated in parallel (only used with --prover local, defaults to the CPU count)
+    #[clap(long)]
+    prover_workers: Option<usize>,
+
+    /// Refetch the inclusion proof instead of using the cached one (only used with --prover local)
+    #[clap(long)]
+    refresh_proof: bool,
+
+    /// Directory of the inclusion proof cache (only used with --prover local, defaults to the
+    /// gas-test data directory)
+    #[clap(long)]
+    proof_cache_dir: Option<String>,
+
+    /// Proving service URL (only used with --prover remote)
+    #[clap(long)]
+    prover_url: Option<String>,
+
+    /// Report the OP stack L1 data fee next to the execution cost
+    #[clap(long, global = true)]
+    l1_fees: bool,
+
+    /// Send with eth_sendRawTransactionConditional, bounded by block number and timestamp
+    #[clap(long, global = true)]
+    conditional: bool,
+
+    /// Account whose storage root has to stay unchanged until a conditional transaction is
+    /// included. Can be repeated
+    #[clap(long = "conditional-known-account", global = true, requires = "conditional")]
+    conditional_known_accounts: Vec<Address>,
+
+    /// Blocks after the current one a conditional transaction may be included in
+    #[clap(long, global = true, default_value = "10")]
+    conditional_blocks: u64,
+
+    /// Seconds a conditional transaction may be included for
+    #[clap(long, global = true, default_value = "60")]
+    conditional_seconds: u64,
+
+    /// Times a transaction rejected for a stale account nonce is signed again with a fresh one
+    #[clap(long, global = true, default_value = "2")]
+    max_retries: u32,
+
+    /// Seconds to wait for inclusion when connected over WebSocket
+    #[clap(long, default_value = "60")]
+    inclusion_timeout: u64,
+
+    /// SQLite file sent transactions are recorded in (defaults to results.db in the gas-test
+    /// data directory)
+    #[clap(long, global = true)]
+    results_db: Option<String>,
+
+    /// Don't record the sent transactions in the results database
+    #[clap(long, global = true)]
+    no_results: bool,
 }
 
 
@@ -104,15 +441,216 @@ async fn main() -> Result<()> {
     
     // Parse command line arguments
     let args = Args::parse();
+
+    // The mock sequencer needs neither a config nor a key
+    if let Some(Command::MockSequencer { listen, depth }) = &args.command {
+        let sequencer = Arc::new(MockSequencer::new(*depth)?);
+        println!("Mock sequencer listening on http://{} (tree depth {})", listen, depth);
+        println!("Root: {}", sequencer.root());
+        return sequencer.serve(*listen).await;
+    }
     
-    // Read configuration from TOML file
     let config_path = Path::new(&args.config_file);
-    if !config_path.exists() {
-        return Err(eyre::eyre!("Configuration file not found: {}", args.config_file));
+
+    // A new identity doesn't need an existing config
+    if let Some(Command::Identity {
+        command: IdentityCommand::New { write },
+    }) = &args.command
+    {
+        let world_id = WorldID::random();
+        println!("Secret: {}", world_id.secret());
+        println!("Commitment: {}", world_id.identity().commitment());
+        if *write {
+            config::write_world_id(config_path, &world_id.secret())?;
+            println!("Updated world_id in {}", args.config_file);
+        }
+        return Ok(());
+    }
+
+    // Results only come from the local database
+    if let Some(Command::Results { command }) = &args.command {
+        let path = results_db_path(&args)?;
+        let db = ResultsDb::open(&path)?;
+        match command {
+            ResultsCommand::Query {
+                run,
+                tx_type,
+                workload,
+                iterations,
+                since,
+                group_by,
+                transactions,
+            } => {
+                let filter = ResultsFilter {
+                    run_id: *run,
+                    network: args.network.clone(),
+                    tx_type: *tx_type,
+                    workload: *workload,
+                    iterations: *iterations,
+                    since: *since,
+                };
+                if *transactions {
+                    print!("{}", results::transactions_table(&db.transactions(&filter)?));
+                } else {
+                    let aggregates = db.aggregate(&filter, *group_by)?;
+                    print!("{}", results::query_table(&aggregates, *group_by));
+                }
+            }
+            ResultsCommand::Diff { run_a, run_b } => {
+                for run_id in [run_a, run_b] {
+                    let run = db
+                        .run(*run_id)?
+                        .ok_or_else(|| eyre::eyre!("No run {} in {}", run_id, path.display()))?;
+                    let workload = match (run.workload, run.iterations) {
+                        (Some(workload), Some(iterations)) => format!(
+                            ", {} with {} iterations",
+                            workload.to_possible_value().unwrap().get_name(),
+                            iterations
+                        ),
+                        _ => String::new(),
+                    };
+                    println!(
+                        "Run {}: {} on {}{}",
+                        run_id,
+                        run.started_at.to_rfc3339(),
+                        run.network,
+                        workload
+                    );
+                }
+                println!();
+                let diffs = results::diff(&db.run_aggregate(*run_a)?, &db.run_aggregate(*run_b)?);
+                print!(
+                    "{}",
+                    results::diff_table(
+                        &format!("Run {}", run_a),
+                        &format!("Run {}", run_b),
+                        &diffs
+                    )
+                );
+            }
+        }
+        return Ok(());
+    }
+
+    // Defaults, then the config file, then GASTEST_* variables, then flags
+    let cli_config = ConfigLayer {
+        contract_address: args.contract_address.clone(),
+        rpc_address: args.provider_uri.clone(),
+        pbh_entry_point: args.pbh_entry_point.clone(),
+        ..ConfigLayer::default()
+    };
+    let config = Config::load(
+        config_path,
+        args.network.as_deref(),
+        ConfigLayer::from_env(),
+        cli_config,
+    )?;
+
+    if let Some(Command::Config {
+        command: ConfigCommand::Show,
+    }) = &args.command
+    {
+        let file = if config.file_found { "" } else { " (not found)" };
+        println!("Config File: {}{}", config.path.display(), file);
+        println!("Network: {}", config.network.as_deref().unwrap_or("(none)"));
+        println!();
+        for (key, value, source) in config.entries() {
+            println!("{:<18} {:<68} {}", key, value, source);
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::L1Cost {
+        iterations,
+        workload,
+        use_pbh,
+        l1_base_fee,
+        l1_blob_base_fee,
+        base_fee_scalar,
+        blob_base_fee_scalar,
+    }) = &args.command
+    {
+        let params = FjordParams {
+            l1_base_fee: *l1_base_fee,
+            l1_blob_base_fee: *l1_blob_base_fee,
+            base_fee_scalar: *base_fee_scalar,
+            blob_base_fee_scalar: *blob_base_fee_scalar,
+        };
+        // The key only changes the signature, a random one is as good for the size
+        let signer = match env::var("PRIVATE_KEY") {
+            Ok(private_key) => private_key.parse::<PrivateKeySigner>()?,
+            Err(_) => PrivateKeySigner::random(),
+        };
+        let contract_address = config.contract_address()?;
+        let pbh_entry_point = config.pbh_entry_point.value;
+        let calldata = workload.calldata(contract_address, *iterations);
+
+        let mut builder =
+            GasTestTransactionBuilder::new(args.gas_fee, args.priority_gas_fee, None).nonce(0);
+        if let Some(gas_limit) = args.gas_limit {
+            builder = builder.gas_limit(gas_limit);
+        }
+        let builder = if *use_pbh {
+            let world_id = WorldID::new(config.world_id()?)?;
+            let calls = single_call_multicall(contract_address, calldata);
+            builder
+                .to(pbh_entry_point)
+                .with_pbh_multicall(
+                    &world_id,
+                    &RandomProofProvider,
+                    0,
+                    signer.address(),
+                    calls,
+                )
+                .await?
+        } else {
+            builder
+                .to(contract_address)
+                .input(TransactionInput::new(calldata))
+        };
+        let tx = builder.build(signer).await?;
+
+        let encoded = tx.encoded_2718();
+        let fastlz_size = fastlz::compress_len(&encoded);
+        let estimate = params.estimate_signed(&tx);
+        println!("Transaction Size: {} bytes", encoded.len());
+        println!("FastLZ Size: {} bytes", fastlz_size);
+        println!(
+            "Estimated Brotli Size: {} bytes",
+            fjord_estimated_size(fastlz_size) / U256::from(1_000_000)
+        );
+        println!("L1 Gas Used: {}", estimate.l1_gas_used);
+        println!("L1 Fee: {} wei", estimate.l1_fee);
+        return Ok(());
+    }
+
+    if let Some(Command::Identity { command }) = &args.command {
+        let world_id = WorldID::new(config.world_id()?)?;
+        let commitment = world_id.identity().commitment();
+        match command {
+            IdentityCommand::Show => {
+                let pbh_entry_point = config.pbh_entry_point.value;
+                let provider = rpc::connect(config.rpc_address()?).await?;
+                let quota = pbh_quota(&provider, pbh_entry_point, &world_id).await?;
+
+                println!("Commitment: {}", commitment);
+                println!("PBH Entry Point: {}", pbh_entry_point);
+                println!("PBH Quota: {} of {} left this month", quota.remaining(), quota.limit);
+                match quota.next_nonce() {
+                    Some(pbh_nonce) => println!("Next PBH Nonce: {}", pbh_nonce),
+                    None => println!("Next PBH Nonce: none"),
+                }
+            }
+            IdentityCommand::Insert { mock_sequencer } => {
+                let inserted = sequencer::insert_identity(mock_sequencer, commitment).await?;
+                println!("Commitment: {}", commitment);
+                println!("Inserted at index {}", inserted.index);
+                println!("Root: {}", inserted.root);
+            }
+            IdentityCommand::New { .. } => unreachable!("handled above"),
+        }
+        return Ok(());
     }
-    
-    let config_content = fs::read_to_string(config_path)?;
-    let config: Config = toml::from_str(&config_content)?;
     
     // Get private key from environment variable
     let private_key = env::var("PRIVATE_KEY")
@@ -120,22 +658,407 @@ async fn main() -> Result<()> {
     
     // Parse the private key
     let signer = private_key.parse::<PrivateKeySigner>()?;
+
+    let network = match &args.command {
+        // GasConsumer doesn't have to exist yet to deploy it, scenarios can deploy it too and
+        // send calls any contract
+        Some(Command::Deploy { .. } | Command::Run { .. } | Command::Send { .. }) => Network {
+            name: config.network.clone(),
+            rpc_address: config.rpc_address()?.to_string(),
+            contract_address: config.contract_address().unwrap_or_default(),
+            pbh_entry_point: config.pbh_entry_point.value,
+        },
+        _ => config.network()?,
+    };
+    let scenario = match &args.command {
+        Some(Command::Run { scenario }) => Some(Scenario::load(Path::new(scenario))?),
+        _ => None,
+    };
+    let baseline_cases = match &args.command {
+        Some(Command::Baseline {
+            command: BaselineCommand::Record { case, .. },
+        }) if case.is_empty() => Some(baseline::default_cases()),
+        Some(Command::Baseline {
+            command: BaselineCommand::Record { case, .. },
+        }) => Some(case.clone()),
+        Some(Command::Baseline {
+            command: BaselineCommand::Check { file, .. },
+        }) => Some(Baseline::load(Path::new(file))?.cases()),
+        _ => None,
+    };
+    let identities = if args.use_pbh
+        || matches!(args.command, Some(Command::PbhFuzz { .. }))
+        || scenario.as_ref().is_some_and(Scenario::sends_pbh)
+        || baseline_cases
+            .iter()
+            .flatten()
+            .any(|case| case.tx_type == BaselineTxType::Pbh)
+    {
+        config.identities()?
+    } else {
+        Vec::new()
+    };
+    let fees = FeeSettings {
+        gas_fee: args.gas_fee,
+        priority_gas_fee: args.priority_gas_fee,
+        gas_limit: args.gas_limit,
+    };
+
+    println!("Using RPC address: {}", network.rpc_address);
+    let client = GasTestClient::connect(network, signer, identities.first().cloned(), fees).await?;
+    let prover: Arc<dyn ProofProvider> = match args.prover {
+        Prover::Mock => Arc::new(MockProofProvider::default()),
+        Prover::Local => {
+            let cache_dir = match &args.proof_cache_dir {
+                Some(dir) => dir.into(),
+                None => InclusionProofCache::default_dir()?,
+            };
+            let cache = InclusionProofCache::new(cache_dir);
+            if args.refresh_proof {
+                for world_id in &identities {
+                    cache.remove(&args.sequencer_url, world_id.identity().commitment())?;
+                }
+            }
+            let roots = WorldIdRoots::new(client.provider().clone(), client.network().pbh_entry_point);
+            let prover = match args.prover_workers {
+                Some(workers) => LocalProver::with_workers(args.sequencer_url.clone(), workers),
+                None => LocalProver::new(args.sequencer_url.clone()),
+            };
+            Arc::new(prover.with_cache(cache, roots))
+        }
+        Prover::Remote => Arc::new(RemoteProver::new(
+            args.prover_url
+                .clone()
+                .ok_or_else(|| eyre::eyre!("--prover-url is required with --prover remote"))?,
+        )),
+    };
+    // Every transaction the client sends is recorded, with what it later learns about it
+    let recorder = if args.no_results {
+        None
+    } else {
+        Some(Arc::new(ResultsRecorder::new(
+            results_db_path(&args)?,
+            Run {
+                started_at: chrono::Utc::now(),
+                network: network_name(&client),
+                workload: None,
+                iterations: None,
+            },
+        )))
+    };
+    let client = client
+        .with_prover(prover)
+        .with_proof_pool(args.use_pbh)
+        .with_l1_fees(args.l1_fees)
+        .with_max_retries(args.max_retries)
+        .with_conditional(args.conditional.then_some(ConditionalBounds {
+            blocks: args.conditional_blocks,
+            seconds: args.conditional_seconds,
+        }))
+        .with_known_accounts(args.conditional_known_accounts.clone())
+        .with_results(recorder.clone());
+
+    if let Some(Command::Deploy { salt }) = &args.command {
+        let deployed = client.deploy(*salt).await?;
+        let address = deployed.address;
+        match deployed.tx_hash {
+            Some(tx_hash) => println!("Deployment transaction: {:?}", tx_hash),
+            None => println!("Contract already deployed at {}", address),
+        }
+        println!("GasConsumer deployed at: {}", address);
+        print_recorded(recorder.as_ref());
+
+        config::write_contract_address(config_path, args.network.as_deref(), address)?;
+        println!("Updated contract_address in {}", args.config_file);
+        return Ok(());
+    }
+
+    if let Some(Command::PbhFuzz {
+        fault,
+        simulate,
+        spend_nullifier,
+        iterations,
+    }) = &args.command
+    {
+        let faults = if fault.is_empty() {
+            PbhFault::value_variants().to_vec()
+        } else {
+            fault.clone()
+        };
+        let contract_address = client.network().contract_address;
+        let calls = single_call_multicall(
+            contract_address,
+            Workload::Keccak.calldata(contract_address, *iterations),
+        );
+        let mut fuzzer =
+            PbhFuzzer::new(&client, calls, Duration::from_secs(args.inclusion_timeout))
+                .await?
+                .with_spend_nullifier(*spend_nullifier);
+
+        let results = fuzzer.run_all(&faults, *simulate).await?;
+
+        println!("PBH Fuzz");
+        println!("--------");
+        for result in &results {
+            let outcome = match &result.outcome {
+                FuzzOutcome::Rejected(reason) => format!("rejected ({})", reason),
+                FuzzOutcome::Reverted(reason) => format!("reverted ({})", reason),
+                FuzzOutcome::Accepted => "accepted".to_string(),
+            };
+            println!(
+                "{:<18} {:<48} expected {:<32} {}",
+                result.fault.to_possible_value().unwrap().get_name(),
+                outcome,
+                result.fault.expected_reasons().join(" | "),
+                if result.passed() { "PASS" } else { "FAIL" }
+            );
+        }
+        let failed = results.iter().filter(|result| !result.passed()).count();
+
+        print_recorded(recorder.as_ref());
+        if failed > 0 {
+            return Err(eyre::eyre!(
+                "{} of {} faulty PBH transactions were not rejected as expected",
+                failed,
+                faults.len()
+            ));
+        }
+        return Ok(());
+    }
+
+    if let Some(scenario) = &scenario {
+        let mut runner = ScenarioRunner::new(
+            &client,
+            identities.clone(),
+            Duration::from_secs(args.inclusion_timeout),
+        );
+
+        let report = runner.run(scenario).await?;
+
+        let title = format!("Scenario: {}", scenario.name.as_deref().unwrap_or("(unnamed)"));
+        println!("{}", title);
+        println!("{}", "-".repeat(title.len()));
+        for (index, (step, result)) in scenario.steps.iter().zip(&report.results).enumerate() {
+            let prefix = format!("[{}] {}", index + 1, step.action());
+            match result {
+                StepResult::Deployed(address) => {
+                    println!("{}: GasConsumer deployed at {}", prefix, address)
+                }
+                StepResult::Funded(tx_hashes) => {
+                    println!("{}: {} account(s) funded", prefix, tx_hashes.len())
+                }
+                StepResult::Sent(txs) => {
+                    println!("{}:", prefix);
+                    for tx in txs {
+                        match (tx.tx_hash, &tx.rejected) {
+                            (Some(tx_hash), _) => println!("  {} {:?}", tx.label, tx_hash),
+                            (None, rejected) => println!(
+                                "  {} rejected: {}",
+                                tx.label,
+                                rejected.as_deref().unwrap_or_default()
+                            ),
+                        }
+                    }
+                }
+                StepResult::Waited { block_number } => {
+                    println!("{}: reached block {}", prefix, block_number)
+                }
+                StepResult::Asserted { failures } if failures.is_empty() => {
+                    println!("{}: PASS", prefix)
+                }
+                StepResult::Asserted { failures } => {
+                    println!("{}: FAIL", prefix);
+                    for failure in failures {
+                        println!("  {}", failure);
+                    }
+                }
+            }
+        }
+
+        let (passed, failed) = (report.passed(), report.failed());
+        println!();
+        println!("Assertions: {} passed, {} failed", passed, failed);
+        print_recorded(recorder.as_ref());
+        if failed > 0 {
+            return Err(eyre::eyre!("{} of {} assertions failed", failed, passed + failed));
+        }
+        return Ok(());
+    }
+
+    if let (Some(Command::Baseline { command }), Some(cases)) = (&args.command, &baseline_cases) {
+        let measurements = baseline::measure(
+            &client,
+            identities.clone(),
+            cases,
+            Duration::from_secs(args.inclusion_timeout),
+        )
+        .await?;
+
+        match command {
+            BaselineCommand::Record { file, .. } => {
+                println!("Baseline");
+                println!("--------");
+                for measurement in &measurements {
+                    println!("{:<32} {}", measurement.case.to_string(), measurement.gas_used);
+                }
+                Baseline::new(config.network.clone(), measurements).save(Path::new(file))?;
+                println!("Wrote {} case(s) to {}", cases.len(), file);
+                print_recorded(recorder.as_ref());
+            }
+            BaselineCommand::Check {
+                file,
+                max_gas_delta,
+                max_percent_delta,
+            } => {
+                let recorded = Baseline::load(Path::new(file))?;
+                let thresholds = Thresholds::new(*max_gas_delta, *max_percent_delta);
+                let check = BaselineCheck::new(&recorded, &measurements, thresholds)?;
+
+                let title = format!("Baseline Check: {} (recorded {})", file, recorded.recorded_at);
+                println!("{}", title);
+                println!("{}", "-".repeat(title.len()));
+                print!("{}", check.table());
+                print_recorded(recorder.as_ref());
+                if check.failed() > 0 {
+                    return Err(eyre::eyre!(
+                        "{} of {} baseline rows moved beyond the threshold",
+                        check.failed(),
+                        check.comparisons.len()
+                    ));
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::Send {
+        to,
+        sig,
+        args: call_args,
+        value,
+        simulate,
+    }) = &args.command
+    {
+        let function = parse_function(sig)?;
+        let calldata = encode_call(&function, call_args)?;
+
+        println!("Send");
+        println!("----");
+        println!("Target: {}", to);
+        println!("Function: {}", function.signature());
+        println!("Value: {} wei", value);
+        println!("Calldata: {}", calldata);
+
+        // A call that reverts in simulation would only burn gas
+        let simulation = client.simulate(*to, calldata.clone(), *value).await?;
+        if !simulation.success {
+            return Err(eyre::eyre!(
+                "Simulation reverted: {}",
+                simulation.error.unwrap_or_default()
+            ));
+        }
+        match decode_output(&function, &simulation.output)? {
+            Some(values) => {
+                let values: Vec<String> = values.iter().map(format_value).collect();
+                println!("Simulation: succeeded, returned ({})", values.join(", "));
+            }
+            None => println!("Simulation: succeeded"),
+        }
+        let estimate = client.estimate(*to, calldata.clone(), *value).await?;
+        println!(
+            "Gas Estimate: {} (max cost {} wei)",
+            estimate.gas,
+            estimate.max_cost()
+        );
+        if *simulate {
+            return Ok(());
+        }
+
+        println!();
+        let mut tracker = client.inclusion_tracker().await?;
+        let mut sent_txs = Vec::new();
+        for _ in 0..args.count {
+            let sent = client.send_call(*to, calldata.clone(), *value).await?;
+            print_sent(&sent, None);
+            if let Some(tracker) = tracker.as_mut() {
+                tracker.watch(sent.tx_hash, sent.sent_at);
+            }
+            sent_txs.push(sent);
+        }
+        let timeout = Duration::from_secs(args.inclusion_timeout);
+        let settled = client.settle(tracker, &sent_txs, timeout).await?;
+        print_settled(&sent_txs, &settled, args.inclusion_timeout);
+
+        println!();
+        let mut reverted = 0;
+        for (sent, receipt) in sent_txs.iter().zip(&settled.receipts) {
+            let receipt = receipt
+                .as_ref()
+                .ok_or_else(|| not_included(sent, args.inclusion_timeout))?;
+            if !receipt.status() {
+                reverted += 1;
+            }
+            println!(
+                "{:?}: {} in block {}, {} gas",
+                sent.tx_hash,
+                if receipt.status() { "succeeded" } else { "reverted" },
+                receipt.block_number.unwrap_or_default(),
+                receipt.gas_used
+            );
+        }
+        print_recorded(recorder.as_ref());
+        if reverted > 0 {
+            return Err(eyre::eyre!("{} of {} calls reverted", reverted, sent_txs.len()));
+        }
+        return Ok(());
+    }
+
+    let mut custom_calls = args.calls.clone();
+    if let Some(path) = &args.calls_file {
+        custom_calls.extend(load_calls(Path::new(path))?);
+    }
+    // Custom calls replace the workload call of the PBH multicall
+    let multicall = if custom_calls.is_empty() {
+        None
+    } else {
+        if args.pbh_mode == PbhMode::Userop {
+            return Err(eyre::eyre!("--call and --calls-file only work with --pbh-mode multicall"));
+        }
+        Some(
+            custom_calls
+                .iter()
+                .map(CallSpec::to_call3)
+                .collect::<Result<Vec<_>>>()?,
+        )
+    };
+
+    let iterations = match args.iterations {
+        Some(iterations) => iterations,
+        None if multicall.is_some() => 0,
+        None => return Err(eyre::eyre!("--iterations is required")),
+    };
+    let contract_address = client.network().contract_address;
     
-    // Convert string addresses to Address type
-    let contract_address = config.contract_address.parse::<Address>()?;
-    let pbh_entry_point = args.pbh_entry_point.parse::<Address>()?;
-    
-    // We'll use the pbh_entry_point directly in our code instead of modifying the static variable
-    
-    // Create calldata for the consumeGas function
-    let iterations = U256::from(args.iterations);
-    let calldata = consume_gas_calldata(&contract_address, iterations);
+    // Create calldata for the selected workload
+    let calldata = args.workload.calldata(contract_address, iterations);
+    if let Some(recorder) = &recorder {
+        recorder.set_workload(
+            multicall.is_none().then_some(args.workload),
+            multicall.is_none().then_some(iterations),
+        )?;
+    }
     
     println!("Gas Test Application");
     println!("-------------------");
     println!("Contract Address: {}", contract_address);
-    println!("PBH Entry Point: {}", pbh_entry_point);
-    println!("Iterations: {}", iterations);
+    println!("PBH Entry Point: {}", client.network().pbh_entry_point);
+    if multicall.is_some() {
+        println!("Calls: {}", custom_calls.len());
+    } else {
+        println!("Workload: {:?}", args.workload);
+        println!("Iterations: {}", iterations);
+    }
+    println!("Transactions: {}", args.count);
     
     // Print gas fee information if provided
     if let Some(gas_fee) = args.gas_fee {
@@ -149,114 +1072,274 @@ async fn main() -> Result<()> {
     // Print transaction type
     if args.use_pbh {
         println!("Transaction Type: PBH");
-        println!("PBH Nonce: {}", args.pbh_nonce);
+        println!("Prover: {:?}", args.prover);
     } else {
         println!("Transaction Type: Direct");
     }
     
     println!();
     println!("Sending transaction to the contract...");
-    
-    // Determine which RPC address to use (command line takes precedence over config)
-    let rpc_address = args.provider_uri.or(Some(config.rpc_address.clone()));
-    
-    // Create a provider with the RPC address if provided
-    let provider = if let Some(rpc_uri) = rpc_address.clone() {
-        println!("Using RPC address: {}", rpc_uri);
-        let provider = ProviderBuilder::new()
-            .on_http(rpc_uri.parse::<Url>()?);
-        Some(Arc::new(provider))
+
+    let user_op_options = match (args.use_pbh, args.pbh_mode) {
+        (true, PbhMode::Userop) => {
+            let options = UserOpOptions {
+                smart_account: args
+                    .smart_account
+                    .as_deref()
+                    .ok_or_else(|| eyre::eyre!("--smart-account is required with --pbh-mode userop"))?
+                    .parse::<Address>()?,
+                aggregator: args
+                    .pbh_signature_aggregator
+                    .as_deref()
+                    .ok_or_else(|| {
+                        eyre::eyre!("--pbh-signature-aggregator is required with --pbh-mode userop")
+                    })?
+                    .parse::<Address>()?,
+                entry_point: args.entry_point,
+                bundler_url: args.bundler_url.clone(),
+                nonce_key: U192::from(args.userop_nonce_key),
+            };
+            println!("Smart Account: {}", options.smart_account);
+            println!("Signature Aggregator: {}", options.aggregator);
+            Some(options)
+        }
+        _ => None,
+    };
+
+    // L1 data fee of the same call sent directly, to show what the PBH payload adds
+    let direct_l1_fee = if args.l1_fees && args.use_pbh && multicall.is_none() {
+        let direct_tx = client.build_direct(calldata.clone()).await?;
+        let estimate = client.estimate_l1_fee(&direct_tx).await?;
+        println!("Direct Call L1 Fee Estimate: {} wei", estimate.l1_fee);
+        Some(estimate)
     } else {
-        println!("No RPC address provided, transaction will not be sent");
         None
     };
-    
-    // Create and send the transaction
-    let tx = if args.use_pbh {
-        // Print transaction type
-        println!("Transaction Type: PBH");
-        
-        // Create a WorldID from the world_id in the config
-        let world_id = WorldID::new(&config.world_id)?;
-        
-        // The PBH entry point address is defined as a constant in transaction.rs
-        
-        // Get the PBH nonce limit and the next available nonce if a provider is available
-        let pbh_nonce = if let Some(_provider_ref) = provider.as_ref() {
-            // For simplicity, just use the provided nonce since we have provider compatibility issues
-            println!("Using provided PBH Nonce: {}", args.pbh_nonce);
-            args.pbh_nonce
-        } else {
-            println!("No provider available, using provided PBH Nonce: {}", args.pbh_nonce);
-            args.pbh_nonce
-        };
-        
-        // Create a multicall for the consumeGas function
-        let calls = consume_gas_multicall(contract_address, args.iterations);
-        
-        // Create a transaction builder
-        let mut tx_builder = GasTestTransactionBuilder::new(args.gas_fee, args.priority_gas_fee, None);
-        
-        // Get the account nonce if a provider is available
-        if let Some(provider_ref) = provider.as_ref() {
-            // Get the current nonce for the signer's address
-            let account_nonce = provider_ref.get_transaction_count(signer.address()).await?;
-            println!("Using account nonce: {}", account_nonce);
-            
-            // Set the nonce on the transaction builder
-            tx_builder = tx_builder.nonce(account_nonce);
-        } else {
-            println!("No provider available, using default nonce");
-        }
-        
-        // Create a PBH transaction (but don't send it since we don't have a provider)
-        let tx = tx_builder
-            .to(pbh_entry_point)
-            .with_pbh_multicall(&world_id, pbh_nonce, signer.address(), calls)
-            .await?
-            .build(signer)
-            .await?;
-            
-        println!("Successfully built PBH transaction: {:?}", tx);
-        println!("PBH functionality is working correctly!");
-        tx
+
+    // PBH transactions are spread over the configured identities by remaining quota
+    let batch = if args.use_pbh {
+        let scheduler = IdentityScheduler::load(
+            client.provider(),
+            client.network().pbh_entry_point,
+            config.identities()?,
+        )
+        .await?;
+        let pbh_calls = multicall
+            .clone()
+            .unwrap_or_else(|| single_call_multicall(contract_address, calldata.clone()));
+        Batch::pbh(
+            &client,
+            calldata,
+            args.count,
+            scheduler,
+            args.pbh_nonce,
+            pbh_calls,
+            user_op_options,
+        )?
     } else {
-        // Print transaction type
-        println!("Transaction Type: Direct");
-        
-        // Create a transaction builder
-        let mut tx_builder = GasTestTransactionBuilder::new(args.gas_fee, args.priority_gas_fee, None);
-        
-        // Get the account nonce if a provider is available
-        if let Some(provider_ref) = provider.as_ref() {
-            // Get the current nonce for the signer's address
-            let account_nonce = provider_ref.get_transaction_count(signer.address()).await?;
-            println!("Using account nonce: {}", account_nonce);
-            
-            // Set the nonce on the transaction builder
-            tx_builder = tx_builder.nonce(account_nonce);
-        } else {
-            println!("No provider available, using default nonce");
-        }
-        
-        // Create and send a direct transaction
-        tx_builder
-            .to(contract_address)
-            .input(TransactionInput::new(calldata))
-            .build(signer)
-            .await?
+        Batch::direct(calldata, args.count)
     };
-    
-    // Send the transaction using the provider if available
-    if let Some(provider) = provider {
-        // Send the transaction using the provider
-        let pending_tx = provider.send_raw_transaction(&tx.encoded_2718()).await?;
-        println!("Transaction sent: {:?}", pending_tx.tx_hash());
-    } else {
-        // Just print the transaction details if no provider is available
-        println!("Transaction built but not sent (no provider available):");
-        println!("  Transaction: {:?}", tx);
+
+    // Subscribe to new heads before sending so the including block can't be missed
+    let mut tracker = client.inclusion_tracker().await?;
+    let sends = batch.send(&client, tracker.as_mut()).await?;
+    for send in &sends {
+        if let (Some(scheduler), Some((identity, pbh_nonce))) = (batch.scheduler(), send.pbh) {
+            println!(
+                "PBH Nonce: {} (identity {})",
+                pbh_nonce,
+                scheduler.world_id(identity).identity().commitment()
+            );
+        }
+        match &send.submission {
+            Submission::Transaction(sent) => print_sent(sent, direct_l1_fee),
+            Submission::UserOp(op_hash) => println!("User operation sent: {:?}", op_hash),
+        }
+    }
+    let sent_txs: Vec<SentTransaction> = sends
+        .iter()
+        .filter_map(BatchSend::transaction)
+        .cloned()
+        .collect();
+
+    let timeout = Duration::from_secs(args.inclusion_timeout);
+    let settled = client.settle(tracker, &sent_txs, timeout).await?;
+    print_settled(&sent_txs, &settled, args.inclusion_timeout);
+
+    if multicall.is_some() {
+        println!();
+        println!("Multicall Results:");
+        for (sent, receipt) in sent_txs.iter().zip(&settled.receipts) {
+            let receipt = receipt
+                .as_ref()
+                .ok_or_else(|| not_included(sent, args.inclusion_timeout))?;
+            if !receipt.status() {
+                println!("{:?}: reverted", sent.tx_hash);
+                continue;
+            }
+            let results = pbh_multicall_results(client.provider(), sent.tx_hash).await?;
+            if results.replayed {
+                // Without a trace the calls ran on the parent block, not after the transactions
+                // before this one
+                println!(
+                    "{:?} (replayed on the parent block, may differ from the included run):",
+                    sent.tx_hash
+                );
+            } else {
+                println!("{:?}:", sent.tx_hash);
+            }
+            for (index, (call, result)) in custom_calls.iter().zip(&results.results).enumerate() {
+                println!(
+                    "  [{}] {} {}: {}",
+                    index,
+                    call.target,
+                    call.label(),
+                    describe_result(call, result)
+                );
+            }
+        }
+    }
+
+    if let Some(scheduler) = batch.scheduler() {
+        println!();
+        println!("Identity Usage:");
+        for usage in scheduler.usage() {
+            println!(
+                "{}: {} sent, {} of {} left",
+                usage.commitment, usage.sent, usage.remaining, usage.limit
+            );
+        }
     }
+
+    print_recorded(recorder.as_ref());
     
     Ok(())
 }
+
+/// Prints a transaction right after sending it, with its L1 fee quote if there is one
+fn print_sent(sent: &SentTransaction, direct_l1_fee: Option<L1FeeEstimate>) {
+    println!("Transaction sent: {:?}", sent.tx_hash);
+    if sent.retries > 0 {
+        println!("Retried {} time(s) with a fresh nonce", sent.retries);
+    }
+    if let Some(estimate) = sent.l1_fee_estimate {
+        println!(
+            "L1 Fee Estimate: {} wei ({} L1 gas)",
+            estimate.l1_fee, estimate.l1_gas_used
+        );
+        if let Some(direct) = direct_l1_fee {
+            println!(
+                "PBH L1 Fee Overhead: {} wei over a direct call",
+                estimate.l1_fee.saturating_sub(direct.l1_fee)
+            );
+        }
+    }
+}
+
+/// Prints the inclusion of the sent transactions and, with --conditional and --l1-fees, what
+/// became of their conditions and what they cost
+fn print_settled(sent_txs: &[SentTransaction], settled: &SettledTransactions, timeout: u64) {
+    for inclusion in &settled.inclusions {
+        println!(
+            "Transaction {:?} included in block {} after {} block(s), {:.3}s",
+            inclusion.tx_hash,
+            inclusion.block_number,
+            inclusion.blocks_waited,
+            inclusion.latency.as_secs_f64()
+        );
+    }
+    if settled.pending > 0 {
+        println!(
+            "{} transaction(s) not included within {}s",
+            settled.pending, timeout
+        );
+    }
+
+    if let Some(outcomes) = &settled.conditional {
+        println!();
+        println!("Conditional Transactions:");
+        for (sent, outcome) in sent_txs.iter().zip(outcomes) {
+            match outcome {
+                ConditionalOutcome::Included(receipt) => println!(
+                    "{:?}: included in block {}",
+                    sent.tx_hash,
+                    receipt.block_number.unwrap_or_default()
+                ),
+                ConditionalOutcome::Dropped(failure) => {
+                    println!("{:?}: dropped, {}", sent.tx_hash, failure)
+                }
+                ConditionalOutcome::Pending => {
+                    println!("{:?}: still pending after {}s", sent.tx_hash, timeout)
+                }
+            }
+        }
+    }
+
+    if let Some(costs) = &settled.costs {
+        println!();
+        println!("Cost Report:");
+        let mut execution_cost = U256::ZERO;
+        let mut l1_cost = U256::ZERO;
+        for (sent, report) in sent_txs.iter().zip(costs) {
+            let Some(report) = report else {
+                println!("{:?}: not included within {}s", sent.tx_hash, timeout);
+                continue;
+            };
+            println!(
+                "{:?}: execution {} wei ({} gas), L1 data {} wei ({} L1 gas, base fee scalar {}), total {} wei",
+                report.tx_hash,
+                report.execution_cost(),
+                report.gas_used,
+                report.l1_cost(),
+                report.l1.l1_gas_used.unwrap_or_default(),
+                report.l1.l1_base_fee_scalar.unwrap_or_default(),
+                report.total_cost()
+            );
+            execution_cost += report.execution_cost();
+            l1_cost += report.l1_cost();
+        }
+        println!(
+            "Total: execution {} wei, L1 data {} wei, total {} wei",
+            execution_cost,
+            l1_cost,
+            execution_cost + l1_cost
+        );
+    }
+}
+
+/// Results database path from --results-db, or the default one
+fn results_db_path(args: &Args) -> Result<PathBuf> {
+    match &args.results_db {
+        Some(path) => Ok(path.into()),
+        None => ResultsDb::default_path(),
+    }
+}
+
+/// Network profile name, or the RPC address without one
+fn network_name(client: &GasTestClient) -> String {
+    let network = client.network();
+    network
+        .name
+        .clone()
+        .unwrap_or_else(|| network.rpc_address.clone())
+}
+
+/// Tells which run the sent transactions were recorded as, if any were sent, once the recorder
+/// has written them
+fn print_recorded(recorder: Option<&Arc<ResultsRecorder>>) {
+    let Some(recorder) = recorder else {
+        return;
+    };
+    if let Some(run_id) = recorder.finish() {
+        println!();
+        println!("Recorded run {} in {}", run_id, recorder.path().display());
+    }
+}
+
+fn not_included(sent: &SentTransaction, inclusion_timeout: u64) -> eyre::Report {
+    eyre::eyre!(
+        "Transaction {:?} not included within {}s",
+        sent.tx_hash,
+        inclusion_timeout
+    )
+}
@@ -0,0 +1,292 @@
+use alloy_consensus::{Transaction, TxEnvelope};
+use alloy_primitives::{address, Address, U256};
+use alloy_provider::Provider;
+use alloy_rpc_types_eth::{TransactionInput, TransactionRequest};
+use chrono::Datelike;
+use clap::ValueEnum;
+use eyre::Result;
+use semaphore_rs::protocol::Proof;
+use std::time::Duration;
+use world_chain_builder_pbh::date_marker::DateMarker;
+use world_chain_builder_pbh::payload::{PBHPayload, Proof as PbhProof};
+
+use crate::bindings::IMulticall3::Call3;
+use crate::client::{GasTestClient, TxType};
+use crate::identity::{pbh_quota, PbhQuota};
+use crate::rpc;
+use crate::send_error::{normalize, rejection_reason, rpc_error_reason};
+use crate::transaction::multicall_signal_hash;
+use crate::world_id::WorldID;
+
+/// Sender whose signal `PbhFault::WrongSender` proves
+const OTHER_SENDER: Address = address!("000000000000000000000000000000000000dEaD");
+
+/// Ways `pbh-fuzz` breaks a PBH payload
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PbhFault {
+    /// Root next to the one of the inclusion proof, which the World ID contract never accepted
+    UnknownRoot,
+    /// Nullifier hash of a PBH nonce that is already spent
+    ReusedNullifier,
+    /// External nullifier of the previous month
+    WrongMonth,
+    /// PBH nonce equal to `numPbhPerMonth`
+    NonceOverLimit,
+    /// Valid proof with one coordinate changed
+    CorruptedProof,
+    /// Proof for the same calls sent by another address
+    WrongSender,
+}
+
+impl PbhFault {
+    /// Rejection reasons that count as the expected one, compared with `normalize`d errors
+    pub fn expected_reasons(&self) -> &'static [&'static str] {
+        match self {
+            Self::UnknownRoot => &["invalidroot"],
+            Self::ReusedNullifier => &["invalidnullifier", "duplicatenullifier"],
+            Self::WrongMonth => &["invalidexternalnullifierperiod"],
+            Self::NonceOverLimit => &["invalidpbhnonce", "invalidexternalnullifiernonce"],
+            Self::CorruptedProof | Self::WrongSender => &["invalidproof", "proofinvalid"],
+        }
+    }
+}
+
+/// What happened to a faulty PBH transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FuzzOutcome {
+    /// Refused by the RPC, e.g. by the builder's transaction pool
+    Rejected(String),
+    /// Reverted by the entry point, on chain or in simulation
+    Reverted(String),
+    /// Went through, the fault wasn't caught
+    Accepted,
+}
+
+impl FuzzOutcome {
+    pub fn reason(&self) -> Option<&str> {
+        match self {
+            Self::Rejected(reason) | Self::Reverted(reason) => Some(reason),
+            Self::Accepted => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzResult {
+    pub fault: PbhFault,
+    pub outcome: FuzzOutcome,
+}
+
+impl FuzzResult {
+    /// Whether the transaction was turned down for the expected reason
+    pub fn passed(&self) -> bool {
+        let Some(reason) = self.outcome.reason() else {
+            return false;
+        };
+        let reason = normalize(reason);
+        self.fault
+            .expected_reasons()
+            .iter()
+            .any(|expected| reason.contains(expected))
+    }
+}
+
+/// Builds PBH transactions with one deliberately broken part each and checks how they are turned down
+pub struct PbhFuzzer<'a> {
+    client: &'a GasTestClient,
+    world_id: WorldID,
+    calls: Vec<Call3>,
+    quota: PbhQuota,
+    timeout: Duration,
+    spend_nullifier: bool,
+}
+
+impl<'a> PbhFuzzer<'a> {
+    /// Fuzzer for the client's identity, reading its PBH quota from the entry point
+    pub async fn new(client: &'a GasTestClient, calls: Vec<Call3>, timeout: Duration) -> Result<Self> {
+        let world_id = client.world_id()?.clone();
+        let quota = pbh_quota(client.provider(), client.network().pbh_entry_point, &world_id).await?;
+        Ok(Self {
+            client,
+            world_id,
+            calls,
+            quota,
+            timeout,
+            spend_nullifier: false,
+        })
+    }
+
+    /// Lets simulated runs of `PbhFault::ReusedNullifier` send a valid PBH transaction first
+    /// when the identity hasn't spent a nullifier this month
+    pub fn with_spend_nullifier(self, spend_nullifier: bool) -> Self {
+        Self {
+            spend_nullifier,
+            ..self
+        }
+    }
+
+    /// Builds the payload with `fault`. Everything else about it is valid.
+    pub async fn payload(&mut self, fault: PbhFault) -> Result<PBHPayload> {
+        let prover = self.client.prover().clone();
+        let signal_hash = multicall_signal_hash(self.client.address(), &self.calls);
+
+        let payload = match fault {
+            PbhFault::UnknownRoot => {
+                let mut payload = self
+                    .world_id
+                    .pbh_payload(prover.as_ref(), self.unused_nonce()?, signal_hash)
+                    .await?;
+                payload.root += U256::from(1);
+                payload
+            }
+            PbhFault::ReusedNullifier => {
+                let pbh_nonce = self.spent_nonce().await?;
+                self.world_id
+                    .pbh_payload(prover.as_ref(), pbh_nonce, signal_hash)
+                    .await?
+            }
+            PbhFault::WrongMonth => {
+                let pbh_nonce = self.unused_nonce()?;
+                self.world_id
+                    .pbh_payload_at(prover.as_ref(), previous_month(), pbh_nonce, signal_hash)
+                    .await?
+            }
+            PbhFault::NonceOverLimit => {
+                self.world_id
+                    .pbh_payload(prover.as_ref(), self.quota.limit, signal_hash)
+                    .await?
+            }
+            PbhFault::CorruptedProof => {
+                let mut payload = self
+                    .world_id
+                    .pbh_payload(prover.as_ref(), self.unused_nonce()?, signal_hash)
+                    .await?;
+                let mut flat = payload.proof.0.flatten();
+                flat[0] ^= U256::from(1);
+                payload.proof = PbhProof(Proof::from_flat(flat));
+                payload
+            }
+            PbhFault::WrongSender => {
+                let signal_hash = multicall_signal_hash(OTHER_SENDER, &self.calls);
+                self.world_id
+                    .pbh_payload(prover.as_ref(), self.unused_nonce()?, signal_hash)
+                    .await?
+            }
+        };
+        Ok(payload)
+    }
+
+    /// Sends the faulty transaction, or simulates it with `eth_call`. Simulation only runs the
+    /// entry point's checks, not the ones of the builder's transaction pool.
+    pub async fn run(&mut self, fault: PbhFault, simulate: bool) -> Result<FuzzResult> {
+        if simulate
+            && fault == PbhFault::ReusedNullifier
+            && self.quota.used.is_empty()
+            && !self.spend_nullifier
+        {
+            return Err(eyre::eyre!(
+                "The identity hasn't spent a PBH nonce this month, so simulating a reused nullifier \
+                 would send a PBH transaction first. Allow it with --spend-nullifier"
+            ));
+        }
+
+        let payload = self.payload(fault).await?;
+        let tx = self
+            .client
+            .build_pbh_with_payload(self.calls.clone(), &payload)
+            .await?;
+
+        let outcome = if simulate {
+            match self.client.provider().call(&self.call_request(&tx)).await {
+                Ok(_) => FuzzOutcome::Accepted,
+                Err(err) => FuzzOutcome::Reverted(rpc_error_reason(&err)),
+            }
+        } else {
+            self.send(&tx).await?
+        };
+
+        Ok(FuzzResult { fault, outcome })
+    }
+
+    /// Runs every fault in order
+    pub async fn run_all(
+        &mut self,
+        faults: &[PbhFault],
+        simulate: bool,
+    ) -> Result<Vec<FuzzResult>> {
+        let mut results = Vec::with_capacity(faults.len());
+        for fault in faults {
+            results.push(self.run(*fault, simulate).await?);
+        }
+        Ok(results)
+    }
+
+    async fn send(&mut self, tx: &TxEnvelope) -> Result<FuzzOutcome> {
+        let sent = match self.client.send_raw(tx, TxType::Pbh).await {
+            Ok(sent) => sent,
+            Err(err) => return Ok(FuzzOutcome::Rejected(rejection_reason(&err))),
+        };
+        let receipt = self.client.wait_for_receipt(sent.tx_hash, self.timeout).await?;
+
+        if receipt.status() {
+            // The fault went through and spent a nullifier
+            self.quota = pbh_quota(
+                self.client.provider(),
+                self.client.network().pbh_entry_point,
+                &self.world_id,
+            )
+            .await?;
+            return Ok(FuzzOutcome::Accepted);
+        }
+
+        // Receipts don't carry the revert data
+        let output = rpc::transaction_output(self.client.provider(), sent.tx_hash).await?;
+        Ok(FuzzOutcome::Reverted(output.revert_reason()))
+    }
+
+    fn call_request(&self, tx: &TxEnvelope) -> TransactionRequest {
+        TransactionRequest::default()
+            .from(self.client.address())
+            .to(self.client.network().pbh_entry_point)
+            .input(TransactionInput::new(tx.input().clone()))
+    }
+
+    fn unused_nonce(&self) -> Result<u16> {
+        self.quota
+            .next_nonce()
+            .ok_or_else(|| eyre::eyre!("The identity used up its PBH quota for this month"))
+    }
+
+    /// A PBH nonce whose nullifier is spent. If there is none yet, a valid PBH transaction is
+    /// sent first, see `with_spend_nullifier` for simulated runs.
+    async fn spent_nonce(&mut self) -> Result<u16> {
+        if let Some(&pbh_nonce) = self.quota.used.first() {
+            return Ok(pbh_nonce);
+        }
+
+        let pbh_nonce = self.unused_nonce()?;
+        let sent = self
+            .client
+            .send_pbh_as(&self.world_id, self.calls.clone(), pbh_nonce)
+            .await?;
+        let receipt = self.client.wait_for_receipt(sent.tx_hash, self.timeout).await?;
+        if !receipt.status() {
+            return Err(eyre::eyre!(
+                "PBH transaction {:?} spending nonce {} reverted",
+                sent.tx_hash,
+                pbh_nonce
+            ));
+        }
+        self.quota.used.push(pbh_nonce);
+        Ok(pbh_nonce)
+    }
+}
+
+/// Date marker of the month before the current one
+fn previous_month() -> DateMarker {
+    let today = chrono::Utc::now().naive_utc().date();
+    match today.month() {
+        1 => DateMarker::new(today.year() - 1, 12),
+        month => DateMarker::new(today.year(), month - 1),
+    }
+}
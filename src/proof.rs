@@ -0,0 +1,354 @@
+use alloy_primitives::{uint, U256};
+use async_trait::async_trait;
+use eyre::Result;
+use rand::Rng;
+use semaphore_rs::identity::Identity;
+use semaphore_rs::protocol::{self, Proof};
+use semaphore_rs::Field;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
+
+use crate::proof_cache::{InclusionProofCache, WorldIdRoots};
+use crate::world_id::{InclusionProof, WorldID};
+
+/// How long cached inclusion proofs are used when the entry point has no World ID contract to
+/// check their root with, the default root history expiry of the World ID contract
+pub const UNCHECKED_ROOT_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Source of the Semaphore proofs in PBH payloads
+#[async_trait]
+pub trait ProofProvider: Send + Sync {
+    /// Proves that `identity` is in the World ID tree for the given signal and external nullifier.
+    /// Returns the proof and the root it was generated against.
+    async fn prove(
+        &self,
+        identity: &Identity,
+        signal_hash: Field,
+        external_nullifier_hash: Field,
+    ) -> Result<(Proof, Field)>;
+}
+
+/// Returns a placeholder proof against a fixed root, for entry points that don't verify proofs
+///
+/// The proof holds the signal hash and external nullifier hash it was made for in its first two
+/// coordinates and zeros elsewhere, so the mock entry point can still catch encoding mismatches.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MockProofProvider {
+    pub root: Field,
+}
+
+#[async_trait]
+impl ProofProvider for MockProofProvider {
+    async fn prove(
+        &self,
+        _identity: &Identity,
+        signal_hash: Field,
+        external_nullifier_hash: Field,
+    ) -> Result<(Proof, Field)> {
+        let mut flat = [U256::ZERO; 8];
+        flat[0] = signal_hash;
+        flat[1] = external_nullifier_hash;
+        Ok((Proof::from_flat(flat), self.root))
+    }
+}
+
+/// Modulus of the BN254 base field, the coordinates of a Groth16 proof are below it
+pub const BASE_FIELD_MODULUS: U256 =
+    uint!(21888242871839275222246405745257275088696311157297823662689037894645226208583_U256);
+
+/// Modulus of the BN254 scalar field, World ID roots are below it
+pub const SCALAR_FIELD_MODULUS: U256 =
+    uint!(21888242871839275222246405745257275088548364400416034343698204186575808495617_U256);
+
+/// Returns proofs and roots of random field elements, for sizing transactions without a sequencer
+///
+/// Nothing can verify these proofs, but they are as long and as incompressible as real ones, unlike
+/// the mostly zero proofs of `MockProofProvider`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RandomProofProvider;
+
+impl RandomProofProvider {
+    /// Uniformly random element below `modulus`
+    pub fn field_element(modulus: U256) -> U256 {
+        let mut rng = rand::thread_rng();
+        let bits = modulus.bit_len();
+        loop {
+            let element = U256::from_be_bytes(rng.gen::<[u8; 32]>()) >> (256 - bits);
+            if element < modulus {
+                return element;
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ProofProvider for RandomProofProvider {
+    async fn prove(
+        &self,
+        _identity: &Identity,
+        _signal_hash: Field,
+        _external_nullifier_hash: Field,
+    ) -> Result<(Proof, Field)> {
+        let flat = std::array::from_fn(|_| Self::field_element(BASE_FIELD_MODULUS));
+        Ok((
+            Proof::from_flat(flat),
+            Self::field_element(SCALAR_FIELD_MODULUS),
+        ))
+    }
+}
+
+/// Fetches the inclusion proof from a sequencer and generates the Groth16 proof locally
+///
+/// Proving is CPU bound, so it runs on the blocking thread pool, at most `workers` proofs at a time.
+/// With a cache, inclusion proofs are only fetched when the World ID contract no longer accepts the
+/// cached root, or after `UNCHECKED_ROOT_TTL` when there is no World ID contract to ask.
+#[derive(Debug, Clone)]
+pub struct LocalProver {
+    pub sequencer_url: String,
+    http: reqwest::Client,
+    workers: Arc<Semaphore>,
+    cache: Option<(InclusionProofCache, WorldIdRoots)>,
+    /// One lock per identity commitment, so concurrent proofs of an identity fetch its inclusion
+    /// proof once and the others find it in the cache
+    fetching: Arc<Mutex<HashMap<Field, Arc<tokio::sync::Mutex<()>>>>>,
+}
+
+impl LocalProver {
+    /// Prover with one worker per available CPU
+    pub fn new(sequencer_url: impl Into<String>) -> Self {
+        let workers = std::thread::available_parallelism().map_or(1, |n| n.get());
+        Self::with_workers(sequencer_url, workers)
+    }
+
+    pub fn with_workers(sequencer_url: impl Into<String>, workers: usize) -> Self {
+        Self {
+            sequencer_url: sequencer_url.into(),
+            http: reqwest::Client::new(),
+            workers: Arc::new(Semaphore::new(workers.max(1))),
+            cache: None,
+            fetching: Arc::default(),
+        }
+    }
+
+    /// Keeps fetched inclusion proofs in `cache`, reusing them while `roots` accepts their root
+    pub fn with_cache(self, cache: InclusionProofCache, roots: WorldIdRoots) -> Self {
+        Self {
+            cache: Some((cache, roots)),
+            ..self
+        }
+    }
+
+    /// Cached inclusion proof of an identity commitment, fetched from the sequencer when
+    /// there is no usable cache entry. Errors checking the cached root are returned, they don't
+    /// throw the entry away.
+    pub async fn cached_inclusion_proof(&self, commitment: Field) -> Result<InclusionProof> {
+        let Some((cache, roots)) = &self.cache else {
+            return self.inclusion_proof(commitment).await;
+        };
+        let lock = self
+            .fetching
+            .lock()
+            .unwrap()
+            .entry(commitment)
+            .or_default()
+            .clone();
+        let _fetching = lock.lock().await;
+
+        if let Some(cached) = cache.get(&self.sequencer_url, commitment) {
+            let valid = match roots.is_valid(cached.root()).await? {
+                Some(valid) => valid,
+                None => cached.age() < UNCHECKED_ROOT_TTL,
+            };
+            if valid {
+                return Ok(cached.inclusion_proof);
+            }
+        }
+
+        let inclusion_proof = self.inclusion_proof(commitment).await?;
+        cache.put(&self.sequencer_url, commitment, &inclusion_proof)?;
+        Ok(inclusion_proof)
+    }
+
+    /// Fetches the inclusion proof of an identity commitment from the sequencer
+    pub async fn inclusion_proof(&self, commitment: Field) -> Result<InclusionProof> {
+        let response = self
+            .http
+            .post(format!("{}/inclusionProof", self.sequencer_url.trim_end_matches('/')))
+            .json(&serde_json::json!({ "identityCommitment": commitment }))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(eyre::eyre!(
+                "Sequencer returned {} for identity {}",
+                response.status(),
+                commitment
+            ));
+        }
+        Ok(response.json().await?)
+    }
+}
+
+#[async_trait]
+impl ProofProvider for LocalProver {
+    async fn prove(
+        &self,
+        identity: &Identity,
+        signal_hash: Field,
+        external_nullifier_hash: Field,
+    ) -> Result<(Proof, Field)> {
+        let inclusion_proof = self.cached_inclusion_proof(identity.commitment()).await?;
+
+        let _permit = self.workers.acquire().await?;
+        let identity = identity.clone();
+        let merkle_proof = inclusion_proof.proof;
+        let proof = tokio::task::spawn_blocking(move || {
+            protocol::generate_proof(&identity, &merkle_proof, external_nullifier_hash, signal_hash)
+        })
+        .await??;
+        Ok((proof, inclusion_proof.root))
+    }
+}
+
+/// Body of a request to a remote proving service
+///
+/// The service needs the identity secrets to build the witness, so it has to be trusted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProveRequest {
+    pub identity_trapdoor: Field,
+    pub identity_nullifier: Field,
+    pub signal_hash: Field,
+    pub external_nullifier_hash: Field,
+}
+
+/// Response of a remote proving service
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProveResponse {
+    pub root: Field,
+    pub proof: [U256; 8],
+}
+
+/// Delegates proving to a service that answers `POST /prove`
+#[derive(Debug, Clone)]
+pub struct RemoteProver {
+    pub url: String,
+    http: reqwest::Client,
+}
+
+impl RemoteProver {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            http: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl ProofProvider for RemoteProver {
+    async fn prove(
+        &self,
+        identity: &Identity,
+        signal_hash: Field,
+        external_nullifier_hash: Field,
+    ) -> Result<(Proof, Field)> {
+        let request = ProveRequest {
+            identity_trapdoor: identity.trapdoor,
+            identity_nullifier: identity.nullifier,
+            signal_hash,
+            external_nullifier_hash,
+        };
+        let response = self
+            .http
+            .post(format!("{}/prove", self.url.trim_end_matches('/')))
+            .json(&request)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(eyre::eyre!("Proving service returned {}", response.status()));
+        }
+
+        let response: ProveResponse = response.json().await?;
+        Ok((Proof::from_flat(response.proof), response.root))
+    }
+}
+
+/// Identity commitment, signal hash and external nullifier hash of a proof
+type ProofKey = (Field, Field, Field);
+
+/// Proofs generated ahead of time by a wrapped provider
+///
+/// `prefetch` starts proving for a payload that will be needed later. `prove` hands out the
+/// prefetched proof when there is one and falls back to the wrapped provider otherwise, so
+/// the pool can stand in for any `ProofProvider`.
+pub struct ProofPool {
+    prover: Arc<dyn ProofProvider>,
+    pending: Mutex<HashMap<ProofKey, JoinHandle<Result<(Proof, Field)>>>>,
+}
+
+impl ProofPool {
+    pub fn new(prover: Arc<dyn ProofProvider>) -> Self {
+        Self {
+            prover,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Starts proving the payload of `world_id` for `pbh_nonce` and `signal_hash` in the background.
+    /// Fails when called outside of a Tokio runtime.
+    pub fn prefetch(&self, world_id: &WorldID, pbh_nonce: u16, signal_hash: Field) -> Result<()> {
+        let runtime = Handle::try_current()
+            .map_err(|_| eyre::eyre!("Prefetching proofs needs a Tokio runtime"))?;
+        let identity = world_id.identity().clone();
+        let (_, external_nullifier_hash, _) = world_id.pbh_ext_nullifier(pbh_nonce);
+        let key = (identity.commitment(), signal_hash, external_nullifier_hash);
+
+        let mut pending = self.pending.lock().unwrap();
+        if pending.contains_key(&key) {
+            return Ok(());
+        }
+        let prover = self.prover.clone();
+        let handle = runtime.spawn(async move {
+            prover
+                .prove(&identity, signal_hash, external_nullifier_hash)
+                .await
+        });
+        pending.insert(key, handle);
+        Ok(())
+    }
+
+    /// Number of prefetched proofs not handed out yet
+    pub fn len(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[async_trait]
+impl ProofProvider for ProofPool {
+    async fn prove(
+        &self,
+        identity: &Identity,
+        signal_hash: Field,
+        external_nullifier_hash: Field,
+    ) -> Result<(Proof, Field)> {
+        let key = (identity.commitment(), signal_hash, external_nullifier_hash);
+        let handle = self.pending.lock().unwrap().remove(&key);
+        match handle {
+            Some(handle) => handle.await?,
+            None => {
+                self.prover
+                    .prove(identity, signal_hash, external_nullifier_hash)
+                    .await
+            }
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use alloy_primitives::{keccak256, Address};
+use alloy_provider::Provider;
+use alloy_rpc_types_eth::{TransactionInput, TransactionRequest};
+use alloy_sol_types::SolCall;
+use alloy_transport::RpcError;
+use eyre::Result;
+use semaphore_rs::Field;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::bindings::{IPBHEntryPoint, IWorldID};
+use crate::rpc;
+use crate::world_id::InclusionProof;
+
+/// Inclusion proof as stored in the cache
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedInclusionProof {
+    /// Unix time in seconds at which the proof was fetched
+    pub fetched_at: u64,
+    /// Sequencer the proof was fetched from
+    pub sequencer_url: String,
+    pub inclusion_proof: InclusionProof,
+}
+
+impl CachedInclusionProof {
+    pub fn root(&self) -> Field {
+        self.inclusion_proof.root
+    }
+
+    pub fn age(&self) -> Duration {
+        let fetched_at = UNIX_EPOCH + Duration::from_secs(self.fetched_at);
+        SystemTime::now()
+            .duration_since(fetched_at)
+            .unwrap_or_default()
+    }
+}
+
+/// On-disk cache of inclusion proofs, one file per sequencer and identity commitment
+///
+/// Entries don't expire on their own. Whether the root of an entry is still accepted is up to
+/// the World ID contract, see `WorldIdRoots`.
+#[derive(Debug, Clone)]
+pub struct InclusionProofCache {
+    dir: PathBuf,
+}
+
+impl InclusionProofCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// `inclusion-proofs` under the gas-test data directory
+    pub fn default_dir() -> Result<PathBuf> {
+        let data_dir = dirs::data_dir()
+            .ok_or_else(|| eyre::eyre!("No data directory for the current user"))?;
+        Ok(data_dir.join("gas-test").join("inclusion-proofs"))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Cached proof of a commitment fetched from `sequencer_url`
+    pub fn get(&self, sequencer_url: &str, commitment: Field) -> Option<CachedInclusionProof> {
+        let contents = std::fs::read_to_string(self.path(sequencer_url, commitment)).ok()?;
+        // Unreadable entries are refetched and overwritten
+        let cached: CachedInclusionProof = serde_json::from_str(&contents).ok()?;
+        // Proofs of one sequencer's tree are useless against another's
+        (cached.sequencer_url == normalize_url(sequencer_url)).then_some(cached)
+    }
+
+    /// Stores a proof freshly fetched from `sequencer_url`
+    pub fn put(
+        &self,
+        sequencer_url: &str,
+        commitment: Field,
+        inclusion_proof: &InclusionProof,
+    ) -> Result<()> {
+        let fetched_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let cached = CachedInclusionProof {
+            fetched_at,
+            sequencer_url: normalize_url(sequencer_url).to_string(),
+            inclusion_proof: inclusion_proof.clone(),
+        };
+
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(
+            self.path(sequencer_url, commitment),
+            serde_json::to_string_pretty(&cached)?,
+        )?;
+        Ok(())
+    }
+
+    /// Drops the cached proof of a commitment, if any
+    pub fn remove(&self, sequencer_url: &str, commitment: Field) -> Result<()> {
+        match std::fs::remove_file(self.path(sequencer_url, commitment)) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn path(&self, sequencer_url: &str, commitment: Field) -> PathBuf {
+        let sequencer = keccak256(normalize_url(sequencer_url).as_bytes());
+        self.dir.join(format!(
+            "0x{}-{}.json",
+            hex::encode(commitment.to_be_bytes::<32>()),
+            hex::encode(&sequencer[..8])
+        ))
+    }
+}
+
+fn normalize_url(url: &str) -> &str {
+    url.trim_end_matches('/')
+}
+
+/// Asks the World ID contract of a PBH entry point whether a root is still accepted
+#[derive(Clone)]
+pub struct WorldIdRoots {
+    provider: Arc<dyn Provider>,
+    pbh_entry_point: Address,
+}
+
+impl std::fmt::Debug for WorldIdRoots {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WorldIdRoots")
+            .field("pbh_entry_point", &self.pbh_entry_point)
+            .finish_non_exhaustive()
+    }
+}
+
+impl WorldIdRoots {
+    pub fn new(provider: Arc<dyn Provider>, pbh_entry_point: Address) -> Self {
+        Self {
+            provider,
+            pbh_entry_point,
+        }
+    }
+
+    /// Whether `root` passes `requireValidRoot` of the entry point's World ID contract, `None` for
+    /// entry points without one, which leave proof verification to the builder. Only a revert
+    /// means the root is no longer accepted, failed requests are errors.
+    pub async fn is_valid(&self, root: Field) -> Result<Option<bool>> {
+        let world_id = rpc::call(
+            &self.provider,
+            self.pbh_entry_point,
+            IPBHEntryPoint::worldIdCall {},
+        )
+        .await?
+        ._0;
+        if world_id.is_zero() {
+            return Ok(None);
+        }
+
+        let request = TransactionRequest::default().to(world_id).input(TransactionInput::new(
+            IWorldID::requireValidRootCall { root }.abi_encode().into(),
+        ));
+        match self.provider.call(&request).await {
+            Ok(_) => Ok(Some(true)),
+            Err(RpcError::ErrorResp(payload)) if payload.as_revert_data().is_some() => {
+                Ok(Some(false))
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+}
@@ -0,0 +1,844 @@
+use alloy_consensus::{Transaction, TxEnvelope};
+use alloy_primitives::TxHash;
+use alloy_rpc_types_eth::TransactionReceipt;
+use chrono::{DateTime, SecondsFormat, Utc};
+use clap::ValueEnum;
+use eyre::Result;
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::client::{SentTransaction, TxType};
+use crate::inclusion::Inclusion;
+use crate::l1_fee::OpReceiptFields;
+use crate::workload::Workload;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    started_at TEXT NOT NULL,
+    network TEXT NOT NULL,
+    workload TEXT,
+    iterations INTEGER
+);
+CREATE TABLE IF NOT EXISTS transactions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    run_id INTEGER NOT NULL REFERENCES runs (id),
+    tx_hash TEXT NOT NULL,
+    tx_type TEXT NOT NULL,
+    nonce INTEGER NOT NULL,
+    retries INTEGER NOT NULL,
+    sent_at TEXT NOT NULL,
+    max_fee_per_gas TEXT,
+    max_priority_fee_per_gas TEXT,
+    gas_limit INTEGER,
+    status INTEGER,
+    block_number INTEGER,
+    gas_used INTEGER,
+    effective_gas_price TEXT,
+    l1_fee TEXT,
+    l1_gas_used INTEGER,
+    blocks_waited INTEGER,
+    latency_ms INTEGER
+);
+CREATE INDEX IF NOT EXISTS transactions_run_id ON transactions (run_id);
+CREATE UNIQUE INDEX IF NOT EXISTS transactions_run_tx_hash ON transactions (run_id, tx_hash);
+";
+
+/// Inserts a transaction, with `?1` its run. A transaction already recorded for the run is kept.
+const INSERT_TRANSACTION: &str = "INSERT OR IGNORE INTO transactions (run_id, tx_hash, tx_type, \
+    nonce, retries, sent_at, max_fee_per_gas, max_priority_fee_per_gas, gas_limit, status, block_number, \
+    gas_used, effective_gas_price, l1_fee, l1_gas_used, blocks_waited, latency_ms) \
+    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)";
+
+/// Columns of `transactions` in the order `TxRecord::from_row` reads them
+const TX_COLUMNS: &str = "t.run_id, t.tx_hash, t.tx_type, t.nonce, t.retries, t.sent_at, \
+    t.max_fee_per_gas, t.max_priority_fee_per_gas, t.gas_limit, t.status, t.block_number, \
+    t.gas_used, t.effective_gas_price, t.l1_fee, t.l1_gas_used, t.blocks_waited, t.latency_ms";
+
+/// One invocation of the tool that sent transactions
+#[derive(Debug, Clone, PartialEq)]
+pub struct Run {
+    pub started_at: DateTime<Utc>,
+    /// Network profile, or the RPC address without one
+    pub network: String,
+    /// Workload of the GasConsumer calls, none for other calls
+    pub workload: Option<Workload>,
+    pub iterations: Option<u64>,
+}
+
+/// A sent transaction with what its receipt says about it. Receipt fields are empty when
+/// it wasn't included in time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TxRecord {
+    /// Run the transaction belongs to, 0 until it is recorded
+    pub run_id: i64,
+    pub tx_hash: TxHash,
+    pub tx_type: TxType,
+    pub nonce: u64,
+    pub retries: u32,
+    pub sent_at: DateTime<Utc>,
+    pub max_fee_per_gas: Option<u128>,
+    pub max_priority_fee_per_gas: Option<u128>,
+    pub gas_limit: Option<u64>,
+    pub status: Option<bool>,
+    pub block_number: Option<u64>,
+    pub gas_used: Option<u64>,
+    pub effective_gas_price: Option<u128>,
+    /// OP stack L1 data fee in wei
+    pub l1_fee: Option<u128>,
+    pub l1_gas_used: Option<u64>,
+    /// Inclusion timings, only tracked over WebSocket
+    pub blocks_waited: Option<u64>,
+    pub latency: Option<Duration>,
+}
+
+impl TxRecord {
+    /// Record of a transaction just accepted by the RPC, without receipt fields
+    pub fn sent(tx: &TxEnvelope, sent: &SentTransaction) -> Result<Self> {
+        Ok(Self {
+            run_id: 0,
+            tx_hash: sent.tx_hash,
+            tx_type: sent.tx_type,
+            nonce: sent.nonce,
+            retries: sent.retries,
+            sent_at: Utc::now() - chrono::Duration::from_std(sent.sent_at.elapsed())?,
+            max_fee_per_gas: Some(tx.max_fee_per_gas()),
+            max_priority_fee_per_gas: tx.max_priority_fee_per_gas(),
+            gas_limit: Some(tx.gas_limit()),
+            status: None,
+            block_number: None,
+            gas_used: None,
+            effective_gas_price: None,
+            l1_fee: None,
+            l1_gas_used: None,
+            blocks_waited: None,
+            latency: None,
+        })
+    }
+
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        let text = |index: usize| -> rusqlite::Result<String> { row.get(index) };
+        let invalid = |index: usize, err: String| {
+            rusqlite::Error::FromSqlConversionFailure(
+                index,
+                rusqlite::types::Type::Text,
+                err.into(),
+            )
+        };
+
+        Ok(Self {
+            run_id: row.get(0)?,
+            tx_hash: text(1)?
+                .parse()
+                .map_err(|err| invalid(1, format!("{err}")))?,
+            tx_type: TxType::from_str(&text(2)?, false).map_err(|err| invalid(2, err))?,
+            nonce: row.get(3)?,
+            retries: row.get(4)?,
+            sent_at: DateTime::parse_from_rfc3339(&text(5)?)
+                .map_err(|err| invalid(5, err.to_string()))?
+                .with_timezone(&Utc),
+            max_fee_per_gas: wei_from_row(row, 6)?,
+            max_priority_fee_per_gas: wei_from_row(row, 7)?,
+            gas_limit: row.get(8)?,
+            status: row.get(9)?,
+            block_number: row.get(10)?,
+            gas_used: row.get(11)?,
+            effective_gas_price: wei_from_row(row, 12)?,
+            l1_fee: wei_from_row(row, 13)?,
+            l1_gas_used: row.get(14)?,
+            blocks_waited: row.get(15)?,
+            latency: row.get::<_, Option<u64>>(16)?.map(Duration::from_millis),
+        })
+    }
+}
+
+/// Wei amounts are stored as decimal text, since SQLite integers only hold up to about 9.2 ETH
+fn wei(value: Option<u128>) -> Option<String> {
+    value.map(|value| value.to_string())
+}
+
+fn wei_from_row(row: &Row<'_>, index: usize) -> rusqlite::Result<Option<u128>> {
+    row.get::<_, Option<String>>(index)?
+        .map(|value| {
+            value.parse().map_err(|err| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    index,
+                    rusqlite::types::Type::Text,
+                    Box::new(err),
+                )
+            })
+        })
+        .transpose()
+}
+
+fn insert_run(conn: &Connection, run: &Run) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO runs (started_at, network, workload, iterations) VALUES (?1, ?2, ?3, ?4)",
+        params![
+            timestamp(run.started_at),
+            run.network,
+            run.workload.map(value_name),
+            run.iterations
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+fn insert_transaction(conn: &Connection, run_id: i64, tx: &TxRecord) -> Result<()> {
+    conn.prepare_cached(INSERT_TRANSACTION)?.execute(params![
+        run_id,
+        tx.tx_hash.to_string(),
+        value_name(tx.tx_type),
+        tx.nonce,
+        tx.retries,
+        timestamp(tx.sent_at),
+        wei(tx.max_fee_per_gas),
+        wei(tx.max_priority_fee_per_gas),
+        tx.gas_limit,
+        tx.status,
+        tx.block_number,
+        tx.gas_used,
+        wei(tx.effective_gas_price),
+        wei(tx.l1_fee),
+        tx.l1_gas_used,
+        tx.blocks_waited,
+        tx.latency.map(|latency| latency.as_millis() as u64),
+    ])?;
+    Ok(())
+}
+
+/// Selects the recorded transactions to query
+#[derive(Debug, Clone, Default)]
+pub struct ResultsFilter {
+    pub run_id: Option<i64>,
+    pub network: Option<String>,
+    pub tx_type: Option<TxType>,
+    pub workload: Option<Workload>,
+    pub iterations: Option<u64>,
+    /// Only transactions sent at or after this time
+    pub since: Option<DateTime<Utc>>,
+}
+
+impl ResultsFilter {
+    fn where_clause(&self) -> (String, Vec<Value>) {
+        let mut conditions = Vec::new();
+        let mut values = Vec::new();
+        if let Some(run_id) = self.run_id {
+            conditions.push("r.id = ?");
+            values.push(Value::Integer(run_id));
+        }
+        if let Some(network) = &self.network {
+            conditions.push("r.network = ?");
+            values.push(Value::Text(network.clone()));
+        }
+        if let Some(tx_type) = self.tx_type {
+            conditions.push("t.tx_type = ?");
+            values.push(Value::Text(value_name(tx_type)));
+        }
+        if let Some(workload) = self.workload {
+            conditions.push("r.workload = ?");
+            values.push(Value::Text(value_name(workload)));
+        }
+        if let Some(iterations) = self.iterations {
+            conditions.push("r.iterations = ?");
+            values.push(Value::Integer(iterations as i64));
+        }
+        if let Some(since) = self.since {
+            conditions.push("t.sent_at >= ?");
+            values.push(Value::Text(timestamp(since)));
+        }
+
+        if conditions.is_empty() {
+            (String::new(), values)
+        } else {
+            (format!("WHERE {}", conditions.join(" AND ")), values)
+        }
+    }
+}
+
+/// Column the aggregates of `results query` are grouped by
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum GroupBy {
+    #[default]
+    Run,
+    Network,
+    TxType,
+    Workload,
+}
+
+impl GroupBy {
+    fn column(self) -> &'static str {
+        match self {
+            GroupBy::Run => "CAST(r.id AS TEXT)",
+            GroupBy::Network => "r.network",
+            GroupBy::TxType => "t.tx_type",
+            GroupBy::Workload => "COALESCE(r.workload, '-')",
+        }
+    }
+
+    fn header(self) -> &'static str {
+        match self {
+            GroupBy::Run => "Run",
+            GroupBy::Network => "Network",
+            GroupBy::TxType => "Type",
+            GroupBy::Workload => "Workload",
+        }
+    }
+}
+
+/// Statistics of a group of recorded transactions. Averages only count the transactions
+/// that have the value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Aggregate {
+    pub key: String,
+    pub transactions: u64,
+    pub included: u64,
+    pub succeeded: u64,
+    pub avg_gas_used: Option<f64>,
+    pub min_gas_used: Option<u64>,
+    pub max_gas_used: Option<u64>,
+    pub avg_gas_price: Option<f64>,
+    pub avg_l1_fee: Option<f64>,
+    pub avg_latency_ms: Option<f64>,
+}
+
+/// SQLite file the sent transactions of every run are recorded in
+pub struct ResultsDb {
+    conn: Connection,
+}
+
+impl ResultsDb {
+    /// Default database in the gas-test data directory
+    pub fn default_path() -> Result<PathBuf> {
+        let data_dir = dirs::data_dir()
+            .ok_or_else(|| eyre::eyre!("No data directory for the current user"))?;
+        Ok(data_dir.join("gas-test").join("results.db"))
+    }
+
+    /// Opens the database, creating it and its tables if needed
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(path).map_err(|err| {
+            eyre::eyre!(
+                "Failed to open results database {}: {}",
+                path.display(),
+                err
+            )
+        })?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self { conn })
+    }
+
+    /// Records a run with its transactions and returns the ID of the run
+    pub fn record(&mut self, run: &Run, transactions: &[TxRecord]) -> Result<i64> {
+        let db = self.conn.transaction()?;
+        let run_id = insert_run(&db, run)?;
+        for tx in transactions {
+            insert_transaction(&db, run_id, tx)?;
+        }
+        db.commit()?;
+        Ok(run_id)
+    }
+
+    /// The run with the given ID, if it was recorded
+    pub fn run(&self, run_id: i64) -> Result<Option<Run>> {
+        let run = self
+            .conn
+            .query_row(
+                "SELECT started_at, network, workload, iterations FROM runs WHERE id = ?1",
+                [run_id],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, Option<String>>(2)?,
+                        row.get::<_, Option<u64>>(3)?,
+                    ))
+                },
+            )
+            .optional()?;
+        let Some((started_at, network, workload, iterations)) = run else {
+            return Ok(None);
+        };
+
+        Ok(Some(Run {
+            started_at: DateTime::parse_from_rfc3339(&started_at)?.with_timezone(&Utc),
+            network,
+            workload: workload
+                .map(|workload| Workload::from_str(&workload, false))
+                .transpose()
+                .map_err(|err| eyre::eyre!(err))?,
+            iterations,
+        }))
+    }
+
+    /// Recorded transactions matching `filter`, in the order they were sent
+    pub fn transactions(&self, filter: &ResultsFilter) -> Result<Vec<TxRecord>> {
+        let (where_clause, values) = filter.where_clause();
+        let mut query = self.conn.prepare(&format!(
+            "SELECT {} FROM transactions t JOIN runs r ON r.id = t.run_id {} ORDER BY t.id",
+            TX_COLUMNS, where_clause
+        ))?;
+        let records = query
+            .query_map(params_from_iter(values), TxRecord::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(records)
+    }
+
+    /// Statistics of the transactions matching `filter`, one per group in the order the
+    /// groups were first recorded
+    pub fn aggregate(&self, filter: &ResultsFilter, group_by: GroupBy) -> Result<Vec<Aggregate>> {
+        let (where_clause, values) = filter.where_clause();
+        let mut query = self.conn.prepare(&format!(
+            "SELECT {key}, COUNT(*), COUNT(t.block_number), COALESCE(SUM(t.status = 1), 0), \
+             AVG(t.gas_used), MIN(t.gas_used), MAX(t.gas_used), \
+             AVG(CAST(t.effective_gas_price AS REAL)), AVG(CAST(t.l1_fee AS REAL)), \
+             AVG(t.latency_ms) \
+             FROM transactions t JOIN runs r ON r.id = t.run_id {where_clause} \
+             GROUP BY {key} ORDER BY MIN(t.id)",
+            key = group_by.column(),
+        ))?;
+        let aggregates = query
+            .query_map(params_from_iter(values), |row| {
+                Ok(Aggregate {
+                    key: row.get(0)?,
+                    transactions: row.get(1)?,
+                    included: row.get(2)?,
+                    succeeded: row.get(3)?,
+                    avg_gas_used: row.get(4)?,
+                    min_gas_used: row.get(5)?,
+                    max_gas_used: row.get(6)?,
+                    avg_gas_price: row.get(7)?,
+                    avg_l1_fee: row.get(8)?,
+                    avg_latency_ms: row.get(9)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(aggregates)
+    }
+
+    /// Statistics of all transactions of a run
+    pub fn run_aggregate(&self, run_id: i64) -> Result<Aggregate> {
+        let filter = ResultsFilter {
+            run_id: Some(run_id),
+            ..ResultsFilter::default()
+        };
+        self.aggregate(&filter, GroupBy::Run)?
+            .pop()
+            .ok_or_else(|| eyre::eyre!("No transactions recorded for run {}", run_id))
+    }
+}
+
+/// Records every transaction a client sends in the results database, and what the client later
+/// learns about it: its receipt, L1 fee and inclusion timings. The run is only stored with its
+/// first transaction, so commands that send nothing leave no empty runs behind.
+///
+/// Writes are handed to a writer thread, so sending never waits for SQLite. Writes that queue up
+/// while the thread is busy are committed in one transaction.
+pub struct ResultsRecorder {
+    path: PathBuf,
+    writes: Mutex<Option<Sender<Write>>>,
+    writer: Mutex<Option<JoinHandle<Option<i64>>>>,
+}
+
+/// A change to the recorded run, applied by the writer thread in the order it was made
+enum Write {
+    Workload {
+        workload: Option<Workload>,
+        iterations: Option<u64>,
+    },
+    Sent(TxRecord),
+    /// Sets columns of a transaction of the run. `assignments` start at `?3`, after the run ID
+    /// and the transaction hash.
+    Update {
+        assignments: &'static str,
+        tx_hash: TxHash,
+        values: Vec<Value>,
+    },
+}
+
+impl ResultsRecorder {
+    pub fn new(path: PathBuf, run: Run) -> Self {
+        let (writes, received) = mpsc::channel();
+        let writer = Writer {
+            path: path.clone(),
+            run,
+            recorded: None,
+        };
+        Self {
+            path,
+            writes: Mutex::new(Some(writes)),
+            writer: Mutex::new(Some(std::thread::spawn(move || writer.serve(received)))),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Waits for the pending writes and returns the ID of the run, if a transaction of it was
+    /// recorded. Nothing is recorded after this, and later calls return `None`.
+    pub fn finish(&self) -> Option<i64> {
+        self.writes.lock().unwrap().take();
+        let writer = self.writer.lock().unwrap().take()?;
+        writer.join().ok().flatten()
+    }
+
+    /// Sets the GasConsumer workload of the run. Only takes effect before the first transaction
+    /// is recorded.
+    pub fn set_workload(&self, workload: Option<Workload>, iterations: Option<u64>) -> Result<()> {
+        self.write(Write::Workload {
+            workload,
+            iterations,
+        })
+    }
+
+    /// Records a transaction accepted by the RPC. A transaction the run already holds, e.g. one
+    /// the node reported as already known, is only recorded once.
+    pub fn record_sent(&self, tx: &TxEnvelope, sent: &SentTransaction) -> Result<()> {
+        self.write(Write::Sent(TxRecord::sent(tx, sent)?))
+    }
+
+    /// Fills in the receipt fields of a recorded transaction
+    pub fn record_receipt(&self, receipt: &TransactionReceipt) -> Result<()> {
+        self.write(Write::Update {
+            assignments: "status = ?3, block_number = ?4, gas_used = ?5, effective_gas_price = ?6",
+            tx_hash: receipt.transaction_hash,
+            values: vec![
+                Value::Integer(receipt.status() as i64),
+                integer(receipt.block_number),
+                integer(Some(receipt.gas_used)),
+                text(wei(Some(receipt.effective_gas_price))),
+            ],
+        })
+    }
+
+    /// Fills in the L1 fee of a recorded transaction from the OP stack fields of its receipt
+    pub fn record_l1_fee(&self, tx_hash: TxHash, l1: &OpReceiptFields) -> Result<()> {
+        self.write(Write::Update {
+            assignments: "l1_fee = ?3, l1_gas_used = ?4",
+            tx_hash,
+            values: vec![
+                text(wei(l1.l1_fee.map(|fee| fee.saturating_to()))),
+                integer(l1.l1_gas_used.map(|gas| gas.saturating_to())),
+            ],
+        })
+    }
+
+    /// Fills in the inclusion timings of a recorded transaction
+    pub fn record_inclusion(&self, inclusion: &Inclusion) -> Result<()> {
+        self.write(Write::Update {
+            assignments: "blocks_waited = ?3, latency_ms = ?4",
+            tx_hash: inclusion.tx_hash,
+            values: vec![
+                integer(Some(inclusion.blocks_waited)),
+                integer(Some(inclusion.latency.as_millis() as u64)),
+            ],
+        })
+    }
+
+    fn write(&self, write: Write) -> Result<()> {
+        let writes = self.writes.lock().unwrap();
+        let writes = writes
+            .as_ref()
+            .ok_or_else(|| eyre::eyre!("The run was already finished"))?;
+        writes
+            .send(write)
+            .map_err(|_| eyre::eyre!("The results writer stopped"))
+    }
+}
+
+/// Flushes the pending writes when the recorder goes away without `finish`, e.g. on an error
+impl Drop for ResultsRecorder {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// State of the writer thread of a `ResultsRecorder`
+struct Writer {
+    path: PathBuf,
+    run: Run,
+    /// Database and ID of the run, once its first transaction is recorded
+    recorded: Option<(ResultsDb, i64)>,
+}
+
+impl Writer {
+    /// Applies writes until the recorder is finished and returns the ID of the run
+    fn serve(mut self, writes: Receiver<Write>) -> Option<i64> {
+        while let Ok(write) = writes.recv() {
+            let batch: Vec<Write> = std::iter::once(write).chain(writes.try_iter()).collect();
+            if let Err(err) = self.apply(batch) {
+                tracing::warn!(
+                    "Failed to record results in {}: {}",
+                    self.path.display(),
+                    err
+                );
+            }
+        }
+        self.recorded.map(|(_, run_id)| run_id)
+    }
+
+    fn apply(&mut self, batch: Vec<Write>) -> Result<()> {
+        let sends = batch.iter().any(|write| matches!(write, Write::Sent(_)));
+        let (mut db, mut run_id) = match self.recorded.take() {
+            Some((db, run_id)) => (db, Some(run_id)),
+            None if sends => (ResultsDb::open(&self.path)?, None),
+            None => {
+                // Nothing was recorded yet, so there is nothing to update either
+                for write in batch {
+                    if let Write::Workload {
+                        workload,
+                        iterations,
+                    } = write
+                    {
+                        self.run.workload = workload;
+                        self.run.iterations = iterations;
+                    }
+                }
+                return Ok(());
+            }
+        };
+
+        // A run stored by a batch that failed was rolled back with it
+        let stored_run_id = run_id;
+        let result = apply_batch(&mut db, &mut self.run, &mut run_id, batch);
+        if result.is_err() {
+            run_id = stored_run_id;
+        }
+        self.recorded = run_id.map(|run_id| (db, run_id));
+        result
+    }
+}
+
+/// Applies writes in one transaction, setting `run_id` once the run is stored
+fn apply_batch(
+    db: &mut ResultsDb,
+    run: &mut Run,
+    run_id: &mut Option<i64>,
+    batch: Vec<Write>,
+) -> Result<()> {
+    let tx = db.conn.transaction()?;
+    for write in batch {
+        match write {
+            Write::Workload {
+                workload,
+                iterations,
+            } => {
+                if run_id.is_none() {
+                    run.workload = workload;
+                    run.iterations = iterations;
+                }
+            }
+            Write::Sent(record) => {
+                let id = match *run_id {
+                    Some(id) => id,
+                    None => *run_id.insert(insert_run(&tx, run)?),
+                };
+                insert_transaction(&tx, id, &record)?;
+            }
+            Write::Update {
+                assignments,
+                tx_hash,
+                values,
+            } => {
+                // Transactions sent before the recorder was set are skipped
+                let Some(id) = *run_id else {
+                    continue;
+                };
+                let keys = [Value::Integer(id), Value::Text(tx_hash.to_string())];
+                tx.execute(
+                    &format!(
+                        "UPDATE transactions SET {} WHERE run_id = ?1 AND tx_hash = ?2",
+                        assignments
+                    ),
+                    params_from_iter(keys.into_iter().chain(values)),
+                )?;
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+fn integer(value: Option<u64>) -> Value {
+    value.map_or(Value::Null, |value| Value::Integer(value as i64))
+}
+
+fn text(value: Option<String>) -> Value {
+    value.map_or(Value::Null, Value::Text)
+}
+
+/// RFC 3339 in UTC with milliseconds, which sorts in time order as text
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Command line name of a value, also stored in the database
+fn value_name<T: ValueEnum>(value: T) -> String {
+    value
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
+
+fn format_optional(value: Option<f64>, precision: usize) -> String {
+    value
+        .map(|value| format!("{:.*}", precision, value))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+/// Table of aggregates, the first column headed by what they are grouped by
+pub fn query_table(aggregates: &[Aggregate], group_by: GroupBy) -> String {
+    let header = group_by.header();
+    let width = aggregates
+        .iter()
+        .map(|aggregate| aggregate.key.len())
+        .max()
+        .unwrap_or(0)
+        .max(header.len());
+
+    let mut table = format!(
+        "{:<width$}  {:>5}  {:>8}  {:>9}  {:>12}  {:>10}  {:>10}  {:>14}  {:>14}  {:>11}\n",
+        header,
+        "Txs",
+        "Included",
+        "Succeeded",
+        "Avg Gas",
+        "Min Gas",
+        "Max Gas",
+        "Avg Gas Price",
+        "Avg L1 Fee",
+        "Avg Latency"
+    );
+    for aggregate in aggregates {
+        table.push_str(&format!(
+            "{:<width$}  {:>5}  {:>8}  {:>9}  {:>12}  {:>10}  {:>10}  {:>14}  {:>14}  {:>11}\n",
+            aggregate.key,
+            aggregate.transactions,
+            aggregate.included,
+            aggregate.succeeded,
+            format_optional(aggregate.avg_gas_used, 1),
+            format_optional(aggregate.min_gas_used.map(|gas| gas as f64), 0),
+            format_optional(aggregate.max_gas_used.map(|gas| gas as f64), 0),
+            format_optional(aggregate.avg_gas_price, 0),
+            format_optional(aggregate.avg_l1_fee, 0),
+            aggregate
+                .avg_latency_ms
+                .map(|latency| format!("{:.0}ms", latency))
+                .unwrap_or_else(|| "-".to_string()),
+        ));
+    }
+    table
+}
+
+/// Table of individual transactions
+pub fn transactions_table(records: &[TxRecord]) -> String {
+    let mut table = format!(
+        "{:>4}  {:<66}  {:<11}  {:<8}  {:>8}  {:>10}  {:>14}  {:>12}  {:>8}\n",
+        "Run", "Hash", "Type", "Status", "Block", "Gas Used", "Gas Price", "L1 Fee", "Latency"
+    );
+    for record in records {
+        let status = match record.status {
+            Some(true) => "success",
+            Some(false) => "reverted",
+            None => "pending",
+        };
+        let optional = |value: Option<u128>| {
+            value
+                .map(|value| value.to_string())
+                .unwrap_or_else(|| "-".to_string())
+        };
+        table.push_str(&format!(
+            "{:>4}  {:<66}  {:<11}  {:<8}  {:>8}  {:>10}  {:>14}  {:>12}  {:>8}\n",
+            record.run_id,
+            record.tx_hash.to_string(),
+            value_name(record.tx_type),
+            status,
+            optional(record.block_number.map(u128::from)),
+            optional(record.gas_used.map(u128::from)),
+            optional(record.effective_gas_price),
+            optional(record.l1_fee),
+            record
+                .latency
+                .map(|latency| format!("{}ms", latency.as_millis()))
+                .unwrap_or_else(|| "-".to_string()),
+        ));
+    }
+    table
+}
+
+/// A statistic of two runs side by side
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricDiff {
+    pub metric: &'static str,
+    pub a: Option<f64>,
+    pub b: Option<f64>,
+}
+
+impl MetricDiff {
+    pub fn delta(&self) -> Option<f64> {
+        Some(self.b? - self.a?)
+    }
+
+    /// Change from `a` to `b` in percent
+    pub fn percent(&self) -> Option<f64> {
+        let a = self.a.filter(|a| *a != 0.0)?;
+        Some(self.delta()? * 100.0 / a)
+    }
+}
+
+/// Compares the statistics of run `a` with those of run `b`
+pub fn diff(a: &Aggregate, b: &Aggregate) -> Vec<MetricDiff> {
+    let row = |metric, value: fn(&Aggregate) -> Option<f64>| MetricDiff {
+        metric,
+        a: value(a),
+        b: value(b),
+    };
+    vec![
+        row("Transactions", |run| Some(run.transactions as f64)),
+        row("Included", |run| Some(run.included as f64)),
+        row("Succeeded", |run| Some(run.succeeded as f64)),
+        row("Avg Gas Used", |run| run.avg_gas_used),
+        row("Min Gas Used", |run| run.min_gas_used.map(|gas| gas as f64)),
+        row("Max Gas Used", |run| run.max_gas_used.map(|gas| gas as f64)),
+        row("Avg Gas Price (wei)", |run| run.avg_gas_price),
+        row("Avg L1 Fee (wei)", |run| run.avg_l1_fee),
+        row("Avg Latency (ms)", |run| run.avg_latency_ms),
+    ]
+}
+
+/// Table of a run comparison, with columns headed by the run labels
+pub fn diff_table(a: &str, b: &str, diffs: &[MetricDiff]) -> String {
+    let mut table = format!(
+        "{:<20}  {:>14}  {:>14}  {:>14}  {:>8}\n",
+        "Metric", a, b, "Delta", "Change"
+    );
+    for diff in diffs {
+        let change = diff
+            .percent()
+            .map(|percent| format!("{:+.2}%", percent))
+            .unwrap_or_else(|| "-".to_string());
+        table.push_str(&format!(
+            "{:<20}  {:>14}  {:>14}  {:>14}  {:>8}\n",
+            diff.metric,
+            format_optional(diff.a, 1),
+            format_optional(diff.b, 1),
+            diff.delta()
+                .map(|delta| format!("{:+.1}", delta))
+                .unwrap_or_else(|| "-".to_string()),
+            change
+        ));
+    }
+    table
+}
@@ -0,0 +1,139 @@
+use alloy_consensus::Transaction;
+use alloy_primitives::{Address, Bytes, TxHash};
+use alloy_provider::{Provider, ProviderBuilder, WsConnect};
+use alloy_rpc_types_eth::{BlockId, TransactionInput, TransactionRequest};
+use alloy_sol_types::SolCall;
+use alloy_transport::RpcError;
+use eyre::Result;
+use reqwest::Url;
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::send_error::entry_point_error;
+
+/// Returns true if the RPC URL uses a pubsub capable transport (`ws://` or `wss://`)
+pub fn is_pubsub(rpc_uri: &str) -> bool {
+    rpc_uri.starts_with("ws://") || rpc_uri.starts_with("wss://")
+}
+
+/// Connects to the given RPC URL, using a WebSocket provider for `ws://` and `wss://`
+/// URLs and an HTTP provider otherwise
+pub async fn connect(rpc_uri: &str) -> Result<Arc<dyn Provider>> {
+    let provider = if is_pubsub(rpc_uri) {
+        ProviderBuilder::new()
+            .on_ws(WsConnect::new(rpc_uri))
+            .await?
+            .boxed()
+    } else {
+        ProviderBuilder::new()
+            .on_http(rpc_uri.parse::<Url>()?)
+            .boxed()
+    };
+
+    Ok(Arc::new(provider))
+}
+
+/// Calls a contract function with `eth_call` and decodes its return values
+pub async fn call<C: SolCall>(provider: &Arc<dyn Provider>, to: Address, call: C) -> Result<C::Return> {
+    let tx = TransactionRequest::default()
+        .to(to)
+        .input(TransactionInput::new(call.abi_encode().into()));
+    let output = provider.call(&tx).await?;
+    Ok(C::abi_decode_returns(&output, true)?)
+}
+
+/// Call request replaying an included transaction and the parent block to replay it on.
+/// Receipts carry neither return nor revert data, replaying is how to get them without a trace.
+/// The replay doesn't see the transactions before this one in its block, so its outcome can
+/// differ from the included one.
+pub async fn replay_request(
+    provider: &Arc<dyn Provider>,
+    tx_hash: TxHash,
+) -> Result<(TransactionRequest, BlockId)> {
+    let tx = provider
+        .get_transaction_by_hash(tx_hash)
+        .await?
+        .ok_or_else(|| eyre::eyre!("Transaction {:?} not found", tx_hash))?;
+    let block_number = tx
+        .block_number
+        .ok_or_else(|| eyre::eyre!("Transaction {:?} is not included yet", tx_hash))?;
+
+    let mut request = TransactionRequest::default()
+        .from(tx.from)
+        .input(TransactionInput::new(tx.input().clone()))
+        .value(tx.value());
+    if let Some(to) = tx.to() {
+        request = request.to(to);
+    }
+    Ok((request, BlockId::number(block_number.saturating_sub(1))))
+}
+
+/// Top level frame of a `callTracer` trace
+#[derive(Debug, Deserialize)]
+struct CallFrame {
+    output: Option<Bytes>,
+    error: Option<String>,
+}
+
+/// What an included transaction returned or reverted with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxOutput {
+    /// Return data, or revert data when `error` is set
+    pub output: Bytes,
+    /// Why the transaction reverted
+    pub error: Option<String>,
+    /// Whether the output comes from replaying the transaction on its parent block, see
+    /// `replay_request`, because the node has no `debug_traceTransaction`
+    pub replayed: bool,
+}
+
+impl TxOutput {
+    /// The PBH entry point error of the revert data, the error message otherwise. Replayed
+    /// outcomes are marked as such, and a replay that didn't revert only says the transaction did.
+    pub fn revert_reason(&self) -> String {
+        let reason = match &self.error {
+            Some(error) => entry_point_error(&self.output)
+                .map(str::to_string)
+                .unwrap_or_else(|| error.clone()),
+            None => "reverted".to_string(),
+        };
+        if self.replayed {
+            format!("{reason} (replayed on the parent block)")
+        } else {
+            reason
+        }
+    }
+}
+
+/// Output of an included transaction from a `callTracer` trace or, on nodes without the debug
+/// namespace, from replaying it on the parent block
+pub async fn transaction_output(provider: &Arc<dyn Provider>, tx_hash: TxHash) -> Result<TxOutput> {
+    let trace: Result<CallFrame, _> = provider
+        .raw_request(
+            "debug_traceTransaction".into(),
+            (tx_hash, serde_json::json!({ "tracer": "callTracer" })),
+        )
+        .await;
+    if let Ok(frame) = trace {
+        return Ok(TxOutput {
+            output: frame.output.unwrap_or_default(),
+            error: frame.error,
+            replayed: false,
+        });
+    }
+
+    let (request, block) = replay_request(provider, tx_hash).await?;
+    Ok(match provider.call(&request).block(block).await {
+        Ok(output) => TxOutput {
+            output,
+            error: None,
+            replayed: true,
+        },
+        Err(RpcError::ErrorResp(payload)) => TxOutput {
+            output: payload.as_revert_data().unwrap_or_default(),
+            error: Some(payload.message.to_string()),
+            replayed: true,
+        },
+        Err(err) => return Err(err.into()),
+    })
+}
@@ -0,0 +1,517 @@
+use alloy_primitives::{Address, Bytes, TxHash, B256, U256};
+use alloy_provider::Provider;
+use alloy_rpc_types_eth::TransactionReceipt;
+use eyre::Result;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::client::GasTestClient;
+use crate::identity::IdentityScheduler;
+use crate::rpc;
+use crate::send_error::{normalize, rejection_reason};
+use crate::transaction::single_call_multicall;
+use crate::workload::Workload;
+use crate::world_id::WorldID;
+
+/// Interval between block number checks in `wait-blocks`
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Multi-step test run read from a TOML file with a `[[steps]]` table per step
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub name: Option<String>,
+    pub steps: Vec<Step>,
+}
+
+/// A step of a scenario, selected by its `action`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "action", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum Step {
+    /// Deploy GasConsumer. Later sends call the new contract.
+    Deploy { salt: Option<B256> },
+    /// Send `amount` wei to every account and wait for the transfers
+    Fund {
+        accounts: Vec<Address>,
+        amount: U256,
+    },
+    /// Send `count` GasConsumer calls directly
+    SendDirect {
+        label: String,
+        #[serde(default = "default_count")]
+        count: u64,
+        #[serde(default)]
+        workload: Workload,
+        iterations: u64,
+    },
+    /// Send `count` GasConsumer calls wrapped in `pbhMulticall`. Without `pbh_nonce` every
+    /// transaction gets the unused nonce of the identity with the most quota left, with it
+    /// the first identity counts up from `pbh_nonce`.
+    SendPbh {
+        label: String,
+        #[serde(default = "default_count")]
+        count: u64,
+        #[serde(default)]
+        workload: Workload,
+        iterations: u64,
+        pbh_nonce: Option<u16>,
+    },
+    /// Wait until `blocks` more blocks are produced
+    WaitBlocks { blocks: u64 },
+    /// Every transaction of `label` used at least `min` and at most `max` gas
+    AssertGas {
+        label: String,
+        min: Option<u64>,
+        max: Option<u64>,
+    },
+    /// Every transaction of `label` succeeded, or with `success = false` was rejected or reverted
+    AssertStatus { label: String, success: bool },
+    /// Every transaction of `label` was rejected or reverted for `reason`, compared ignoring
+    /// case and punctuation
+    AssertRevert { label: String, reason: String },
+    /// Every transaction of `first` was included before every transaction of `then`
+    AssertOrder { first: String, then: String },
+}
+
+fn default_count() -> u64 {
+    1
+}
+
+impl Scenario {
+    /// Reads and validates a scenario file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| eyre::eyre!("Failed to read scenario {}: {}", path.display(), err))?;
+        let scenario: Self = toml::from_str(&content)
+            .map_err(|err| eyre::eyre!("Invalid scenario {}: {}", path.display(), err))?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    /// Checks that assertions only refer to labels of earlier send steps
+    pub fn validate(&self) -> Result<()> {
+        let mut labels = HashSet::new();
+        for (index, step) in self.steps.iter().enumerate() {
+            let invalid =
+                |reason: String| eyre::eyre!("Step {} ({}): {}", index + 1, step.action(), reason);
+            match step {
+                Step::SendDirect { label, .. } | Step::SendPbh { label, .. } => {
+                    labels.insert(label.as_str());
+                }
+                Step::AssertGas {
+                    min: None,
+                    max: None,
+                    ..
+                } => return Err(invalid("needs min, max or both".to_string())),
+                _ => {}
+            }
+            for label in step.asserted_labels() {
+                if !labels.contains(label) {
+                    return Err(invalid(format!("no earlier step sends `{}`", label)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether any step sends PBH transactions, which need identities
+    pub fn sends_pbh(&self) -> bool {
+        self.steps
+            .iter()
+            .any(|step| matches!(step, Step::SendPbh { .. }))
+    }
+}
+
+impl Step {
+    /// The `action` of the step as written in the scenario
+    pub fn action(&self) -> &'static str {
+        match self {
+            Self::Deploy { .. } => "deploy",
+            Self::Fund { .. } => "fund",
+            Self::SendDirect { .. } => "send-direct",
+            Self::SendPbh { .. } => "send-pbh",
+            Self::WaitBlocks { .. } => "wait-blocks",
+            Self::AssertGas { .. } => "assert-gas",
+            Self::AssertStatus { .. } => "assert-status",
+            Self::AssertRevert { .. } => "assert-revert",
+            Self::AssertOrder { .. } => "assert-order",
+        }
+    }
+
+    fn asserted_labels(&self) -> Vec<&str> {
+        match self {
+            Self::AssertGas { label, .. }
+            | Self::AssertStatus { label, .. }
+            | Self::AssertRevert { label, .. } => vec![label.as_str()],
+            Self::AssertOrder { first, then } => vec![first.as_str(), then.as_str()],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Transaction sent by a scenario step
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScenarioTx {
+    pub label: String,
+    pub tx_hash: Option<TxHash>,
+    /// Why sending failed, if the RPC refused the transaction
+    pub rejected: Option<String>,
+}
+
+/// What a step did
+#[derive(Debug, Clone)]
+pub enum StepResult {
+    Deployed(Address),
+    Funded(Vec<TxHash>),
+    Sent(Vec<ScenarioTx>),
+    Waited {
+        block_number: u64,
+    },
+    /// Outcome of an assertion, passed if there are no failures
+    Asserted {
+        failures: Vec<String>,
+    },
+}
+
+impl StepResult {
+    /// Whether an assertion passed, `None` for steps that assert nothing
+    pub fn passed(&self) -> Option<bool> {
+        match self {
+            Self::Asserted { failures } => Some(failures.is_empty()),
+            _ => None,
+        }
+    }
+}
+
+/// Results of every step of a scenario, in order
+#[derive(Debug, Clone)]
+pub struct ScenarioReport {
+    pub results: Vec<StepResult>,
+}
+
+impl ScenarioReport {
+    pub fn passed(&self) -> usize {
+        self.assertions(true)
+    }
+
+    pub fn failed(&self) -> usize {
+        self.assertions(false)
+    }
+
+    fn assertions(&self, passed: bool) -> usize {
+        self.results
+            .iter()
+            .filter(|result| result.passed() == Some(passed))
+            .count()
+    }
+}
+
+/// How a sent transaction ended
+enum TxOutcome {
+    Rejected(String),
+    Included(TransactionReceipt),
+}
+
+/// Executes scenario steps with a client, keeping the transactions of earlier steps for the
+/// assertions of later ones
+pub struct ScenarioRunner<'a> {
+    client: &'a GasTestClient,
+    identities: Vec<WorldID>,
+    scheduler: Option<IdentityScheduler>,
+    contract_address: Address,
+    timeout: Duration,
+    txs: Vec<ScenarioTx>,
+    receipts: HashMap<TxHash, TransactionReceipt>,
+}
+
+impl<'a> ScenarioRunner<'a> {
+    /// Runner sending with `client`, proving PBH transactions with `identities`. `timeout` bounds
+    /// the wait for every receipt and `wait-blocks` step.
+    pub fn new(client: &'a GasTestClient, identities: Vec<WorldID>, timeout: Duration) -> Self {
+        Self {
+            client,
+            identities,
+            scheduler: None,
+            contract_address: client.network().contract_address,
+            timeout,
+            txs: Vec::new(),
+            receipts: HashMap::new(),
+        }
+    }
+
+    /// Runs every step of `scenario` in order, stopping at the first one that couldn't run
+    pub async fn run(&mut self, scenario: &Scenario) -> Result<ScenarioReport> {
+        let mut results = Vec::with_capacity(scenario.steps.len());
+        for (index, step) in scenario.steps.iter().enumerate() {
+            let result = self.step(step).await.map_err(|err| {
+                err.wrap_err(format!("Step {} ({}) failed", index + 1, step.action()))
+            })?;
+            results.push(result);
+        }
+        Ok(ScenarioReport { results })
+    }
+
+    /// Runs one step. Failed assertions are results, errors are reserved for steps that
+    /// couldn't run.
+    pub async fn step(&mut self, step: &Step) -> Result<StepResult> {
+        match step {
+            Step::Deploy { salt } => {
+                self.contract_address = self.client.deploy(*salt).await?.address;
+                Ok(StepResult::Deployed(self.contract_address))
+            }
+            Step::Fund { accounts, amount } => {
+                let mut tx_hashes = Vec::new();
+                for &account in accounts {
+                    let sent = self
+                        .client
+                        .send_call(account, Bytes::new(), *amount)
+                        .await?;
+                    tx_hashes.push(sent.tx_hash);
+                }
+                for &tx_hash in &tx_hashes {
+                    if !self.receipt(tx_hash).await?.status() {
+                        return Err(eyre::eyre!("Funding transaction {:?} reverted", tx_hash));
+                    }
+                }
+                Ok(StepResult::Funded(tx_hashes))
+            }
+            Step::SendDirect {
+                label,
+                count,
+                workload,
+                iterations,
+            } => {
+                let calldata = self.workload_calldata(*workload, *iterations)?;
+                let mut sent = Vec::new();
+                for _ in 0..*count {
+                    let result = self
+                        .client
+                        .send_call(self.contract_address, calldata.clone(), U256::ZERO)
+                        .await;
+                    sent.push(self.record(label, result.map(|sent| sent.tx_hash)));
+                }
+                Ok(StepResult::Sent(sent))
+            }
+            Step::SendPbh {
+                label,
+                count,
+                workload,
+                iterations,
+                pbh_nonce,
+            } => {
+                // Every nonce of the step is reserved, and checked against numPbhPerMonth, before
+                // the first transaction is sent
+                let plan = self.scheduler().await?.plan(*count, *pbh_nonce)?;
+
+                let calldata = self.workload_calldata(*workload, *iterations)?;
+                let mut sent = Vec::new();
+                for (identity, nonce) in plan {
+                    let calls = single_call_multicall(self.contract_address, calldata.clone());
+                    let world_id = self.identities[identity].clone();
+                    let result = self.client.send_pbh_as(&world_id, calls, nonce).await;
+                    sent.push(self.record(label, result.map(|sent| sent.tx_hash)));
+                }
+                Ok(StepResult::Sent(sent))
+            }
+            Step::WaitBlocks { blocks } => {
+                let provider = self.client.provider();
+                let target = provider.get_block_number().await? + blocks;
+                let deadline = Instant::now() + self.timeout;
+                loop {
+                    let block_number = provider.get_block_number().await?;
+                    if block_number >= target {
+                        return Ok(StepResult::Waited { block_number });
+                    }
+                    if Instant::now() >= deadline {
+                        return Err(eyre::eyre!(
+                            "Block {} not reached within {}s, at block {}",
+                            target,
+                            self.timeout.as_secs(),
+                            block_number
+                        ));
+                    }
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                }
+            }
+            Step::AssertGas { label, min, max } => {
+                let mut failures = Vec::new();
+                for tx in self.labelled(label) {
+                    let receipt = match self.outcome(&tx).await? {
+                        TxOutcome::Included(receipt) => receipt,
+                        TxOutcome::Rejected(reason) => {
+                            failures.push(format!("transaction rejected: {}", reason));
+                            continue;
+                        }
+                    };
+                    let gas_used = receipt.gas_used;
+                    if let Some(min) = min.filter(|&min| gas_used < min) {
+                        failures.push(format!(
+                            "{:?} used {} gas, below {}",
+                            receipt.transaction_hash, gas_used, min
+                        ));
+                    }
+                    if let Some(max) = max.filter(|&max| gas_used > max) {
+                        failures.push(format!(
+                            "{:?} used {} gas, above {}",
+                            receipt.transaction_hash, gas_used, max
+                        ));
+                    }
+                }
+                Ok(StepResult::Asserted { failures })
+            }
+            Step::AssertStatus { label, success } => {
+                let mut failures = Vec::new();
+                for tx in self.labelled(label) {
+                    match self.outcome(&tx).await? {
+                        TxOutcome::Included(receipt) if receipt.status() != *success => {
+                            let status = if receipt.status() {
+                                "succeeded"
+                            } else {
+                                "reverted"
+                            };
+                            failures.push(format!("{:?} {}", receipt.transaction_hash, status));
+                        }
+                        TxOutcome::Rejected(reason) if *success => {
+                            failures.push(format!("transaction rejected: {}", reason));
+                        }
+                        _ => {}
+                    }
+                }
+                Ok(StepResult::Asserted { failures })
+            }
+            Step::AssertRevert { label, reason } => {
+                let expected = normalize(reason);
+                let mut failures = Vec::new();
+                for tx in self.labelled(label) {
+                    let (name, actual) = match self.outcome(&tx).await? {
+                        TxOutcome::Rejected(actual) => ("transaction".to_string(), actual),
+                        TxOutcome::Included(receipt) if receipt.status() => {
+                            failures.push(format!("{:?} succeeded", receipt.transaction_hash));
+                            continue;
+                        }
+                        TxOutcome::Included(receipt) => (
+                            format!("{:?}", receipt.transaction_hash),
+                            self.revert_reason(receipt.transaction_hash).await?,
+                        ),
+                    };
+                    if !normalize(&actual).contains(&expected) {
+                        failures.push(format!("{} failed for another reason: {}", name, actual));
+                    }
+                }
+                Ok(StepResult::Asserted { failures })
+            }
+            Step::AssertOrder { first, then } => {
+                let mut failures = Vec::new();
+                let mut positions = Vec::new();
+                for label in [first, then] {
+                    let mut label_positions = Vec::new();
+                    for tx in self.labelled(label) {
+                        match self.outcome(&tx).await? {
+                            TxOutcome::Included(receipt) => label_positions.push((
+                                receipt.block_number.unwrap_or_default(),
+                                receipt.transaction_index.unwrap_or_default(),
+                                receipt.transaction_hash,
+                            )),
+                            TxOutcome::Rejected(reason) => {
+                                failures.push(format!("{} transaction rejected: {}", label, reason))
+                            }
+                        }
+                    }
+                    positions.push(label_positions);
+                }
+
+                if let (Some(last), Some(earliest)) =
+                    (positions[0].iter().max(), positions[1].iter().min())
+                {
+                    if last >= earliest {
+                        failures.push(format!(
+                            "{} {:?} (block {}, index {}) was included after {} {:?} (block {}, index {})",
+                            first, last.2, last.0, last.1, then, earliest.2, earliest.0, earliest.1
+                        ));
+                    }
+                }
+                Ok(StepResult::Asserted { failures })
+            }
+        }
+    }
+
+    fn workload_calldata(&self, workload: Workload, iterations: u64) -> Result<Bytes> {
+        if self.contract_address.is_zero() {
+            return Err(eyre::eyre!(
+                "No GasConsumer to call, set contract_address or deploy it in an earlier step"
+            ));
+        }
+        Ok(workload.calldata(self.contract_address, iterations))
+    }
+
+    /// Scheduler of the identities, loaded on the first PBH step
+    async fn scheduler(&mut self) -> Result<&mut IdentityScheduler> {
+        if self.identities.is_empty() {
+            return Err(eyre::eyre!("send-pbh needs a world_id"));
+        }
+        if self.scheduler.is_none() {
+            let scheduler = IdentityScheduler::load(
+                self.client.provider(),
+                self.client.network().pbh_entry_point,
+                self.identities.clone(),
+            )
+            .await?;
+            self.scheduler = Some(scheduler);
+        }
+        Ok(self.scheduler.as_mut().expect("loaded above"))
+    }
+
+    fn record(&mut self, label: &str, result: Result<TxHash>) -> ScenarioTx {
+        let tx = match result {
+            Ok(tx_hash) => ScenarioTx {
+                label: label.to_string(),
+                tx_hash: Some(tx_hash),
+                rejected: None,
+            },
+            Err(err) => ScenarioTx {
+                label: label.to_string(),
+                tx_hash: None,
+                rejected: Some(rejection_reason(&err)),
+            },
+        };
+        self.txs.push(tx.clone());
+        tx
+    }
+
+    fn labelled(&self, label: &str) -> Vec<ScenarioTx> {
+        self.txs
+            .iter()
+            .filter(|tx| tx.label == label)
+            .cloned()
+            .collect()
+    }
+
+    async fn outcome(&mut self, tx: &ScenarioTx) -> Result<TxOutcome> {
+        match (&tx.rejected, tx.tx_hash) {
+            (Some(reason), _) => Ok(TxOutcome::Rejected(reason.clone())),
+            (None, Some(tx_hash)) => Ok(TxOutcome::Included(self.receipt(tx_hash).await?)),
+            (None, None) => Err(eyre::eyre!(
+                "Transaction of {} was neither sent nor rejected",
+                tx.label
+            )),
+        }
+    }
+
+    async fn receipt(&mut self, tx_hash: TxHash) -> Result<TransactionReceipt> {
+        if let Some(receipt) = self.receipts.get(&tx_hash) {
+            return Ok(receipt.clone());
+        }
+        let receipt = self.client.wait_for_receipt(tx_hash, self.timeout).await?;
+        self.receipts.insert(tx_hash, receipt.clone());
+        Ok(receipt)
+    }
+
+    /// Revert reason of an included transaction, see `rpc::transaction_output`
+    async fn revert_reason(&self, tx_hash: TxHash) -> Result<String> {
+        let output = rpc::transaction_output(self.client.provider(), tx_hash).await?;
+        Ok(output.revert_reason())
+    }
+}
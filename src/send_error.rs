@@ -0,0 +1,273 @@
+use alloy_primitives::Bytes;
+use alloy_sol_types::SolError;
+use alloy_transport::{RpcError, TransportErrorKind};
+use std::fmt;
+
+use crate::bindings::IPBHEntryPoint;
+
+/// Why the RPC refused a raw transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendErrorKind {
+    /// The account nonce was already used by another transaction
+    NonceTooLow,
+    /// A transaction with a lower nonce is missing
+    NonceTooHigh,
+    /// The fee cap is below the base fee or the pool's minimum
+    Underpriced,
+    /// A pending transaction with the same nonce pays more
+    ReplacementUnderpriced,
+    /// The account can't pay for gas limit times fee cap plus value
+    InsufficientFunds,
+    /// The gas limit is above the block gas limit
+    GasLimitExceeded,
+    /// The transaction is already in the pool
+    AlreadyKnown,
+    /// The Semaphore proof of the PBH payload doesn't verify
+    PbhInvalidProof,
+    /// The nullifier hash of the PBH payload was already spent
+    PbhDuplicateNullifier,
+    /// The gas limit is above what the builder allows for PBH transactions
+    PbhGasLimitExceeded,
+    /// The root of the PBH payload isn't accepted
+    PbhInvalidRoot,
+    /// The external nullifier has the wrong month or a PBH nonce over the limit
+    PbhInvalidExternalNullifier,
+    /// A condition of `eth_sendRawTransactionConditional` already fails
+    ConditionFailed,
+    Other,
+}
+
+impl SendErrorKind {
+    /// Classifies an RPC error message from geth, reth or the World Chain builder by the phrases
+    /// of their errors, matched as whole words so unrelated messages sharing a word don't count
+    pub fn classify(message: &str) -> Self {
+        let message = format!(" {} ", words(message));
+        let matches = |phrases: &[&str]| {
+            phrases
+                .iter()
+                .any(|phrase| message.contains(&format!(" {phrase} ")))
+        };
+
+        // PBH errors first, their messages can contain the generic ones
+        if matches(&[
+            "pbh transaction gas limit exceeded",
+            "pbh gas limit exceeded",
+        ]) {
+            Self::PbhGasLimitExceeded
+        } else if matches(&["invalid proof", "invalid semaphore proof"]) {
+            Self::PbhInvalidProof
+        } else if matches(&["duplicate nullifier hash"]) {
+            Self::PbhDuplicateNullifier
+        } else if matches(&["invalid root"]) {
+            Self::PbhInvalidRoot
+        } else if matches(&["invalid external nullifier"]) {
+            Self::PbhInvalidExternalNullifier
+        } else if matches(&["nonce too low"]) {
+            Self::NonceTooLow
+        } else if matches(&["nonce too high"]) {
+            Self::NonceTooHigh
+        } else if matches(&["replacement transaction underpriced"]) {
+            Self::ReplacementUnderpriced
+        } else if matches(&[
+            "transaction underpriced",
+            "max fee per gas less than block base fee",
+        ]) {
+            Self::Underpriced
+        } else if matches(&["insufficient funds"]) {
+            Self::InsufficientFunds
+        } else if matches(&["exceeds block gas limit"]) {
+            Self::GasLimitExceeded
+        } else if matches(&["already known"]) {
+            Self::AlreadyKnown
+        } else if matches(&[
+            "failed conditional",
+            "conditional failed",
+            "invalid conditional",
+        ]) {
+            Self::ConditionFailed
+        } else {
+            Self::Other
+        }
+    }
+
+    /// Kind of a PBH entry point error named by `entry_point_error`
+    pub fn from_entry_point_error(name: &str) -> Self {
+        match name {
+            "InvalidNullifier" => Self::PbhDuplicateNullifier,
+            "InvalidRoot" => Self::PbhInvalidRoot,
+            "InvalidExternalNullifierVersion"
+            | "InvalidExternalNullifierPeriod"
+            | "InvalidPbhNonce" => Self::PbhInvalidExternalNullifier,
+            "ProofInvalid" => Self::PbhInvalidProof,
+            _ => Self::Other,
+        }
+    }
+
+    /// What to change before sending again
+    pub fn suggestion(&self) -> &'static str {
+        match self {
+            Self::NonceTooLow => "another transaction used the nonce, send again with the pending nonce",
+            Self::NonceTooHigh => {
+                "a transaction with a lower nonce is missing, wait for pending transactions or resend the gap"
+            }
+            Self::Underpriced => "raise --gas-fee and --priority-gas-fee above the current base fee",
+            Self::ReplacementUnderpriced => {
+                "a pending transaction has the same nonce, wait for it or raise the fees by at least 10%"
+            }
+            Self::InsufficientFunds => "fund the account or lower --gas-limit and --gas-fee",
+            Self::GasLimitExceeded => "lower --gas-limit or --iterations below the block gas limit",
+            Self::AlreadyKnown => "the transaction is already pending, wait for it to be included",
+            Self::PbhInvalidProof => {
+                "check the prover: the proof has to be for this sender, calls and external nullifier"
+            }
+            Self::PbhDuplicateNullifier => {
+                "the PBH nonce is spent this month, pick another with --pbh-nonce or leave it to the scheduler"
+            }
+            Self::PbhGasLimitExceeded => "lower --gas-limit or --iterations below the PBH gas limit",
+            Self::PbhInvalidRoot => {
+                "refetch the inclusion proof with --refresh-proof, its root may have left the root window"
+            }
+            Self::PbhInvalidExternalNullifier => {
+                "use a PBH nonce below numPbhPerMonth for the current month, see `identity show`"
+            }
+            Self::ConditionFailed => {
+                "a known account changed or a bound passed, send again or widen --conditional-blocks and --conditional-seconds"
+            }
+            Self::Other => "see the RPC error",
+        }
+    }
+
+    /// Whether the client can send the same transaction again with a fresh account nonce.
+    /// Nothing that changes what the transaction pays or does is retried, and a replacement
+    /// would need higher fees.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::NonceTooLow)
+    }
+}
+
+/// `eth_sendRawTransaction` failure, with the RPC message it was classified from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SendError {
+    pub kind: SendErrorKind,
+    pub message: String,
+    /// Revert data of the error response, when the RPC simulated the transaction
+    pub data: Option<Bytes>,
+}
+
+impl SendError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self::with_data(message, None)
+    }
+
+    /// Classifies by the PBH entry point error in `data` if there is one, by the message otherwise
+    pub fn with_data(message: impl Into<String>, data: Option<Bytes>) -> Self {
+        let message = message.into();
+        let kind = match data.as_deref().and_then(entry_point_error) {
+            Some(error) => SendErrorKind::from_entry_point_error(error),
+            None => SendErrorKind::classify(&message),
+        };
+        Self {
+            kind,
+            message,
+            data,
+        }
+    }
+
+    /// Classifies an RPC error response, `None` for transport failures
+    pub fn from_rpc(err: &RpcError<TransportErrorKind>) -> Option<Self> {
+        match err {
+            RpcError::ErrorResp(payload) => Some(Self::with_data(
+                payload.message.to_string(),
+                payload.as_revert_data(),
+            )),
+            _ => None,
+        }
+    }
+
+    /// The PBH entry point error of the revert data, the RPC message otherwise
+    pub fn reason(&self) -> String {
+        self.data
+            .as_deref()
+            .and_then(entry_point_error)
+            .map(str::to_string)
+            .unwrap_or_else(|| self.message.clone())
+    }
+}
+
+impl fmt::Display for SendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Transaction rejected ({:?}): {}\nSuggested fix: {}",
+            self.kind,
+            self.message,
+            self.kind.suggestion()
+        )
+    }
+}
+
+impl std::error::Error for SendError {}
+
+/// Why sending failed: the reason of a classified rejection, the whole error otherwise
+pub fn rejection_reason(err: &eyre::Report) -> String {
+    match err.downcast_ref::<SendError>() {
+        Some(err) => err.reason(),
+        None => err.to_string(),
+    }
+}
+
+/// Name of the PBH entry point error in revert data
+pub fn entry_point_error(data: &[u8]) -> Option<&'static str> {
+    use IPBHEntryPoint::*;
+
+    let errors = [
+        (InvalidNullifier::SELECTOR, "InvalidNullifier"),
+        (InvalidRoot::SELECTOR, "InvalidRoot"),
+        (
+            InvalidExternalNullifierVersion::SELECTOR,
+            "InvalidExternalNullifierVersion",
+        ),
+        (
+            InvalidExternalNullifierPeriod::SELECTOR,
+            "InvalidExternalNullifierPeriod",
+        ),
+        (InvalidPbhNonce::SELECTOR, "InvalidPbhNonce"),
+        (ProofInvalid::SELECTOR, "ProofInvalid"),
+    ];
+    let selector = data.get(..4)?;
+    errors
+        .iter()
+        .find(|(known, _)| known.as_slice() == selector)
+        .map(|(_, name)| *name)
+}
+
+/// Reason of an RPC error: the entry point error if it reverted with one, the message otherwise
+pub fn rpc_error_reason(err: &RpcError<TransportErrorKind>) -> String {
+    match err {
+        RpcError::ErrorResp(payload) => payload
+            .as_revert_data()
+            .and_then(|data| entry_point_error(&data))
+            .map(str::to_string)
+            .unwrap_or_else(|| payload.message.to_string()),
+        _ => err.to_string(),
+    }
+}
+
+/// Lowercase words of an error message, separated by single spaces
+fn words(message: &str) -> String {
+    message
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Lowercase alphanumerics of an error, so `InvalidRoot` and `invalid root` compare equal
+pub fn normalize(message: &str) -> String {
+    message
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
@@ -0,0 +1,200 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use eyre::Result;
+use semaphore_rs::poseidon_tree::{LazyPoseidonTree, Proof};
+use semaphore_rs::Field;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+/// Depth of the World ID tree
+pub const DEFAULT_TREE_DEPTH: usize = 30;
+
+/// Tree depths there is a Semaphore circuit for, so proofs against the tree can be generated
+pub const SUPPORTED_TREE_DEPTHS: [usize; 1] = [DEFAULT_TREE_DEPTH];
+
+/// Returns `depth` if it is one of `SUPPORTED_TREE_DEPTHS`
+pub fn check_depth(depth: usize) -> Result<usize> {
+    if !SUPPORTED_TREE_DEPTHS.contains(&depth) {
+        return Err(eyre::eyre!(
+            "Unsupported tree depth {}, expected one of {:?}",
+            depth,
+            SUPPORTED_TREE_DEPTHS
+        ));
+    }
+    Ok(depth)
+}
+
+/// Parses a `--depth` argument, see `check_depth`
+pub fn parse_depth(value: &str) -> Result<usize> {
+    check_depth(value.parse()?)
+}
+
+/// Body of `insertIdentity` and `inclusionProof` requests
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IdentityRequest {
+    pub identity_commitment: Field,
+}
+
+/// Response of `insertIdentity`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InsertResponse {
+    pub index: usize,
+    pub root: Field,
+}
+
+/// Response of `inclusionProof`, in the format of the signup sequencer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InclusionProofResponse {
+    pub status: String,
+    pub root: Field,
+    pub proof: Proof,
+}
+
+/// Response of `root`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RootResponse {
+    pub root: Field,
+}
+
+struct TreeState {
+    tree: LazyPoseidonTree,
+    indices: HashMap<Field, usize>,
+}
+
+/// Local stand-in for the World ID signup sequencer
+///
+/// Keeps a Semaphore Merkle tree of identity commitments and serves inclusion proofs for
+/// them, so PBH payloads can be built without access to the real World ID tree.
+pub struct MockSequencer {
+    depth: usize,
+    state: Mutex<TreeState>,
+}
+
+impl MockSequencer {
+    /// Empty tree of `depth`, which has to be one of `SUPPORTED_TREE_DEPTHS`
+    pub fn new(depth: usize) -> Result<Self> {
+        check_depth(depth)?;
+        Ok(Self {
+            depth,
+            state: Mutex::new(TreeState {
+                tree: LazyPoseidonTree::new(depth, Field::ZERO).derived(),
+                indices: HashMap::new(),
+            }),
+        })
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Inserts an identity commitment, returning its leaf index.
+    /// Inserting a commitment that is already in the tree returns its existing index.
+    pub fn insert(&self, commitment: Field) -> Result<usize> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(index) = state.indices.get(&commitment) {
+            return Ok(*index);
+        }
+
+        let index = state.indices.len();
+        if index >= 1 << self.depth {
+            return Err(eyre::eyre!("Tree of depth {} is full", self.depth));
+        }
+        state.tree = state.tree.update(index, &commitment);
+        state.indices.insert(commitment, index);
+        Ok(index)
+    }
+
+    /// Current root of the tree
+    pub fn root(&self) -> Field {
+        self.state.lock().unwrap().tree.root()
+    }
+
+    /// Inclusion proof of a commitment against the current root
+    pub fn inclusion_proof(&self, commitment: Field) -> Option<InclusionProofResponse> {
+        let state = self.state.lock().unwrap();
+        let index = *state.indices.get(&commitment)?;
+        Some(InclusionProofResponse {
+            status: "mined".to_string(),
+            root: state.tree.root(),
+            proof: state.tree.proof(index),
+        })
+    }
+
+    /// HTTP routes: `POST /insertIdentity`, `POST /inclusionProof` and `GET /root`
+    pub fn router(self: Arc<Self>) -> Router {
+        Router::new()
+            .route("/insertIdentity", post(handle_insert_identity))
+            .route("/inclusionProof", post(handle_inclusion_proof))
+            .route("/root", get(handle_root))
+            .with_state(self)
+    }
+
+    /// Binds to `addr` and serves the routes in the background. Returns the bound address.
+    pub async fn spawn(self: Arc<Self>, addr: SocketAddr) -> Result<SocketAddr> {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+        let app = self.router();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        Ok(local_addr)
+    }
+
+    /// Binds to `addr` and serves the routes until the process exits
+    pub async fn serve(self: Arc<Self>, addr: SocketAddr) -> Result<()> {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        axum::serve(listener, self.router()).await?;
+        Ok(())
+    }
+}
+
+/// Registers an identity commitment with a running mock sequencer
+pub async fn insert_identity(sequencer_url: &str, commitment: Field) -> Result<InsertResponse> {
+    let response = reqwest::Client::new()
+        .post(format!("{}/insertIdentity", sequencer_url.trim_end_matches('/')))
+        .json(&IdentityRequest {
+            identity_commitment: commitment,
+        })
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        return Err(eyre::eyre!(
+            "Sequencer returned {}: {}",
+            response.status(),
+            response.text().await?
+        ));
+    }
+    Ok(response.json().await?)
+}
+
+async fn handle_insert_identity(
+    State(sequencer): State<Arc<MockSequencer>>,
+    Json(request): Json<IdentityRequest>,
+) -> Result<Json<InsertResponse>, (StatusCode, String)> {
+    let index = sequencer
+        .insert(request.identity_commitment)
+        .map_err(|err| (StatusCode::BAD_REQUEST, err.to_string()))?;
+    Ok(Json(InsertResponse {
+        index,
+        root: sequencer.root(),
+    }))
+}
+
+async fn handle_inclusion_proof(
+    State(sequencer): State<Arc<MockSequencer>>,
+    Json(request): Json<IdentityRequest>,
+) -> Result<Json<InclusionProofResponse>, StatusCode> {
+    sequencer
+        .inclusion_proof(request.identity_commitment)
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn handle_root(State(sequencer): State<Arc<MockSequencer>>) -> Json<RootResponse> {
+    Json(RootResponse {
+        root: sequencer.root(),
+    })
+}
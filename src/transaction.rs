@@ -1,19 +1,23 @@
 use alloy_consensus::TxEnvelope;
 use alloy_network::{EthereumWallet, TransactionBuilder};
-use alloy_primitives::Address;
+use alloy_primitives::{Address, Bytes, U256};
 use alloy_provider::Provider;
 use alloy_rpc_types_eth::{TransactionInput, TransactionRequest};
 use alloy_signer_local::PrivateKeySigner;
-use alloy_sol_types::SolValue;
+use alloy_sol_types::{SolCall, SolValue};
 use eyre::Result;
-use semaphore_rs::hash_to_field;
+use semaphore_rs::{hash_to_field, Field};
 use std::sync::Arc;
-use world_chain_builder_test_utils::bindings::IMulticall3::Call3;
 
+use crate::bindings::{self, IMulticall3::Call3, IPBHEntryPoint};
+use crate::proof::ProofProvider;
 use crate::world_id::WorldID;
+use world_chain_builder_pbh::payload::PBHPayload;
 
-// PBH Entry Point address
-pub static PBH_ENTRY_POINT: Address = Address::ZERO;
+/// Signal hash of a `pbhMulticall` from `from`: `hashToField(abi.encode(msg.sender, calls))`
+pub fn multicall_signal_hash(from: Address, calls: &[Call3]) -> Field {
+    hash_to_field(&SolValue::abi_encode_params(&(from, calls.to_vec())))
+}
 
 #[derive(Clone, Default)]
 pub struct GasTestTransactionBuilder {
@@ -54,29 +58,30 @@ impl GasTestTransactionBuilder {
     pub async fn with_pbh_multicall(
         self,
         world_id: &WorldID,
+        prover: &dyn ProofProvider,
         pbh_nonce: u16,
         from: Address,
         calls: Vec<Call3>,
     ) -> Result<Self> {
         // Get the inclusion proof for the identity in the from the World Tree
-        let signal_hash = hash_to_field(&SolValue::abi_encode_packed(&(from, calls.clone())));
-        let _pbh_payload = world_id.pbh_payload(pbh_nonce, signal_hash).await?;
+        let signal_hash = multicall_signal_hash(from, &calls);
+        let pbh_payload = world_id.pbh_payload(prover, pbh_nonce, signal_hash).await?;
 
-        // For now, we'll use a simplified approach to create the calldata
-        // Function selector for pbhMulticall (first 4 bytes of keccak256("pbhMulticall((address,bytes,bool)[],(uint256,uint256,(uint8,uint8,uint8,uint16),(uint256[8],)))"))
-        let selector = [0x96, 0x4a, 0x97, 0x50];
-        
-        // Create a simplified calldata - in a real implementation, we would properly encode the calls and payload
-        let mut calldata_bytes = selector.to_vec();
-        
-        // Add some placeholder data for the parameters
-        // This is a simplified implementation - in a real implementation, we would properly encode the parameters
-        calldata_bytes.extend_from_slice(&[0; 64]); // Placeholder data
+        Ok(self.with_pbh_payload(calls, &pbh_payload))
+    }
+
+    /// Sets the input to a `pbhMulticall` with a payload built by the caller
+    pub fn with_pbh_payload(self, calls: Vec<Call3>, pbh_payload: &PBHPayload) -> Self {
+        let calldata = IPBHEntryPoint::pbhMulticallCall {
+            calls,
+            payload: bindings::PBHPayload::from(pbh_payload),
+        }
+        .abi_encode();
 
         let tx = self.tx
-            .input(TransactionInput::new(calldata_bytes.into()));
+            .input(TransactionInput::new(calldata.into()));
         
-        Ok(Self { tx, provider: self.provider })
+        Self { tx, provider: self.provider }
     }
 
     pub async fn build(self, signer: PrivateKeySigner) -> Result<TxEnvelope> {
@@ -98,63 +103,36 @@ impl GasTestTransactionBuilder {
         Self { tx, provider: self.provider }
     }
 
-    /// Sets the nonce for the transaction.
-    pub fn nonce(self, nonce: u64) -> Self {
-        let tx = self.tx.nonce(nonce);
+    /// Sets the ETH value sent with the transaction.
+    pub fn value(self, value: U256) -> Self {
+        let tx = self.tx.value(value);
         Self { tx, provider: self.provider }
     }
-}
 
-/// Creates a multicall call for the gas consumption function
-pub fn consume_gas_multicall(contract_address: Address, iterations: u64) -> Vec<Call3> {
-    // Function selector for consumeGas(address,uint256)
-    // keccak256("consumeGas(address,uint256)")[0..4]
-    let selector = [0x41, 0x4c, 0xf8, 0x5d];
-    
-    // Encode the parameters
-    let mut calldata = selector.to_vec();
-    
-    // Encode address (pad to 32 bytes)
-    let mut address_bytes = vec![0u8; 12];
-    address_bytes.extend_from_slice(contract_address.as_slice());
-    calldata.extend_from_slice(&address_bytes);
-    
-    // Encode iterations
-    let mut iterations_bytes = [0u8; 32];
-    let iterations_vec = iterations.to_be_bytes().to_vec();
-    for (i, b) in iterations_vec.iter().rev().enumerate() {
-        iterations_bytes[32 - iterations_vec.len() + i] = *b;
+    /// Turns the transaction into a contract creation with the given init code.
+    pub fn deploy_code(self, code: Bytes) -> Self {
+        let tx = self.tx.with_deploy_code(code);
+        Self { tx, provider: self.provider }
     }
-    calldata.extend_from_slice(&iterations_bytes);
-    
-    let call = Call3 {
-        target: contract_address,
-        callData: calldata.into(),
-        allowFailure: false,
-    };
 
-    vec![call]
-}
+    /// Sets the gas limit for the transaction.
+    pub fn gas_limit(self, gas_limit: u64) -> Self {
+        let tx = self.tx.gas_limit(gas_limit);
+        Self { tx, provider: self.provider }
+    }
 
-/// Gets the next available PBH nonce for the given WorldID
-pub async fn get_pbh_nonce<P>(
-    world_id: &WorldID,
-    _provider: Arc<P>,
-    max_pbh_nonce: u16,
-) -> Result<u16>
-where
-    P: Provider + 'static,
-{
-    let start_nonce = 0;
-    // Simplified implementation - in a real implementation, we would check if the nonce is already used
-    // For now, just return the first nonce
-    for i in start_nonce..=max_pbh_nonce {
-        let (_external_nullifier, _external_nullifier_hash, _nullifier_hash) = 
-            world_id.pbh_ext_nullifier(i);
-        // In a real implementation, we would check if the nonce is already used
-        // For simplicity, just return the first nonce
-        return Ok(i);
+    /// Sets the nonce for the transaction.
+    pub fn nonce(self, nonce: u64) -> Self {
+        let tx = self.tx.nonce(nonce);
+        Self { tx, provider: self.provider }
     }
+}
 
-    Err(eyre::eyre!("No available PBH nonce"))
+/// Creates a multicall with a single call to `target` with the given calldata
+pub fn single_call_multicall(target: Address, calldata: Bytes) -> Vec<Call3> {
+    vec![Call3 {
+        target,
+        callData: calldata,
+        allowFailure: false,
+    }]
 }
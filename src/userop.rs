@@ -0,0 +1,197 @@
+use alloy_primitives::{aliases::U192, Address, Bytes, B256, U256};
+use alloy_provider::Provider;
+use alloy_signer::SignerSync;
+use alloy_signer_local::PrivateKeySigner;
+use alloy_sol_types::{SolCall, SolValue};
+use eyre::Result;
+use semaphore_rs::{hash_to_field, Field};
+use std::sync::Arc;
+use world_chain_builder_pbh::payload::PBHPayload;
+
+use crate::bindings::{self, IEntryPoint, ISmartAccount, PackedUserOperation, UserOpsPerAggregator};
+use crate::proof::ProofProvider;
+use crate::rpc;
+use crate::world_id::WorldID;
+
+/// Canonical ERC-4337 v0.7 EntryPoint address
+pub const ENTRY_POINT_V07: Address = alloy_primitives::address!("0000000071727De22E5E9d8BAf0edAc6f37da032");
+
+/// Gas parameters of a user operation
+#[derive(Debug, Clone, Copy)]
+pub struct UserOpGas {
+    pub call_gas_limit: u128,
+    pub verification_gas_limit: u128,
+    pub pre_verification_gas: u128,
+    pub max_fee_per_gas: u128,
+    pub max_priority_fee_per_gas: u128,
+}
+
+impl Default for UserOpGas {
+    fn default() -> Self {
+        Self {
+            call_gas_limit: 130000,
+            verification_gas_limit: 500000,
+            pre_verification_gas: 60000,
+            max_fee_per_gas: 1e8 as u128,
+            max_priority_fee_per_gas: 1e8 as u128,
+        }
+    }
+}
+
+impl UserOpGas {
+    pub fn new(gas_fee: Option<f64>, priority_gas_fee: Option<f64>) -> Self {
+        let mut gas = Self::default();
+        if let Some(gas_fee) = gas_fee {
+            gas.max_fee_per_gas = (gas_fee * 1e9) as u128;
+        }
+        if let Some(priority_gas_fee) = priority_gas_fee {
+            gas.max_priority_fee_per_gas = (priority_gas_fee * 1e9) as u128;
+        }
+        gas
+    }
+}
+
+/// Packs two 128 bit values into a single word, as done for `accountGasLimits` and `gasFees`
+pub fn pack_u128s(high: u128, low: u128) -> B256 {
+    B256::from((U256::from(high) << 128) | U256::from(low))
+}
+
+/// Signal hash of a PBH user operation: `hashToField(abi.encodePacked(sender, nonce, callData))`
+pub fn userop_signal_hash(sender: Address, nonce: U256, call_data: &Bytes) -> Field {
+    hash_to_field(&SolValue::abi_encode_packed(&(sender, nonce, call_data.clone())))
+}
+
+/// Builds the user operation signature: the 12 bytes of `validAfter`/`validUntil`
+/// (both zero, i.e. always valid), the 65 byte ECDSA signature of the owner and the
+/// ABI encoded PBH payload which the signature aggregator strips off
+pub fn pbh_signature(ecdsa: &[u8; 65], payload: &PBHPayload) -> Bytes {
+    let mut signature = vec![0u8; 12];
+    signature.extend_from_slice(ecdsa);
+    signature.extend_from_slice(&bindings::PBHPayload::from(payload).abi_encode());
+    signature.into()
+}
+
+/// Aggregated signature passed to `handleAggregatedOps`: `abi.encode(PBHPayload[])`
+pub fn aggregated_signature(payloads: &[PBHPayload]) -> Bytes {
+    let payloads: Vec<bindings::PBHPayload> = payloads.iter().map(Into::into).collect();
+    payloads.abi_encode().into()
+}
+
+/// Calldata for a smart account `execute` call
+pub fn execute_calldata(target: Address, value: U256, call_data: Bytes) -> Bytes {
+    ISmartAccount::executeCall {
+        dest: target,
+        value,
+        func: call_data,
+    }
+    .abi_encode()
+    .into()
+}
+
+/// Calldata for `handleAggregatedOps` with all operations under the PBH signature aggregator
+pub fn handle_aggregated_ops_calldata(
+    ops: Vec<(PackedUserOperation, PBHPayload)>,
+    aggregator: Address,
+    beneficiary: Address,
+) -> Bytes {
+    let payloads: Vec<PBHPayload> = ops.iter().map(|(_, payload)| payload.clone()).collect();
+    let user_ops = ops.into_iter().map(|(op, _)| op).collect();
+
+    IEntryPoint::handleAggregatedOpsCall {
+        opsPerAggregator: vec![UserOpsPerAggregator {
+            userOps: user_ops,
+            aggregator,
+            signature: aggregated_signature(&payloads),
+        }],
+        beneficiary,
+    }
+    .abi_encode()
+    .into()
+}
+
+/// Builds and signs a PBH user operation for `sender` executing `call_data`
+///
+/// The nonce is read from the entry point for `nonce_key`, the signal hash is computed
+/// from the sender, nonce and callData and the PBH payload is appended to the signature.
+#[allow(clippy::too_many_arguments)]
+pub async fn build_pbh_user_op(
+    provider: &Arc<dyn Provider>,
+    world_id: &WorldID,
+    prover: &dyn ProofProvider,
+    pbh_nonce: u16,
+    entry_point: Address,
+    sender: Address,
+    nonce_key: U192,
+    call_data: Bytes,
+    gas: UserOpGas,
+    signer: &PrivateKeySigner,
+) -> Result<(PackedUserOperation, PBHPayload)> {
+    let nonce = rpc::call(
+        provider,
+        entry_point,
+        IEntryPoint::getNonceCall {
+            sender,
+            key: nonce_key,
+        },
+    )
+    .await?
+    .nonce;
+
+    let signal_hash = userop_signal_hash(sender, nonce, &call_data);
+    let payload = world_id.pbh_payload(prover, pbh_nonce, signal_hash).await?;
+
+    let mut op = PackedUserOperation {
+        sender,
+        nonce,
+        initCode: Bytes::new(),
+        callData: call_data,
+        accountGasLimits: pack_u128s(gas.verification_gas_limit, gas.call_gas_limit),
+        preVerificationGas: U256::from(gas.pre_verification_gas),
+        gasFees: pack_u128s(gas.max_priority_fee_per_gas, gas.max_fee_per_gas),
+        paymasterAndData: Bytes::new(),
+        signature: Bytes::new(),
+    };
+
+    let op_hash = rpc::call(
+        provider,
+        entry_point,
+        IEntryPoint::getUserOpHashCall { userOp: op.clone() },
+    )
+    .await?
+    ._0;
+    let ecdsa = signer.sign_hash_sync(&op_hash)?;
+    op.signature = pbh_signature(&ecdsa.as_bytes(), &payload);
+
+    Ok((op, payload))
+}
+
+/// Submits a user operation to a bundler through `eth_sendUserOperation`.
+/// Returns the user operation hash.
+pub async fn send_to_bundler(
+    bundler_url: &str,
+    entry_point: Address,
+    op: &PackedUserOperation,
+    gas: &UserOpGas,
+    aggregator: Address,
+) -> Result<B256> {
+    let bundler = rpc::connect(bundler_url).await?;
+
+    // Bundlers take the unpacked v0.7 representation
+    let user_op = serde_json::json!({
+        "sender": op.sender,
+        "nonce": op.nonce,
+        "callData": op.callData,
+        "callGasLimit": U256::from(gas.call_gas_limit),
+        "verificationGasLimit": U256::from(gas.verification_gas_limit),
+        "preVerificationGas": op.preVerificationGas,
+        "maxFeePerGas": U256::from(gas.max_fee_per_gas),
+        "maxPriorityFeePerGas": U256::from(gas.max_priority_fee_per_gas),
+        "signature": op.signature,
+        "aggregator": aggregator,
+    });
+
+    let op_hash = bundler
+        .raw_request::<_, B256>("eth_sendUserOperation".into(), (user_op, entry_point))
+        .await?;
+    Ok(op_hash)
+}
@@ -0,0 +1,106 @@
+use alloy_primitives::{Address, Bytes, U256};
+use alloy_rpc_types_eth::TransactionReceipt;
+use alloy_sol_types::SolCall;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::bindings::GasConsumer;
+
+/// Kinds of execution cost that GasConsumer can generate
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum Workload {
+    /// keccak256 loop (`consumeGas`)
+    #[default]
+    Keccak,
+    /// SSTOREs to fresh slots (`storeCold`)
+    StorageCold,
+    /// Repeated SSTOREs to the same slot (`storeWarm`)
+    StorageWarm,
+    /// Memory expansion by `iterations` words (`expandMemory`)
+    Memory,
+    /// `iterations` bytes of non-zero calldata (`consumeCalldata`)
+    Calldata,
+    /// Event emission (`emitLogs`)
+    Logs,
+    /// ecrecover precompile calls (`callEcrecover`)
+    Ecrecover,
+    /// SHA256 precompile calls (`callSha256`)
+    Sha256,
+}
+
+impl Workload {
+    /// Builds the GasConsumer calldata for this workload with the given size
+    pub fn calldata(&self, contract_address: Address, iterations: u64) -> Bytes {
+        let iterations = U256::from(iterations);
+        match self {
+            Workload::Keccak => consume_gas_calldata(contract_address, iterations),
+            Workload::StorageCold => store_cold_calldata(iterations),
+            Workload::StorageWarm => store_warm_calldata(iterations),
+            Workload::Memory => expand_memory_calldata(iterations),
+            Workload::Calldata => {
+                let len = iterations.saturating_to::<usize>();
+                consume_calldata_calldata(vec![0xff; len].into())
+            }
+            Workload::Logs => emit_logs_calldata(iterations),
+            Workload::Ecrecover => call_ecrecover_calldata(iterations),
+            Workload::Sha256 => call_sha256_calldata(iterations),
+        }
+    }
+}
+
+/// Calldata for `consumeGas(address,uint256)`
+pub fn consume_gas_calldata(address: Address, iterations: U256) -> Bytes {
+    GasConsumer::consumeGasCall {
+        _address: address,
+        _iterations: iterations,
+    }
+    .abi_encode()
+    .into()
+}
+
+/// Calldata for `storeCold(uint256)`
+pub fn store_cold_calldata(count: U256) -> Bytes {
+    GasConsumer::storeColdCall { _count: count }.abi_encode().into()
+}
+
+/// Calldata for `storeWarm(uint256)`
+pub fn store_warm_calldata(count: U256) -> Bytes {
+    GasConsumer::storeWarmCall { _count: count }.abi_encode().into()
+}
+
+/// Calldata for `expandMemory(uint256)`
+pub fn expand_memory_calldata(words: U256) -> Bytes {
+    GasConsumer::expandMemoryCall { _words: words }.abi_encode().into()
+}
+
+/// Calldata for `consumeCalldata(bytes)`
+pub fn consume_calldata_calldata(data: Bytes) -> Bytes {
+    GasConsumer::consumeCalldataCall { _data: data }.abi_encode().into()
+}
+
+/// Calldata for `emitLogs(uint256)`
+pub fn emit_logs_calldata(count: U256) -> Bytes {
+    GasConsumer::emitLogsCall { _count: count }.abi_encode().into()
+}
+
+/// Calldata for `callEcrecover(uint256)`
+pub fn call_ecrecover_calldata(count: U256) -> Bytes {
+    GasConsumer::callEcrecoverCall { _count: count }.abi_encode().into()
+}
+
+/// Calldata for `callSha256(uint256)`
+pub fn call_sha256_calldata(count: U256) -> Bytes {
+    GasConsumer::callSha256Call { _count: count }.abi_encode().into()
+}
+
+/// Decodes the `Consumed` events emitted by GasConsumer in a receipt
+pub fn consumed_events(receipt: &TransactionReceipt) -> Vec<GasConsumer::Consumed> {
+    receipt
+        .inner
+        .logs()
+        .iter()
+        .filter_map(|log| log.log_decode::<GasConsumer::Consumed>().ok())
+        .map(|log| log.inner.data)
+        .collect()
+}
@@ -1,39 +1,39 @@
 use base64::{Engine, prelude::BASE64_STANDARD};
 use eyre::Result;
-use semaphore_rs::{Field, identity::Identity, protocol::Proof};
+use rand::RngCore;
+use semaphore_rs::{Field, identity::Identity, poseidon_tree::Proof};
 use serde::{Deserialize, Serialize};
+use crate::proof::ProofProvider;
 use world_chain_builder_pbh::{
     date_marker::DateMarker,
     external_nullifier::{EncodedExternalNullifier, ExternalNullifier},
     payload::PBHPayload,
 };
 
+#[derive(Debug, Clone)]
 pub struct WorldID {
     pub identity: Identity,
 }
 
 
+/// Inclusion proof of an identity as served by the sequencer
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InclusionProof {
     pub root: Field,
-    #[serde(skip)]
-    pub proof_data: Vec<u8>,
+    pub proof: Proof,
 }
 impl WorldID {
+    /// Identity from its secret: base64 of the 32 byte trapdoor followed by the 32 byte nullifier
     pub fn new(secret: &str) -> Result<Self> {
-        // For testing purposes, we'll create a dummy identity if the secret is invalid
-        let decoded = match BASE64_STANDARD.decode(secret) {
-            Ok(d) if d.len() == 64 => d,
-            _ => {
-                // Create a dummy identity for testing
-                let mut dummy = vec![0; 64];
-                // Fill with some non-zero values to make it more realistic
-                for i in 0..64 {
-                    dummy[i] = i as u8;
-                }
-                dummy
-            }
-        };
+        let decoded = BASE64_STANDARD
+            .decode(secret)
+            .map_err(|err| eyre::eyre!("World ID secret is not valid base64: {}", err))?;
+        if decoded.len() != 64 {
+            return Err(eyre::eyre!(
+                "World ID secret has {} bytes, expected 64",
+                decoded.len()
+            ));
+        }
 
         let trapdoor = &decoded[..32];
         let nullifier = &decoded[32..];
@@ -46,6 +46,28 @@ impl WorldID {
         Ok(Self { identity })
     }
 
+    /// Identity with a random trapdoor and nullifier
+    pub fn random() -> Self {
+        let mut secret = [0u8; 64];
+        rand::thread_rng().fill_bytes(&mut secret);
+        // Clear the top bits so both halves are below the field modulus
+        secret[0] &= 0x1f;
+        secret[32] &= 0x1f;
+
+        let identity = Identity {
+            trapdoor: Field::from_be_slice(&secret[..32]),
+            nullifier: Field::from_be_slice(&secret[32..]),
+        };
+        Self { identity }
+    }
+
+    /// Secret of the identity in the format `new` accepts: base64 of the trapdoor and nullifier
+    pub fn secret(&self) -> String {
+        let mut secret = self.identity.trapdoor.to_be_bytes::<32>().to_vec();
+        secret.extend_from_slice(&self.identity.nullifier.to_be_bytes::<32>());
+        BASE64_STANDARD.encode(secret)
+    }
+
     pub fn identity(&self) -> &Identity {
         &self.identity
     }
@@ -54,7 +76,15 @@ impl WorldID {
     /// Returns `external_nullifier`, `external_nullifier_hash``, `nullifier_hash`
     pub fn pbh_ext_nullifier(&self, pbh_nonce: u16) -> (ExternalNullifier, Field, Field) {
         let date = chrono::Utc::now().naive_utc().date();
-        let date_marker = DateMarker::from(date);
+        self.pbh_ext_nullifier_at(DateMarker::from(date), pbh_nonce)
+    }
+
+    /// Same as `pbh_ext_nullifier`, for the month of `date_marker`
+    pub fn pbh_ext_nullifier_at(
+        &self,
+        date_marker: DateMarker,
+        pbh_nonce: u16,
+    ) -> (ExternalNullifier, Field, Field) {
         let external_nullifier = ExternalNullifier::with_date_marker(date_marker, pbh_nonce);
         let external_nullifier_hash = EncodedExternalNullifier::from(external_nullifier).0;
         let nullifier_hash = semaphore_rs::protocol::generate_nullifier_hash(
@@ -65,45 +95,31 @@ impl WorldID {
         (external_nullifier, external_nullifier_hash, nullifier_hash)
     }
 
-    pub async fn inclusion_proof(&self) -> Result<InclusionProof> {
-        // For testing purposes, we'll create a dummy inclusion proof
-        // In a real implementation, we would fetch this from the server
-        let root = Field::default();
-        
-        // Create a mock proof that matches the expected structure
-        // This is a simplified version for testing purposes
-        let proof_data = vec![0; 32]; // Dummy data
-        
-        Ok(InclusionProof { root, proof_data })
-    }
-
-    pub async fn generate_proof(
+    /// Builds the PBH payload for a signal, with the proof from `prover`
+    pub async fn pbh_payload(
         &self,
-        _signal_hash: Field,
-        _external_nullifier_hash: Field,
-    ) -> Result<(Proof, Field)> {
-        // For testing purposes, we'll create a dummy proof
-        // In a real implementation, we would fetch this from the server
-        let inclusion_proof = self.inclusion_proof().await?;
-        
-        // Create a dummy proof for testing
-        use alloy_primitives::U256;
-        let flat_proof = [U256::ZERO; 8];
-        let semaphore_proof = semaphore_rs::protocol::Proof::from_flat(flat_proof);
-
-        Ok((semaphore_proof, inclusion_proof.root))
+        prover: &dyn ProofProvider,
+        pbh_nonce: u16,
+        signal_hash: Field,
+    ) -> Result<PBHPayload> {
+        let date = chrono::Utc::now().naive_utc().date();
+        self.pbh_payload_at(prover, DateMarker::from(date), pbh_nonce, signal_hash)
+            .await
     }
 
-    pub async fn pbh_payload(
+    /// Same as `pbh_payload`, for the month of `date_marker`
+    pub async fn pbh_payload_at(
         &self,
+        prover: &dyn ProofProvider,
+        date_marker: DateMarker,
         pbh_nonce: u16,
         signal_hash: Field,
     ) -> Result<PBHPayload> {
         let (external_nullifier, external_nullifier_hash, nullifier_hash) =
-            self.pbh_ext_nullifier(pbh_nonce);
+            self.pbh_ext_nullifier_at(date_marker, pbh_nonce);
 
-        let (proof, root) = self
-            .generate_proof(signal_hash, external_nullifier_hash)
+        let (proof, root) = prover
+            .prove(self.identity(), signal_hash, external_nullifier_hash)
             .await?;
 
         let payload = PBHPayload {
@@ -0,0 +1,184 @@
+mod common;
+
+use common::Harness;
+use gas_test::baseline::{
+    compare, default_cases, diff_table, Baseline, BaselineCase, BaselineCheck, BaselineTxType,
+    Comparison, Measurement, Thresholds,
+};
+use gas_test::workload::Workload;
+
+fn measurement(case: &str, gas_used: u64) -> Measurement {
+    Measurement {
+        case: case.parse().unwrap(),
+        gas_used,
+    }
+}
+
+#[test]
+fn cases_parse_and_print() {
+    let case: BaselineCase = "storage-cold:10:pbh".parse().unwrap();
+    assert_eq!(
+        case,
+        BaselineCase {
+            workload: Workload::StorageCold,
+            iterations: 10,
+            tx_type: BaselineTxType::Pbh,
+        }
+    );
+    assert_eq!(case.to_string(), "storage-cold:10:pbh");
+
+    assert!("keccak:10".parse::<BaselineCase>().is_err());
+    assert!("keccak:ten:direct".parse::<BaselineCase>().is_err());
+    assert!("keccak:10:user-op".parse::<BaselineCase>().is_err());
+    // User operations aren't measured, so baseline files can't hold them either
+    assert!("keccak:10:pbh-user-op".parse::<BaselineCase>().is_err());
+    let user_op = r#"{ "workload": "keccak", "iterations": 10, "txType": "pbh-user-op" }"#;
+    assert!(serde_json::from_str::<BaselineCase>(user_op).is_err());
+    assert!("sorting:10:direct".parse::<BaselineCase>().is_err());
+
+    for case in default_cases() {
+        assert_eq!(case.to_string().parse::<BaselineCase>().unwrap(), case);
+    }
+}
+
+#[test]
+fn pbh_overhead_is_compared() {
+    let baseline = [
+        measurement("keccak:100:direct", 30_000),
+        measurement("keccak:100:pbh", 100_000),
+        measurement("logs:10:pbh", 90_000),
+    ];
+    let measured = [
+        measurement("keccak:100:direct", 30_000),
+        measurement("keccak:100:pbh", 101_000),
+        measurement("logs:10:pbh", 90_000),
+    ];
+
+    let comparisons = compare(&baseline, &measured).unwrap();
+    let names: Vec<&str> = comparisons.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "keccak:100:direct",
+            "keccak:100:pbh",
+            "keccak:100 pbh overhead",
+            "logs:10:pbh"
+        ]
+    );
+    assert_eq!(comparisons[2].baseline, 70_000);
+    assert_eq!(comparisons[2].measured, 71_000);
+
+    // Every baseline case has to be measured again
+    assert!(compare(&baseline, &measured[..2]).is_err());
+}
+
+#[test]
+fn thresholds_fail_rows() {
+    let comparison = Comparison {
+        name: "keccak:100:pbh".into(),
+        baseline: 100_000,
+        measured: 100_800,
+    };
+    assert!(!comparison.exceeds(&Thresholds::new(None, None)));
+    assert!(comparison.exceeds(&Thresholds::new(None, Some(0.5))));
+    assert!(comparison.exceeds(&Thresholds::new(Some(500), None)));
+    assert!(!comparison.exceeds(&Thresholds::new(Some(1_000), None)));
+
+    let improved = Comparison {
+        measured: 98_000,
+        ..comparison.clone()
+    };
+    assert_eq!(improved.delta(), -2_000);
+    assert!(improved.exceeds(&Thresholds::new(None, None)));
+
+    let table = diff_table(&[comparison, improved], &Thresholds::new(Some(1_000), None));
+    let rows: Vec<&str> = table.lines().collect();
+    assert!(rows[0].starts_with("Case"));
+    assert!(
+        rows[1].contains("+800") && rows[1].ends_with("ok"),
+        "{table}"
+    );
+    assert!(
+        rows[2].contains("-2.00%") && rows[2].ends_with("FAIL"),
+        "{table}"
+    );
+}
+
+#[test]
+fn checks_count_failed_rows() {
+    let recorded = Baseline::new(
+        None,
+        vec![
+            measurement("keccak:100:direct", 30_000),
+            measurement("keccak:100:pbh", 100_000),
+        ],
+    );
+    let measured = [
+        measurement("keccak:100:direct", 30_100),
+        measurement("keccak:100:pbh", 102_000),
+    ];
+
+    let check = BaselineCheck::new(&recorded, &measured, Thresholds::new(Some(500), None)).unwrap();
+    assert_eq!(check.comparisons.len(), 3);
+    // The PBH row and its overhead moved by more than 500 gas
+    assert_eq!(check.failed(), 2);
+    assert_eq!(
+        check.table(),
+        diff_table(&check.comparisons, &check.thresholds)
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn recorded_baselines_are_checked() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let path = harness.dir.join("gas-baseline.json");
+    let file = path.display().to_string();
+    let entry_point = harness.entry_point.to_string();
+    let run = |command: &'static str, extra: &'static [&'static str]| {
+        let mut args = vec![
+            "--pbh-entry-point",
+            entry_point.as_str(),
+            "baseline",
+            command,
+            "--file",
+            file.as_str(),
+        ];
+        args.extend_from_slice(extra);
+        args
+    };
+
+    let output = harness
+        .run(&run(
+            "record",
+            &["--case", "keccak:100:direct", "--case", "keccak:100:pbh"],
+        ))
+        .await;
+    assert!(output.status.success());
+    let baseline = Baseline::load(&path).unwrap();
+    assert_eq!(baseline.measurements.len(), 2);
+    assert!(baseline.measurements[1].gas_used > baseline.measurements[0].gas_used);
+
+    // PBH calldata carries a new nullifier hash each time, so its zero bytes vary
+    let output = harness
+        .run(&run("check", &["--max-gas-delta", "200"]))
+        .await;
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    assert!(output.status.success(), "{stdout}");
+    assert!(stdout.contains("keccak:100 pbh overhead"), "{stdout}");
+
+    // A cheaper recording makes the same measurement a regression
+    let mut cheaper = baseline.clone();
+    cheaper.measurements[0].gas_used -= 1_000;
+    cheaper.save(&path).unwrap();
+    let output = harness.run(&run("check", &[])).await;
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    assert!(!output.status.success());
+    assert!(
+        stdout
+            .lines()
+            .any(|line| line.starts_with("keccak:100:direct") && line.ends_with("FAIL")),
+        "{stdout}"
+    );
+}
@@ -0,0 +1,152 @@
+mod common;
+
+use alloy_primitives::{address, Address, Bytes, U256};
+use alloy_provider::Provider;
+use alloy_sol_types::SolValue;
+use common::Harness;
+use gas_test::abi::{decode_output, encode_call, format_value, parse_function};
+use gas_test::bindings::IMulticall3;
+use gas_test::calls::{describe_result, load_calls, CallSpec};
+
+const TARGET: Address = address!("00000000000000000000000000000000000000aa");
+
+#[test]
+fn calls_parse_from_the_command_line() {
+    let call: CallSpec = format!("{TARGET} transfer(address,uint256) {TARGET} 5").parse().unwrap();
+    assert_eq!(call.signature.as_deref(), Some("transfer(address,uint256)"));
+    assert_eq!(call.args, [TARGET.to_string(), "5".to_string()]);
+
+    // `returns (...)` belongs to the signature, not the arguments
+    let call: CallSpec = format!("{TARGET} balanceOf(address) returns (uint256) {TARGET}")
+        .parse()
+        .unwrap();
+    assert_eq!(call.signature.as_deref(), Some("balanceOf(address) returns (uint256)"));
+    assert_eq!(call.args, [TARGET.to_string()]);
+
+    let call: CallSpec = format!("{TARGET} 0xdeadbeef").parse().unwrap();
+    assert_eq!(call.calldata, Some(Bytes::from([0xde, 0xad, 0xbe, 0xef])));
+    assert_eq!(call.label(), "0xdeadbeef");
+
+    assert!(!call.allow_failure);
+
+    let call: CallSpec = format!("allowFailure {TARGET} 0xdeadbeef").parse().unwrap();
+    assert!(call.allow_failure);
+    assert!(call.to_call3().unwrap().allowFailure);
+
+    assert!(format!("{TARGET} 0xdeadbeef 1").parse::<CallSpec>().is_err());
+    assert!("not-an-address f()".parse::<CallSpec>().is_err());
+}
+
+#[test]
+fn calldata_is_encoded_from_the_signature() {
+    let call: CallSpec = format!("{TARGET} transfer(address,uint256) {TARGET} 5").parse().unwrap();
+    let calldata = call.calldata().unwrap();
+    // transfer(address,uint256)
+    assert_eq!(calldata[..4], [0xa9, 0x05, 0x9c, 0xbb]);
+    assert_eq!(calldata[16..36], TARGET[..]);
+    assert_eq!(U256::from_be_slice(&calldata[36..68]), U256::from(5));
+
+    let function = parse_function("transfer(address,uint256)").unwrap();
+    assert!(encode_call(&function, &["5".to_string()]).is_err());
+    assert!(encode_call(&function, &[TARGET.to_string(), "x".to_string()]).is_err());
+
+    let both = CallSpec {
+        calldata: Some(Bytes::from([0x01])),
+        ..call
+    };
+    assert!(both.calldata().is_err());
+}
+
+#[test]
+fn return_data_is_decoded() {
+    let function = parse_function("f() returns (uint256, bool, address)").unwrap();
+    let data = (U256::from(7), true, TARGET).abi_encode_params();
+    let values = decode_output(&function, &data).unwrap().unwrap();
+    let values: Vec<String> = values.iter().map(format_value).collect();
+    assert_eq!(values, ["7", "true", &TARGET.to_string()]);
+
+    let call: CallSpec = format!("{TARGET} f() returns (uint256,bool,address)").parse().unwrap();
+    let result = IMulticall3::Result {
+        success: true,
+        returnData: data.into(),
+    };
+    assert_eq!(
+        describe_result(&call, &result),
+        format!("succeeded, returned (7, true, {TARGET})")
+    );
+    let failed = IMulticall3::Result {
+        success: false,
+        returnData: Bytes::new(),
+    };
+    assert_eq!(describe_result(&call, &failed), "failed");
+}
+
+#[test]
+fn calls_load_from_json() {
+    let path = std::env::temp_dir().join(format!("gas-test-calls-{}.json", std::process::id()));
+    std::fs::write(
+        &path,
+        format!(
+            r#"[
+                {{ "target": "{TARGET}", "signature": "transfer(address,uint256)", "args": ["{TARGET}", "5"] }},
+                {{ "target": "{TARGET}", "calldata": "0xdeadbeef", "allowFailure": true }}
+            ]"#
+        ),
+    )
+    .unwrap();
+    let calls = load_calls(&path).unwrap();
+    assert_eq!(calls.len(), 2);
+    assert!(!calls[0].allow_failure);
+    assert!(calls[1].allow_failure);
+
+    std::fs::write(&path, format!(r#"[{{ "target": "{TARGET}", "gas": 1 }}]"#)).unwrap();
+    assert!(load_calls(&path).is_err());
+    // aggregate3 can't forward ETH, so calls have no value
+    std::fs::write(&path, format!(r#"[{{ "target": "{TARGET}", "value": "1" }}]"#)).unwrap();
+    assert!(load_calls(&path).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn multicall_results_are_reported() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+
+    // One call returning a value and one hitting Multicall3's missing fallback
+    let calls_file = harness.dir.join("calls.json");
+    std::fs::write(
+        &calls_file,
+        format!(
+            r#"[{{ "target": "{}", "calldata": "0xdeadbeef", "allowFailure": true }}]"#,
+            harness.multicall3
+        ),
+    )
+    .unwrap();
+    let entry_point = harness.entry_point.to_string();
+    let call = format!("{} getChainId() returns (uint256)", harness.multicall3);
+    let output = harness
+        .run(&[
+            "--use-pbh",
+            "--pbh-entry-point",
+            &entry_point,
+            "--pbh-nonce",
+            "1",
+            "--gas-limit",
+            "1000000",
+            "--call",
+            &call,
+            "--calls-file",
+            &calls_file.display().to_string(),
+        ])
+        .await;
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let chain_id = harness.provider.get_chain_id().await.unwrap();
+    assert!(stdout.contains(&format!(
+        "[0] {} getChainId() returns (uint256): succeeded, returned ({})",
+        harness.multicall3, chain_id
+    )));
+    assert!(stdout.contains(&format!("[1] {} 0xdeadbeef: failed", harness.multicall3)));
+}
@@ -0,0 +1,116 @@
+mod common;
+
+use alloy_consensus::Transaction;
+use alloy_primitives::U256;
+use alloy_sol_types::SolCall;
+use common::{Harness, WORLD_ID};
+use gas_test::bindings::IPBHEntryPoint;
+use gas_test::client::TxType;
+use gas_test::proof::MockProofProvider;
+use gas_test::transaction::single_call_multicall;
+use gas_test::workload::consume_gas_calldata;
+use gas_test::world_id::WorldID;
+use std::sync::Arc;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn direct_transactions_are_built_and_sent() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let client = harness.client().await;
+    let calldata = consume_gas_calldata(harness.gas_consumer, U256::from(10));
+
+    let tx = client.build_direct(calldata.clone()).await.unwrap();
+    assert_eq!(tx.to(), Some(harness.gas_consumer));
+    assert_eq!(tx.input(), &calldata);
+    assert_eq!(tx.max_fee_per_gas(), 10_000_000_000);
+    assert_eq!(tx.max_priority_fee_per_gas(), Some(1_000_000_000));
+    assert_eq!(tx.gas_limit(), 1_000_000);
+    // The four contract deployments used nonces 0 to 3
+    assert_eq!(tx.nonce(), 4);
+
+    let sent = client.send_raw(&tx, TxType::Direct).await.unwrap();
+    assert_eq!(sent.tx_hash, *tx.tx_hash());
+    assert_eq!((sent.nonce, sent.retries), (4, 0));
+    assert!(harness.receipt(sent.tx_hash).await.status());
+
+    // The next transaction takes the next nonce
+    let tx = client.build_direct(calldata).await.unwrap();
+    assert_eq!(tx.nonce(), 5);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pbh_transactions_call_the_entry_point() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let client = harness.client().await;
+    let world_id = WorldID::new(WORLD_ID).unwrap();
+    let calls = single_call_multicall(
+        harness.gas_consumer,
+        consume_gas_calldata(harness.gas_consumer, U256::from(10)),
+    );
+
+    let tx = client
+        .build_pbh_as(&world_id, calls.clone(), 3)
+        .await
+        .unwrap();
+    assert_eq!(tx.to(), Some(harness.entry_point));
+    let call = IPBHEntryPoint::pbhMulticallCall::abi_decode(tx.input(), true).unwrap();
+    assert_eq!(call.calls.len(), 1);
+    assert_eq!(call.calls[0].target, calls[0].target);
+    assert_eq!(call.calls[0].callData, calls[0].callData);
+
+    let sent = client.send_raw(&tx, TxType::Pbh).await.unwrap();
+    assert!(harness.receipt(sent.tx_hash).await.status());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn prefetched_proofs_are_used_by_the_client() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let world_id = WorldID::new(WORLD_ID).unwrap();
+    let calls = single_call_multicall(
+        harness.gas_consumer,
+        consume_gas_calldata(harness.gas_consumer, U256::from(10)),
+    );
+
+    // Without a pool there is nothing to prefetch into
+    let client = harness.client().await;
+    assert!(client.prefetch_pbh_as(&world_id, &calls, 0).is_err());
+
+    // The pool survives replacing the prover
+    let client = client
+        .with_proof_pool(true)
+        .with_prover(Arc::new(MockProofProvider::default()));
+    client.prefetch_pbh_as(&world_id, &calls, 0).unwrap();
+    let pool = client.proof_pool().unwrap();
+    assert_eq!(pool.len(), 1);
+
+    let tx = client.build_pbh_as(&world_id, calls, 0).await.unwrap();
+    assert!(pool.is_empty());
+    let sent = client.send_raw(&tx, TxType::Pbh).await.unwrap();
+    assert!(harness.receipt(sent.tx_hash).await.status());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn estimates_use_the_configured_fees() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let client = harness.client().await;
+    let calldata = consume_gas_calldata(harness.gas_consumer, U256::from(100));
+
+    let estimate = client
+        .estimate(harness.gas_consumer, calldata, U256::ZERO)
+        .await
+        .unwrap();
+    assert!(estimate.gas > 21_000, "{estimate:?}");
+    assert_eq!(estimate.max_fee_per_gas, 10_000_000_000);
+    assert_eq!(estimate.max_priority_fee_per_gas, 1_000_000_000);
+    assert_eq!(
+        estimate.max_cost(),
+        U256::from(estimate.gas) * U256::from(10_000_000_000u64)
+    );
+}
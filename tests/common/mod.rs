@@ -0,0 +1,495 @@
+//! Hermetic test harness: a local anvil with GasConsumer, a Multicall3, a mock PBH entry
+//! point and a mock smart account deployed, plus an in-process mock sequencer. Conditional
+//! transactions go through `ConditionalBuilder`, which anvil can't stand in for.
+
+#![allow(dead_code)]
+
+use alloy_consensus::TxEnvelope;
+use alloy_primitives::{keccak256, Address, Bytes, TxHash, U64};
+use alloy_provider::Provider;
+use alloy_rpc_types_eth::{TransactionInput, TransactionReceipt, TransactionRequest};
+use alloy_signer_local::PrivateKeySigner;
+use alloy_network::eip2718::{Decodable2718, Encodable2718};
+use alloy_sol_types::{sol, SolCall, SolValue};
+use axum::extract::State;
+use axum::routing::post;
+use axum::{Json, Router};
+use gas_test::bindings::MULTICALL3_ADDRESS;
+use gas_test::client::{FeeSettings, GasTestClient};
+use gas_test::conditional::TransactionConditional;
+use gas_test::config::Network;
+use gas_test::rpc;
+use gas_test::sequencer::{MockSequencer, DEFAULT_TREE_DEPTH};
+use gas_test::transaction::GasTestTransactionBuilder;
+use std::path::PathBuf;
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+sol! {
+    /// Test-only functions of `contracts/test/MockPBHEntryPoint.sol`
+    interface IMockPBHEntryPoint {
+        event PBH(address indexed sender, uint256 indexed nullifierHash, uint256 signalHash);
+
+        function addRoot(uint256 root) external;
+        function validRoots(uint256) external view returns (bool);
+    }
+}
+
+/// First anvil dev account
+pub const DEV_PRIVATE_KEY: &str = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+
+/// Chain id used by `GasTestTransactionBuilder`
+pub const CHAIN_ID: u64 = 4801;
+
+/// PBH transactions allowed per month by the mock entry point
+pub const NUM_PBH_PER_MONTH: u16 = 30;
+
+/// Secret of the test World ID, in the format of `world_id` in the config
+pub const WORLD_ID: &str =
+    "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8gISIjJCUmJygpKissLS4vMDEyMzQ1Njc4OTo7PD0+Pw==";
+
+pub const GAS_CONSUMER_BYTECODE: &str = include_str!(concat!(env!("OUT_DIR"), "/GasConsumer.bin"));
+pub const MOCK_PBH_ENTRY_POINT_BYTECODE: &str =
+    include_str!(concat!(env!("OUT_DIR"), "/MockPBHEntryPoint.bin"));
+pub const MOCK_SMART_ACCOUNT_BYTECODE: &str =
+    include_str!(concat!(env!("OUT_DIR"), "/MockSmartAccount.bin"));
+pub const MULTICALL3_BYTECODE: &str = include_str!(concat!(env!("OUT_DIR"), "/Multicall3.bin"));
+pub const PBH_VECTORS_BYTECODE: &str = include_str!(concat!(env!("OUT_DIR"), "/PBHVectors.bin"));
+
+/// Set to `1` to skip, instead of fail, the tests that need foundry when it isn't installed
+pub const SKIP_ANVIL_VAR: &str = "GASTEST_SKIP_ANVIL";
+
+/// Returns `None` if `GASTEST_SKIP_ANVIL=1`, so the calling test can return early.
+/// Panics with `reason` otherwise, so a missing foundry doesn't pass as a green test.
+pub fn skip_without_foundry<T>(reason: &str) -> Option<T> {
+    if std::env::var(SKIP_ANVIL_VAR).is_ok_and(|value| value == "1") {
+        eprintln!("{reason}, skipping");
+        return None;
+    }
+    panic!("{reason}, install foundry or set {SKIP_ANVIL_VAR}=1 to skip this test");
+}
+
+/// A running anvil, killed on drop
+pub struct Anvil {
+    child: Child,
+    pub endpoint: String,
+}
+
+impl Anvil {
+    /// Spawns anvil on a free port. Returns `None` if anvil is not installed and
+    /// `GASTEST_SKIP_ANVIL=1`, panics if it is not installed otherwise.
+    pub fn spawn() -> Option<Self> {
+        Self::spawn_with(&["--chain-id", &CHAIN_ID.to_string()])
+    }
+
+    /// Spawns anvil on a free port with extra arguments, see `spawn`
+    pub fn spawn_with(args: &[&str]) -> Option<Self> {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let Ok(child) = Command::new("anvil")
+            .args(["--port", &port.to_string()])
+            .args(args)
+            .arg("--silent")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        else {
+            return skip_without_foundry("anvil not available");
+        };
+
+        Some(Self {
+            child,
+            endpoint: format!("http://127.0.0.1:{port}"),
+        })
+    }
+}
+
+impl Drop for Anvil {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Local chain with every contract the tool talks to
+pub struct Harness {
+    pub anvil: Anvil,
+    pub provider: Arc<dyn Provider>,
+    pub signer: PrivateKeySigner,
+    pub gas_consumer: Address,
+    pub multicall3: Address,
+    pub entry_point: Address,
+    pub smart_account: Address,
+    pub sequencer: Arc<MockSequencer>,
+    pub sequencer_url: String,
+    pub dir: PathBuf,
+}
+
+impl Harness {
+    /// Starts anvil and the mock sequencer and deploys the contracts.
+    /// If anvil or the contract artifacts are missing, returns `None` when `GASTEST_SKIP_ANVIL=1`
+    /// and panics otherwise.
+    pub async fn start() -> Option<Self> {
+        if GAS_CONSUMER_BYTECODE.is_empty() || MOCK_PBH_ENTRY_POINT_BYTECODE.is_empty() {
+            return skip_without_foundry("contract artifacts not available");
+        }
+        let anvil = Anvil::spawn()?;
+
+        let provider = wait_for_rpc(&anvil.endpoint).await;
+        let signer: PrivateKeySigner = DEV_PRIVATE_KEY.parse().unwrap();
+
+        let gas_consumer = deploy_contract(&anvil.endpoint, &signer, GAS_CONSUMER_BYTECODE, Bytes::new()).await;
+        let multicall3 = deploy_contract(&anvil.endpoint, &signer, MULTICALL3_BYTECODE, Bytes::new()).await;
+        // The tool batches reads through Multicall3 at its canonical address
+        let code = provider.get_code_at(multicall3).await.unwrap();
+        let _: () = provider
+            .raw_request("anvil_setCode".into(), (MULTICALL3_ADDRESS, code))
+            .await
+            .unwrap();
+        let entry_point = deploy_contract(
+            &anvil.endpoint,
+            &signer,
+            MOCK_PBH_ENTRY_POINT_BYTECODE,
+            (NUM_PBH_PER_MONTH, multicall3).abi_encode_params().into(),
+        )
+        .await;
+        let smart_account =
+            deploy_contract(&anvil.endpoint, &signer, MOCK_SMART_ACCOUNT_BYTECODE, Bytes::new()).await;
+
+        let sequencer = Arc::new(MockSequencer::new(DEFAULT_TREE_DEPTH).unwrap());
+        let sequencer_addr = sequencer
+            .clone()
+            .spawn("127.0.0.1:0".parse().unwrap())
+            .await
+            .unwrap();
+        let sequencer_url = format!("http://{sequencer_addr}");
+
+        let dir = std::env::temp_dir().join(format!(
+            "gas-test-{}",
+            anvil.endpoint.rsplit(':').next().unwrap()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let harness = Self {
+            anvil,
+            provider,
+            signer,
+            gas_consumer,
+            multicall3,
+            entry_point,
+            smart_account,
+            sequencer,
+            sequencer_url,
+            dir,
+        };
+        harness.write_config();
+        Some(harness)
+    }
+
+    pub fn config_path(&self) -> PathBuf {
+        self.dir.join("config.toml")
+    }
+
+    pub fn results_db(&self) -> PathBuf {
+        self.dir.join("results.db")
+    }
+
+    /// Writes a config file pointing at the local chain
+    pub fn write_config(&self) {
+        let config = format!(
+            "contract_address = \"{}\"\nworld_id = \"{}\"\nrpc_address = \"{}\"\n",
+            self.gas_consumer, WORLD_ID, self.anvil.endpoint
+        );
+        std::fs::write(self.config_path(), config).unwrap();
+    }
+
+    /// Runs the gas-test binary against the local chain
+    pub async fn run(&self, args: &[&str]) -> Output {
+        let output = tokio::process::Command::new(env!("CARGO_BIN_EXE_gas-test"))
+            .env("PRIVATE_KEY", DEV_PRIVATE_KEY)
+            .arg("--config-file")
+            .arg(self.config_path())
+            .arg("--results-db")
+            .arg(self.results_db())
+            .args(["--gas-fee", "10", "--priority-gas-fee", "1"])
+            .args(args)
+            .output()
+            .await
+            .unwrap();
+
+        if !output.status.success() {
+            eprintln!("{}", String::from_utf8_lossy(&output.stdout));
+            eprintln!("{}", String::from_utf8_lossy(&output.stderr));
+        }
+        output
+    }
+
+    /// Runs the binary and returns the receipt of the transaction it sent
+    pub async fn run_and_wait(&self, args: &[&str]) -> TransactionReceipt {
+        let output = self.run(args).await;
+        assert!(output.status.success(), "gas-test failed");
+        let tx_hash = sent_tx_hash(&String::from_utf8_lossy(&output.stdout));
+        self.receipt(tx_hash).await
+    }
+
+    pub async fn receipt(&self, tx_hash: TxHash) -> TransactionReceipt {
+        for _ in 0..50 {
+            if let Some(receipt) = self.provider.get_transaction_receipt(tx_hash).await.unwrap() {
+                return receipt;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("transaction {tx_hash} was not included");
+    }
+
+    /// Client of the local chain sending from the first dev account
+    pub async fn client(&self) -> GasTestClient {
+        self.client_at(&self.anvil.endpoint).await
+    }
+
+    /// Client of the local chain talking to it through `rpc_address`, e.g. a `ConditionalBuilder`
+    pub async fn client_at(&self, rpc_address: &str) -> GasTestClient {
+        let network = Network {
+            name: None,
+            rpc_address: rpc_address.to_string(),
+            contract_address: self.gas_consumer,
+            pbh_entry_point: self.entry_point,
+        };
+        let fees = FeeSettings {
+            gas_fee: Some(10.0),
+            priority_gas_fee: Some(1.0),
+            gas_limit: Some(1_000_000),
+        };
+        GasTestClient::connect(network, self.signer.clone(), None, fees)
+            .await
+            .unwrap()
+    }
+
+    /// Deploys another contract to the local chain
+    pub async fn deploy(&self, bytecode: &str, constructor_args: Bytes) -> Address {
+        deploy_contract(&self.anvil.endpoint, &self.signer, bytecode, constructor_args).await
+    }
+
+    /// Makes the mock entry point accept the current root of the mock sequencer.
+    /// From then on, payloads with any other root are rejected.
+    pub async fn accept_sequencer_root(&self) {
+        let call = IMockPBHEntryPoint::addRootCall {
+            root: self.sequencer.root(),
+        };
+        let nonce = self
+            .provider
+            .get_transaction_count(self.signer.address())
+            .await
+            .unwrap();
+        let tx = GasTestTransactionBuilder::new(Some(10.0), Some(1.0), None)
+            .nonce(nonce)
+            .to(self.entry_point)
+            .input(TransactionInput::new(call.abi_encode().into()))
+            .build(self.signer.clone())
+            .await
+            .unwrap();
+        let pending_tx = self
+            .provider
+            .send_raw_transaction(&tx.encoded_2718())
+            .await
+            .unwrap();
+        assert!(self.receipt(*pending_tx.tx_hash()).await.status());
+    }
+
+    /// Calls a view function
+    pub async fn call<C: SolCall>(&self, to: Address, call: C) -> C::Return {
+        let request = TransactionRequest::default()
+            .to(to)
+            .input(TransactionInput::new(call.abi_encode().into()));
+        let output = self.provider.call(&request).await.unwrap();
+        C::abi_decode_returns(&output, true).unwrap()
+    }
+}
+
+/// JSON-RPC proxy in front of anvil that handles `eth_sendRawTransactionConditional` the way the
+/// World Chain builder does: conditional transactions are queued and `build_block` only includes
+/// the ones whose conditions still hold when it gets to them. Pending nonces count the queued
+/// transactions, other requests go to anvil.
+pub struct ConditionalBuilder {
+    pub url: String,
+    state: Arc<BuilderState>,
+}
+
+struct BuilderState {
+    anvil: String,
+    provider: Arc<dyn Provider>,
+    http: reqwest::Client,
+    /// Sender, encoded transaction and conditions
+    queue: Mutex<Vec<(Address, Bytes, TransactionConditional)>>,
+}
+
+impl ConditionalBuilder {
+    pub async fn start(harness: &Harness) -> Self {
+        let state = Arc::new(BuilderState {
+            anvil: harness.anvil.endpoint.clone(),
+            provider: harness.provider.clone(),
+            http: reqwest::Client::new(),
+            queue: Mutex::new(Vec::new()),
+        });
+        let app = Router::new()
+            .route("/", post(handle_rpc))
+            .with_state(state.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        Self { url, state }
+    }
+
+    /// Goes through the queue in order and sends every transaction whose conditions hold to
+    /// anvil, which mines it right away. Returns the included transactions, the others are dropped.
+    pub async fn build_block(&self) -> Vec<TxHash> {
+        build_block(&self.state).await
+    }
+
+    /// Builds a block every `interval` in the background
+    pub fn build_every(&self, interval: Duration) {
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                build_block(&state).await;
+            }
+        });
+    }
+}
+
+async fn build_block(state: &BuilderState) -> Vec<TxHash> {
+    // Held until the block is built, so pending nonces never miss a transaction
+    let mut queue = state.queue.lock().await;
+    let mut included = Vec::new();
+    for (_, encoded_tx, conditional) in queue.drain(..) {
+        if conditional.check(&state.provider).await.unwrap().is_some() {
+            continue;
+        }
+        let receipt = state
+            .provider
+            .send_raw_transaction(&encoded_tx)
+            .await
+            .unwrap()
+            .get_receipt()
+            .await
+            .unwrap();
+        included.push(receipt.transaction_hash);
+    }
+    included
+}
+
+async fn handle_rpc(
+    State(state): State<Arc<BuilderState>>,
+    Json(request): Json<serde_json::Value>,
+) -> Json<serde_json::Value> {
+    if request["method"] == "eth_getTransactionCount" && request["params"][1] == "pending" {
+        let account: Address = serde_json::from_value(request["params"][0].clone()).unwrap();
+        let queue = state.queue.lock().await;
+        let queued = queue.iter().filter(|(sender, ..)| *sender == account).count();
+        let nonce = state
+            .provider
+            .get_transaction_count(account)
+            .pending()
+            .await
+            .unwrap();
+        return Json(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "result": U64::from(nonce + queued as u64),
+        }));
+    }
+    if request["method"] != "eth_sendRawTransactionConditional" {
+        let response = state
+            .http
+            .post(&state.anvil)
+            .json(&request)
+            .send()
+            .await
+            .unwrap();
+        return Json(response.json().await.unwrap());
+    }
+
+    let (encoded_tx, conditional): (Bytes, TransactionConditional) =
+        serde_json::from_value(request["params"].clone()).unwrap();
+    let mut response = match conditional.check(&state.provider).await.unwrap() {
+        Some(failure) => serde_json::json!({
+            "error": { "code": -32003, "message": format!("conditional failed: {failure}") }
+        }),
+        None => {
+            let tx_hash = keccak256(&encoded_tx);
+            let sender = TxEnvelope::decode_2718(&mut encoded_tx.as_ref())
+                .unwrap()
+                .recover_signer()
+                .unwrap();
+            state
+                .queue
+                .lock()
+                .await
+                .push((sender, encoded_tx, conditional));
+            serde_json::json!({ "result": tx_hash })
+        }
+    };
+    response["jsonrpc"] = "2.0".into();
+    response["id"] = request["id"].clone();
+    Json(response)
+}
+
+/// Extracts the hash printed as `Transaction sent: <hash>` by the binary
+pub fn sent_tx_hash(stdout: &str) -> TxHash {
+    stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("Transaction sent: "))
+        .expect("no transaction was sent")
+        .trim()
+        .parse()
+        .unwrap()
+}
+
+pub async fn wait_for_rpc(endpoint: &str) -> Arc<dyn Provider> {
+    for _ in 0..100 {
+        if let Ok(provider) = rpc::connect(endpoint).await {
+            if provider.get_block_number().await.is_ok() {
+                return provider;
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("anvil did not start at {endpoint}");
+}
+
+async fn deploy_contract(
+    endpoint: &str,
+    signer: &PrivateKeySigner,
+    bytecode: &str,
+    constructor_args: Bytes,
+) -> Address {
+    let mut init_code = hex::decode(bytecode.trim()).unwrap();
+    init_code.extend_from_slice(&constructor_args);
+
+    // No contracts are configured yet, the client only signs and sends the deployment
+    let network = Network {
+        name: None,
+        rpc_address: endpoint.to_string(),
+        contract_address: Address::ZERO,
+        pbh_entry_point: Address::ZERO,
+    };
+    let fees = FeeSettings {
+        gas_fee: Some(10.0),
+        priority_gas_fee: Some(1.0),
+        gas_limit: None,
+    };
+    let client = GasTestClient::connect(network, signer.clone(), None, fees)
+        .await
+        .unwrap();
+    client
+        .deploy_code(init_code.into(), None)
+        .await
+        .unwrap()
+        .address
+}
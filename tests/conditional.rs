@@ -0,0 +1,182 @@
+mod common;
+
+use alloy_primitives::{address, b256, U256, U64};
+use common::{ConditionalBuilder, Harness};
+use gas_test::conditional::{
+    storage_root, AccountStorage, ConditionFailure, ConditionalBounds, ConditionalOutcome,
+    TransactionConditional,
+};
+use gas_test::transaction::single_call_multicall;
+use gas_test::workload::consume_gas_calldata;
+use gas_test::world_id::WorldID;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+#[test]
+fn conditional_serializes_like_the_rpc_expects() {
+    let account = address!("5FbDB2315678afecb367f032d93F642f64180aa3");
+    let root = b256!("56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421");
+    let conditional = TransactionConditional {
+        known_accounts: BTreeMap::from([(account, AccountStorage::RootHash(root))]),
+        block_number_min: Some(U64::from(100)),
+        block_number_max: Some(U64::from(110)),
+        timestamp_min: None,
+        timestamp_max: Some(U64::from(1_700_000_060)),
+    };
+
+    let json = serde_json::to_value(&conditional).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "knownAccounts": { "0x5fbdb2315678afecb367f032d93f642f64180aa3": root },
+            "blockNumberMin": "0x64",
+            "blockNumberMax": "0x6e",
+            "timestampMax": "0x6553f13c",
+        })
+    );
+    assert_eq!(
+        serde_json::from_value::<TransactionConditional>(json).unwrap(),
+        conditional
+    );
+
+    // Known accounts can also pin single slots
+    let slots: TransactionConditional = serde_json::from_value(serde_json::json!({
+        "knownAccounts": { "0x5fbdb2315678afecb367f032d93f642f64180aa3": { "0x00": root } }
+    }))
+    .unwrap();
+    assert!(matches!(
+        slots.known_accounts[&account],
+        AccountStorage::Slots(_)
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn storage_root_changes_fail_the_condition() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let bounds = ConditionalBounds {
+        blocks: 100,
+        seconds: 600,
+    };
+    let conditional = TransactionConditional::derive(&harness.provider, bounds, &[harness.gas_consumer])
+        .await
+        .unwrap();
+    assert_eq!(conditional.check(&harness.provider).await.unwrap(), None);
+
+    // Writing a fresh slot changes GasConsumer's storage root
+    let before = storage_root(&harness.provider, harness.gas_consumer).await.unwrap();
+    let receipt = harness
+        .run_and_wait(&["--workload", "storage-cold", "--iterations", "1"])
+        .await;
+    assert!(receipt.status());
+    let after = storage_root(&harness.provider, harness.gas_consumer).await.unwrap();
+
+    assert_eq!(
+        conditional.check(&harness.provider).await.unwrap(),
+        Some(ConditionFailure::StorageRoot {
+            address: harness.gas_consumer,
+            expected: before,
+            current: after,
+        })
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn passed_block_bound_fails_the_condition() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let bounds = ConditionalBounds {
+        blocks: 0,
+        seconds: 600,
+    };
+    let conditional = TransactionConditional::derive(&harness.provider, bounds, &[])
+        .await
+        .unwrap();
+
+    // Any transaction mines a block on anvil
+    harness.run_and_wait(&["--iterations", "1"]).await;
+    assert!(matches!(
+        conditional.check(&harness.provider).await.unwrap(),
+        Some(ConditionFailure::BlockNumber { .. })
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pbh_transactions_are_sent_conditionally() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let builder = ConditionalBuilder::start(&harness).await;
+    builder.build_every(Duration::from_millis(200));
+
+    let entry_point = harness.entry_point.to_string();
+    let output = harness
+        .run(&[
+            "--provider-uri",
+            &builder.url,
+            "--pbh-entry-point",
+            &entry_point,
+            "--gas-limit",
+            "1000000",
+            "--use-pbh",
+            "--count",
+            "2",
+            "--conditional",
+        ])
+        .await;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let included = stdout
+        .lines()
+        .filter(|line| line.contains(": included in block"))
+        .count();
+    assert_eq!(included, 2, "{stdout}");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pinned_entry_point_drops_later_pbh_transactions() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let builder = ConditionalBuilder::start(&harness).await;
+    let world_id = WorldID::new(common::WORLD_ID).unwrap();
+    let calls = single_call_multicall(
+        harness.gas_consumer,
+        consume_gas_calldata(harness.gas_consumer, U256::from(10)),
+    );
+    let bounds = ConditionalBounds {
+        blocks: 100,
+        seconds: 600,
+    };
+
+    // By default only the block and time bounds apply, so both PBH transactions get in
+    let client = harness
+        .client_at(&builder.url)
+        .await
+        .with_conditional(Some(bounds));
+    let first = client.send_pbh_as(&world_id, calls.clone(), 0).await.unwrap();
+    let second = client.send_pbh_as(&world_id, calls.clone(), 1).await.unwrap();
+    assert!(first.conditional.as_ref().unwrap().known_accounts.is_empty());
+    assert_eq!(builder.build_block().await, [first.tx_hash, second.tx_hash]);
+
+    // Each PBH transaction spends a nullifier, so the entry point's storage root moves under
+    // every one after the first
+    let client = client.with_known_accounts(vec![harness.entry_point]);
+    let first = client.send_pbh_as(&world_id, calls.clone(), 2).await.unwrap();
+    let second = client.send_pbh_as(&world_id, calls, 3).await.unwrap();
+    assert_eq!(builder.build_block().await, [first.tx_hash]);
+    let outcome = client
+        .wait_for_conditional(&second, Duration::from_secs(5))
+        .await
+        .unwrap();
+    assert!(
+        matches!(
+            outcome,
+            ConditionalOutcome::Dropped(ConditionFailure::StorageRoot { address, .. })
+                if address == harness.entry_point
+        ),
+        "{outcome:?}"
+    );
+}
@@ -0,0 +1,179 @@
+use alloy_primitives::{address, Address};
+use gas_test::config::{Config, ConfigLayer, ConfigSource, DEFAULT_PBH_ENTRY_POINT};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+const FILE_CONTRACT: Address = address!("bA9FfCB4cc50eE2EC2F89740f5d6841cC8A74030");
+const PROFILE_CONTRACT: Address = address!("00000000000000000000000000000000000000bb");
+const CLI_CONTRACT: Address = address!("00000000000000000000000000000000000000cc");
+
+fn write_config(name: &str, content: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "gas-test-config-{}-{}.toml",
+        name,
+        std::process::id()
+    ));
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+fn env(vars: &[(&str, &str)]) -> ConfigLayer {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    ConfigLayer::from_vars(|name| vars.get(name).cloned())
+}
+
+#[test]
+fn later_layers_take_precedence() {
+    let path = write_config(
+        "precedence",
+        &format!(
+            r#"
+contract_address = "{FILE_CONTRACT}"
+rpc_address = "http://127.0.0.1:8545"
+world_id = "file"
+
+[networks.local]
+contract_address = "{PROFILE_CONTRACT}"
+"#
+        ),
+    );
+
+    let config = Config::load(&path, None, ConfigLayer::default(), ConfigLayer::default()).unwrap();
+    assert!(config.file_found);
+    assert_eq!(config.contract_address().unwrap(), FILE_CONTRACT);
+    assert_eq!(config.pbh_entry_point.value, DEFAULT_PBH_ENTRY_POINT);
+    assert_eq!(config.pbh_entry_point.source, ConfigSource::Default);
+
+    let config = Config::load(
+        &path,
+        Some("local"),
+        env(&[("GASTEST_RPC_ADDRESS", "ws://127.0.0.1:8546")]),
+        ConfigLayer::default(),
+    )
+    .unwrap();
+    let contract = config.contract_address.as_ref().unwrap();
+    assert_eq!(contract.value, PROFILE_CONTRACT);
+    assert_eq!(contract.source, ConfigSource::Network("local".into()));
+    assert_eq!(config.rpc_address().unwrap(), "ws://127.0.0.1:8546");
+    assert_eq!(
+        config.rpc_address.as_ref().unwrap().source,
+        ConfigSource::Env
+    );
+
+    let config = Config::load(
+        &path,
+        Some("local"),
+        env(&[("GASTEST_CONTRACT_ADDRESS", &PROFILE_CONTRACT.to_string())]),
+        ConfigLayer {
+            contract_address: Some(CLI_CONTRACT.to_string()),
+            ..ConfigLayer::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(config.contract_address().unwrap(), CLI_CONTRACT);
+    assert_eq!(config.world_id().unwrap(), "file");
+
+    // Sources name the variable or flag that set the value
+    let entries = config.entries();
+    let source = |key: &str| entries.iter().find(|(k, ..)| *k == key).unwrap().2.clone();
+    assert_eq!(source("contract_address"), "--contract-address");
+    assert_eq!(source("rpc_address"), "config file");
+    assert_eq!(source("pbh_entry_point"), "default");
+
+    assert!(Config::load(
+        &path,
+        Some("mainnet"),
+        ConfigLayer::default(),
+        ConfigLayer::default()
+    )
+    .is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn config_file_is_optional() {
+    let path = std::env::temp_dir().join("gas-test-config-missing.toml");
+    let config = Config::load(
+        &path,
+        None,
+        env(&[
+            ("GASTEST_RPC_ADDRESS", "https://example.com/rpc"),
+            ("GASTEST_WORLD_IDS", "a, b,"),
+        ]),
+        ConfigLayer::default(),
+    )
+    .unwrap();
+    assert!(!config.file_found);
+    assert_eq!(config.rpc_address().unwrap(), "https://example.com/rpc");
+    assert_eq!(config.world_ids.unwrap().value, ["a", "b"]);
+    assert!(config.contract_address.is_none());
+
+    // Missing values are reported with the ways to set them
+    let config = Config::load(&path, None, ConfigLayer::default(), ConfigLayer::default()).unwrap();
+    let err = config.network().unwrap_err().to_string();
+    assert!(err.contains("GASTEST_RPC_ADDRESS"), "{err}");
+    assert!(err.contains("--provider-uri"), "{err}");
+}
+
+#[test]
+fn addresses_and_urls_are_validated() {
+    let path = write_config("invalid", "contract_address = \"0x1234\"\n");
+    let err =
+        Config::load(&path, None, ConfigLayer::default(), ConfigLayer::default()).unwrap_err();
+    assert!(err.to_string().contains("contract_address"), "{err}");
+    assert!(err.to_string().contains("config file"), "{err}");
+    std::fs::remove_file(&path).unwrap();
+
+    let missing = std::env::temp_dir().join("gas-test-config-missing.toml");
+    let err = Config::load(
+        &missing,
+        None,
+        env(&[("GASTEST_RPC_ADDRESS", "ftp://example.com")]),
+        ConfigLayer::default(),
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("GASTEST_RPC_ADDRESS"), "{err}");
+
+    let err = Config::load(
+        &missing,
+        None,
+        ConfigLayer::default(),
+        ConfigLayer {
+            pbh_entry_point: Some("entry point".into()),
+            ..ConfigLayer::default()
+        },
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("--pbh-entry-point"), "{err}");
+}
+
+#[test]
+fn config_show_prints_sources() {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_gas-test"))
+        .args([
+            "--config-file",
+            "/nonexistent/config.toml",
+            "config",
+            "show",
+        ])
+        .env("GASTEST_RPC_ADDRESS", "http://127.0.0.1:8545")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("(not found)"));
+    let rpc = stdout
+        .lines()
+        .find(|line| line.starts_with("rpc_address"))
+        .unwrap();
+    assert!(rpc.contains("http://127.0.0.1:8545") && rpc.ends_with("GASTEST_RPC_ADDRESS"));
+    let contract = stdout
+        .lines()
+        .find(|line| line.starts_with("contract_address"))
+        .unwrap();
+    assert!(contract.contains("(unset)"));
+}
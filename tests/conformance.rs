@@ -0,0 +1,407 @@
+//! Checks the Rust PBH encoders against the Solidity ones in `contracts/test/PBHEncoding.sol`.
+//!
+//! `tests/vectors/pbh.json` holds vectors produced by the contracts on anvil. Regenerate it with
+//! `cargo test --test conformance -- --ignored generate_fixed_vectors` after changing the encodings.
+//!
+//! `PBHEncoding.sol` mirrors World Chain's PBHEntryPoint, PBHExternalNullifier and ByteHasher, it
+//! isn't compiled from them. A misreading of the upstream contracts shared by the mirror and the
+//! Rust encoders passes these checks. `onchain_pbh_transaction_verifies` closes the loop: it
+//! verifies the proof of a real `pbhMulticall` against the signal hash computed here, which only
+//! succeeds if the encodings match the deployed entry point.
+
+mod common;
+
+use alloy_consensus::Transaction;
+use alloy_primitives::{address, Address, Bytes, TxHash, U256};
+use alloy_provider::Provider;
+use alloy_sol_types::{sol, SolCall};
+use common::{Harness, IMockPBHEntryPoint, PBH_VECTORS_BYTECODE};
+use gas_test::bindings::IMulticall3::Call3;
+use gas_test::bindings::IPBHEntryPoint;
+use gas_test::rpc;
+use gas_test::sequencer::DEFAULT_TREE_DEPTH;
+use gas_test::transaction::{multicall_signal_hash, single_call_multicall};
+use gas_test::userop::userop_signal_hash;
+use gas_test::workload::consume_gas_calldata;
+use gas_test::world_id::WorldID;
+use semaphore_rs::protocol::{verify_proof, Proof};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use world_chain_builder_pbh::date_marker::DateMarker;
+
+sol! {
+    interface IPBHVectors {
+        struct Call3 {
+            address target;
+            bool allowFailure;
+            bytes callData;
+        }
+
+        function multicallSignalHash(address sender, Call3[] calldata calls) external pure returns (uint256);
+        function userOpSignalHash(address sender, uint256 nonce, bytes calldata callData) external pure returns (uint256);
+        function encodeExternalNullifier(uint8 version, uint16 pbhNonce, uint8 month, uint16 year) external pure returns (uint256);
+    }
+}
+
+/// Version of the external nullifier encoding
+const EXTERNAL_NULLIFIER_VERSION: u8 = 1;
+
+/// Identities of the nullifier hash vectors, in the format of `world_id` in the config
+const VECTOR_WORLD_IDS: [&str; 2] = [
+    "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8gISIjJCUmJygpKissLS4vMDEyMzQ1Njc4OTo7PD0+Pw==",
+    "EREREREREREREREREREREREREREREREREREREREREREf7u7u7u7u7u7u7u7u7u7u7u7u7u7u7u7u7u7u7u7u7g==",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct CallVector {
+    target: Address,
+    allow_failure: bool,
+    call_data: Bytes,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct MulticallSignalVector {
+    sender: Address,
+    calls: Vec<CallVector>,
+    signal_hash: U256,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct UserOpSignalVector {
+    sender: Address,
+    nonce: U256,
+    call_data: Bytes,
+    signal_hash: U256,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ExternalNullifierVector {
+    year: u16,
+    month: u8,
+    pbh_nonce: u16,
+    encoded: U256,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct NullifierHashVector {
+    /// Secret of the identity, in the format of `world_id` in the config
+    world_id: String,
+    year: u16,
+    month: u8,
+    pbh_nonce: u16,
+    nullifier_hash: U256,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Vectors {
+    multicall_signal_hashes: Vec<MulticallSignalVector>,
+    user_op_signal_hashes: Vec<UserOpSignalVector>,
+    external_nullifiers: Vec<ExternalNullifierVector>,
+    nullifier_hashes: Vec<NullifierHashVector>,
+}
+
+impl CallVector {
+    fn to_call3(&self) -> Call3 {
+        Call3 {
+            target: self.target,
+            allowFailure: self.allow_failure,
+            callData: self.call_data.clone(),
+        }
+    }
+}
+
+fn vectors_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/vectors/pbh.json")
+}
+
+fn fixed_vectors() -> Vectors {
+    let contents = std::fs::read_to_string(vectors_path()).unwrap_or_else(|err| {
+        panic!(
+            "{} is missing ({err}), see the top of this file",
+            vectors_path().display()
+        )
+    });
+    serde_json::from_str(&contents).unwrap()
+}
+
+/// Inputs of the vectors, with every output zeroed
+fn inputs() -> Vectors {
+    let sender = address!("f39Fd6e51aad88F6F4ce6aB8827279cffFb92266");
+    let target = address!("5FbDB2315678afecb367f032d93F642f64180aa3");
+    let call = |allow_failure: bool, call_data: Bytes| CallVector {
+        target,
+        allow_failure,
+        call_data,
+    };
+
+    let multicall_signal_hashes = vec![
+        vec![],
+        vec![call(false, consume_gas_calldata(target, U256::from(10)))],
+        vec![
+            call(false, consume_gas_calldata(target, U256::from(1))),
+            call(true, Bytes::new()),
+            call(true, Bytes::from(vec![0xab; 33])),
+        ],
+    ]
+    .into_iter()
+    .flat_map(|calls| {
+        [Address::ZERO, sender].map(|sender| MulticallSignalVector {
+            sender,
+            calls: calls.clone(),
+            signal_hash: U256::ZERO,
+        })
+    })
+    .collect();
+
+    let user_op_signal_hashes = [
+        (U256::ZERO, Bytes::new()),
+        (U256::from(1), consume_gas_calldata(target, U256::from(10))),
+        ((U256::from(7) << 64) | U256::from(3), Bytes::from(vec![0xcd; 67])),
+    ]
+    .into_iter()
+    .map(|(nonce, call_data)| UserOpSignalVector {
+        sender,
+        nonce,
+        call_data,
+        signal_hash: U256::ZERO,
+    })
+    .collect();
+
+    // The last two are the round trip cases of `ExternalNullifier` in world-chain's PBH crate
+    let dates = [
+        (2025, 1, 0),
+        (2025, 12, 29),
+        (2026, 10, 1),
+        (2099, 7, u16::MAX),
+        (2025, 1, 11),
+        (3078, 12, 19),
+    ];
+    let external_nullifiers = dates
+        .iter()
+        .map(|&(year, month, pbh_nonce)| ExternalNullifierVector {
+            year,
+            month,
+            pbh_nonce,
+            encoded: U256::ZERO,
+        })
+        .collect();
+    let nullifier_hashes = VECTOR_WORLD_IDS
+        .iter()
+        .flat_map(|world_id| {
+            dates
+                .iter()
+                .map(|&(year, month, pbh_nonce)| NullifierHashVector {
+                    world_id: world_id.to_string(),
+                    year,
+                    month,
+                    pbh_nonce,
+                    nullifier_hash: U256::ZERO,
+                })
+        })
+        .collect();
+
+    Vectors {
+        multicall_signal_hashes,
+        user_op_signal_hashes,
+        external_nullifiers,
+        nullifier_hashes,
+    }
+}
+
+/// Fills in the outputs with the Rust encoders
+fn rust_vectors(inputs: &Vectors) -> Vectors {
+    let mut vectors = inputs.clone();
+    for vector in &mut vectors.multicall_signal_hashes {
+        let calls: Vec<Call3> = vector.calls.iter().map(CallVector::to_call3).collect();
+        vector.signal_hash = multicall_signal_hash(vector.sender, &calls);
+    }
+    for vector in &mut vectors.user_op_signal_hashes {
+        vector.signal_hash = userop_signal_hash(vector.sender, vector.nonce, &vector.call_data);
+    }
+    for vector in &mut vectors.external_nullifiers {
+        let date_marker = DateMarker::new(vector.year as i32, vector.month as u32);
+        let (_, encoded, _) = WorldID::new(common::WORLD_ID)
+            .unwrap()
+            .pbh_ext_nullifier_at(date_marker, vector.pbh_nonce);
+        vector.encoded = encoded;
+    }
+    for vector in &mut vectors.nullifier_hashes {
+        let date_marker = DateMarker::new(vector.year as i32, vector.month as u32);
+        let (_, _, nullifier_hash) = WorldID::new(&vector.world_id)
+            .unwrap()
+            .pbh_ext_nullifier_at(date_marker, vector.pbh_nonce);
+        vector.nullifier_hash = nullifier_hash;
+    }
+    vectors
+}
+
+/// Fills in the outputs with the contracts. Nullifier hashes are only computed inside the
+/// Semaphore circuit, so they are derived from the external nullifier the contract encodes.
+async fn contract_vectors(harness: &Harness, inputs: &Vectors) -> Vectors {
+    let contract = harness.deploy(PBH_VECTORS_BYTECODE, Bytes::new()).await;
+    let encode_external_nullifier = |year: u16, month: u8, pbh_nonce: u16| {
+        IPBHVectors::encodeExternalNullifierCall {
+            version: EXTERNAL_NULLIFIER_VERSION,
+            pbhNonce: pbh_nonce,
+            month,
+            year,
+        }
+    };
+
+    let mut vectors = inputs.clone();
+    for vector in &mut vectors.multicall_signal_hashes {
+        let calls = vector
+            .calls
+            .iter()
+            .map(|call| IPBHVectors::Call3 {
+                target: call.target,
+                allowFailure: call.allow_failure,
+                callData: call.call_data.clone(),
+            })
+            .collect();
+        let call = IPBHVectors::multicallSignalHashCall {
+            sender: vector.sender,
+            calls,
+        };
+        vector.signal_hash = harness.call(contract, call).await._0;
+    }
+    for vector in &mut vectors.user_op_signal_hashes {
+        let call = IPBHVectors::userOpSignalHashCall {
+            sender: vector.sender,
+            nonce: vector.nonce,
+            callData: vector.call_data.clone(),
+        };
+        vector.signal_hash = harness.call(contract, call).await._0;
+    }
+    for vector in &mut vectors.external_nullifiers {
+        let call = encode_external_nullifier(vector.year, vector.month, vector.pbh_nonce);
+        vector.encoded = harness.call(contract, call).await._0;
+    }
+    for vector in &mut vectors.nullifier_hashes {
+        let call = encode_external_nullifier(vector.year, vector.month, vector.pbh_nonce);
+        let encoded = harness.call(contract, call).await._0;
+        let identity = WorldID::new(&vector.world_id).unwrap().identity;
+        vector.nullifier_hash = semaphore_rs::protocol::generate_nullifier_hash(&identity, encoded);
+    }
+    vectors
+}
+
+#[test]
+fn rust_encoders_match_fixed_vectors() {
+    let fixed = fixed_vectors();
+    assert_eq!(rust_vectors(&fixed), fixed);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rust_encoders_match_contracts() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let inputs = inputs();
+    let expected = contract_vectors(&harness, &inputs).await;
+    assert_eq!(rust_vectors(&inputs), expected);
+
+    // The fixed vectors must be regenerated when the inputs or encodings change
+    assert_eq!(fixed_vectors(), expected, "tests/vectors/pbh.json is out of date");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pbh_multicall_signal_matches_entry_point() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let entry_point = harness.entry_point.to_string();
+
+    let receipt = harness
+        .run_and_wait(&[
+            "--use-pbh",
+            "--pbh-entry-point",
+            &entry_point,
+            "--iterations",
+            "10",
+            "--gas-limit",
+            "1000000",
+        ])
+        .await;
+    assert!(receipt.status());
+
+    let event = receipt
+        .inner
+        .logs()
+        .iter()
+        .find_map(|log| log.log_decode::<IMockPBHEntryPoint::PBH>().ok())
+        .expect("no PBH event")
+        .inner
+        .data;
+    let calls = single_call_multicall(
+        harness.gas_consumer,
+        consume_gas_calldata(harness.gas_consumer, U256::from(10)),
+    );
+    assert_eq!(event.sender, harness.signer.address());
+    assert_eq!(
+        event.signalHash,
+        multicall_signal_hash(harness.signer.address(), &calls)
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "writes tests/vectors/pbh.json"]
+async fn generate_fixed_vectors() {
+    let harness = Harness::start()
+        .await
+        .expect("generating the vectors needs foundry");
+    let vectors = contract_vectors(&harness, &inputs()).await;
+
+    let json = serde_json::to_string_pretty(&vectors).unwrap();
+    std::fs::create_dir_all(vectors_path().parent().unwrap()).unwrap();
+    std::fs::write(vectors_path(), json + "\n").unwrap();
+}
+
+/// Verifies the proof of a `pbhMulticall` included on World Chain with the signal hash of the Rust
+/// encoder, and decodes its external nullifier. Run it with the RPC of a World Chain network and
+/// the hash of a PBH transaction sent there directly, e.g. one of this tool's `--use-pbh` runs:
+/// `PBH_RPC_URL=<url> PBH_TX_HASH=<hash> cargo test --test conformance -- --ignored onchain`
+#[tokio::test]
+#[ignore = "needs PBH_RPC_URL and PBH_TX_HASH of a real PBH transaction"]
+async fn onchain_pbh_transaction_verifies() {
+    let rpc_url = std::env::var("PBH_RPC_URL").expect("PBH_RPC_URL is not set");
+    let tx_hash: TxHash = std::env::var("PBH_TX_HASH")
+        .expect("PBH_TX_HASH is not set")
+        .parse()
+        .unwrap();
+
+    let provider = rpc::connect(&rpc_url).await.unwrap();
+    let tx = provider
+        .get_transaction_by_hash(tx_hash)
+        .await
+        .unwrap()
+        .expect("PBH transaction not found");
+    let receipt = provider
+        .get_transaction_receipt(tx_hash)
+        .await
+        .unwrap()
+        .expect("PBH transaction is not included");
+    assert!(receipt.status(), "PBH transaction reverted");
+    let call = IPBHEntryPoint::pbhMulticallCall::abi_decode(tx.input(), true)
+        .expect("not a pbhMulticall transaction");
+    let payload = call.payload;
+
+    // The entry point verified this proof against its own signal hash, so it only verifies
+    // here if the Rust encoder computes the same one
+    let signal_hash = multicall_signal_hash(tx.from, &call.calls);
+    assert!(verify_proof(
+        payload.root,
+        payload.nullifierHash,
+        signal_hash,
+        payload.pbhExternalNullifier,
+        &Proof::from_flat(payload.proof),
+        DEFAULT_TREE_DEPTH,
+    )
+    .unwrap());
+
+    // The external nullifier names a month and the version the encoder writes
+    let encoded = payload.pbhExternalNullifier;
+    assert_eq!(encoded.byte(0), EXTERNAL_NULLIFIER_VERSION);
+    let month = encoded.byte(3);
+    assert!((1..=12).contains(&month), "month {month}");
+}
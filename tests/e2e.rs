@@ -0,0 +1,380 @@
+mod common;
+
+use alloy_primitives::U256;
+use clap::ValueEnum;
+use common::{Harness, IMockPBHEntryPoint};
+use gas_test::bindings::{GasConsumer, IPBHEntryPoint};
+use gas_test::sequencer::{InclusionProofResponse, InsertResponse};
+use gas_test::workload::{consumed_events, Workload};
+use gas_test::world_id::{InclusionProof, WorldID};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn direct_transaction() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+
+    let receipt = harness.run_and_wait(&["--iterations", "10"]).await;
+    assert!(receipt.status());
+    assert_eq!(receipt.to, Some(harness.gas_consumer));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn direct_transaction_with_workload() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+
+    let receipt = harness
+        .run_and_wait(&["--workload", "logs", "--iterations", "3", "--gas-limit", "500000"])
+        .await;
+    assert!(receipt.status());
+    assert_eq!(consumed_events(&receipt).len(), 3);
+
+    let receipt = harness
+        .run_and_wait(&["--workload", "storage-cold", "--iterations", "2", "--gas-limit", "500000"])
+        .await;
+    assert!(receipt.status());
+    let next_slot = harness
+        .call(harness.gas_consumer, GasConsumer::nextSlotCall {})
+        .await
+        ._0;
+    assert_eq!(next_slot, U256::from(2));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn every_workload_grows_with_iterations() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+
+    for workload in Workload::value_variants() {
+        let name = workload.to_possible_value().unwrap().get_name().to_string();
+        let mut gas_used = Vec::new();
+        for iterations in ["2", "20"] {
+            let receipt = harness
+                .run_and_wait(&[
+                    "--workload",
+                    &name,
+                    "--iterations",
+                    iterations,
+                    "--gas-limit",
+                    "3000000",
+                ])
+                .await;
+            assert!(receipt.status(), "{name} reverted");
+            gas_used.push(receipt.gas_used);
+        }
+        assert!(gas_used[1] > gas_used[0], "{name} used {gas_used:?}");
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pbh_multicall_transaction() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let entry_point = harness.entry_point.to_string();
+    let args = [
+        "--use-pbh",
+        "--pbh-entry-point",
+        &entry_point,
+        "--pbh-nonce",
+        "1",
+        "--iterations",
+        "10",
+        "--gas-limit",
+        "1000000",
+    ];
+
+    let receipt = harness.run_and_wait(&args).await;
+    assert!(receipt.status());
+    assert_eq!(receipt.to, Some(harness.entry_point));
+
+    let (_, _, nullifier_hash) = WorldID::new(common::WORLD_ID).unwrap().pbh_ext_nullifier(1);
+    let used = harness
+        .call(
+            harness.entry_point,
+            IPBHEntryPoint::nullifierHashesCall { _0: nullifier_hash },
+        )
+        .await
+        ._0;
+    assert!(used);
+
+    // The same PBH nonce can't be used twice in a month
+    let receipt = harness.run_and_wait(&args).await;
+    assert!(!receipt.status());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pbh_multicall_with_local_prover() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let commitment = WorldID::new(common::WORLD_ID).unwrap().identity.commitment();
+    harness.sequencer.insert(commitment).unwrap();
+    harness.accept_sequencer_root().await;
+
+    let entry_point = harness.entry_point.to_string();
+    let cache_dir = harness.dir.join("inclusion-proofs");
+    let _ = std::fs::remove_dir_all(&cache_dir);
+    let pbh_args = |prover: &'static str| {
+        vec![
+            "--use-pbh".to_string(),
+            "--pbh-entry-point".to_string(),
+            entry_point.clone(),
+            "--prover".to_string(),
+            prover.to_string(),
+            "--sequencer-url".to_string(),
+            harness.sequencer_url.clone(),
+            "--proof-cache-dir".to_string(),
+            cache_dir.display().to_string(),
+            "--iterations".to_string(),
+            "10".to_string(),
+            "--gas-limit".to_string(),
+            "1000000".to_string(),
+        ]
+    };
+
+    // The mock prover's root is not in the tree the entry point accepts
+    let args = pbh_args("mock");
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let receipt = harness.run_and_wait(&args).await;
+    assert!(!receipt.status());
+
+    // The local prover proves against the sequencer's root
+    let args = pbh_args("local");
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let receipt = harness.run_and_wait(&args).await;
+    assert!(receipt.status());
+    assert!(std::fs::read_dir(&cache_dir).unwrap().next().is_some());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pbh_nonce_over_limit_is_refused() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+
+    // Refused before anything is sent, the last nonce of a run included
+    let limit = common::NUM_PBH_PER_MONTH;
+    for (pbh_nonce, count) in [(limit, 1), (limit - 1, 2), (u16::MAX, 2)] {
+        let output = run_with_pbh_nonce(&harness, pbh_nonce, count).await;
+        assert!(!output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(!stdout.contains("Transaction sent"), "{stdout}");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("numPbhPerMonth"), "{stderr}");
+    }
+}
+
+async fn run_with_pbh_nonce(harness: &Harness, pbh_nonce: u16, count: u64) -> std::process::Output {
+    let entry_point = harness.entry_point.to_string();
+    let pbh_nonce = pbh_nonce.to_string();
+    let count = count.to_string();
+    harness
+        .run(&[
+            "--use-pbh",
+            "--pbh-entry-point",
+            &entry_point,
+            "--pbh-nonce",
+            &pbh_nonce,
+            "--count",
+            &count,
+            "--iterations",
+            "10",
+            "--gas-limit",
+            "1000000",
+        ])
+        .await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pbh_user_operation() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let entry_point = harness.entry_point.to_string();
+    let smart_account = harness.smart_account.to_string();
+
+    let receipt = harness
+        .run_and_wait(&[
+            "--use-pbh",
+            "--pbh-mode",
+            "userop",
+            "--pbh-entry-point",
+            &entry_point,
+            "--entry-point",
+            &entry_point,
+            "--smart-account",
+            &smart_account,
+            "--pbh-signature-aggregator",
+            &entry_point,
+            "--workload",
+            "logs",
+            "--iterations",
+            "2",
+        ])
+        .await;
+    assert!(receipt.status());
+
+    // The smart account called GasConsumer, which logged it as the sender
+    let events = consumed_events(&receipt);
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].sender, harness.smart_account);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn mock_sequencer_serves_inclusion_proofs() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let client = reqwest::Client::new();
+
+    let commitment = WorldID::new(common::WORLD_ID).unwrap().identity.commitment();
+    let inserted: InsertResponse = client
+        .post(format!("{}/insertIdentity", harness.sequencer_url))
+        .json(&serde_json::json!({ "identityCommitment": commitment }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(inserted.index, 0);
+    assert_eq!(inserted.root, harness.sequencer.root());
+
+    let response: InclusionProofResponse = client
+        .post(format!("{}/inclusionProof", harness.sequencer_url))
+        .json(&serde_json::json!({ "identityCommitment": commitment }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(response.status, "mined");
+    assert_eq!(response.proof.root(commitment), response.root);
+
+    // The response is readable as the inclusion proof `WorldID` consumes
+    let proof: InclusionProof =
+        serde_json::from_value(serde_json::to_value(&response).unwrap()).unwrap();
+    assert_eq!(proof.root, inserted.root);
+
+    // Unknown identities have no proof
+    let missing = client
+        .post(format!("{}/inclusionProof", harness.sequencer_url))
+        .json(&serde_json::json!({ "identityCommitment": U256::from(1) }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+
+    // The mock entry point accepts the root once told about it
+    harness.accept_sequencer_root().await;
+    let valid = harness
+        .call(
+            harness.entry_point,
+            IMockPBHEntryPoint::validRootsCall {
+                _0: harness.sequencer.root(),
+            },
+        )
+        .await;
+    assert!(valid._0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn identity_subcommands() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let entry_point = harness.entry_point.to_string();
+    let stdout = |output: std::process::Output| {
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    // A new identity replaces the one in the config
+    let created = stdout(harness.run(&["identity", "new", "--write"]).await);
+    let secret = created
+        .lines()
+        .find_map(|line| line.strip_prefix("Secret: "))
+        .unwrap();
+    let world_id = WorldID::new(secret).unwrap();
+    assert_eq!(world_id.secret(), secret);
+    let config = std::fs::read_to_string(harness.config_path()).unwrap();
+    assert!(config.contains(secret));
+
+    let shown = stdout(harness.run(&["identity", "show", "--pbh-entry-point", &entry_point]).await);
+    let commitment = world_id.identity().commitment();
+    assert!(shown.contains(&format!("Commitment: {commitment}")));
+    assert!(shown.contains("PBH Quota: 30 of 30 left this month"));
+
+    // Spending PBH nonce 0 uses up one transaction of the quota
+    let receipt = harness
+        .run_and_wait(&[
+            "--use-pbh",
+            "--pbh-entry-point",
+            &entry_point,
+            "--iterations",
+            "1",
+            "--gas-limit",
+            "1000000",
+        ])
+        .await;
+    assert!(receipt.status());
+    let shown = stdout(harness.run(&["identity", "show", "--pbh-entry-point", &entry_point]).await);
+    assert!(shown.contains("PBH Quota: 29 of 30 left this month"));
+    assert!(shown.contains("Next PBH Nonce: 1"));
+
+    let sequencer_url = harness.sequencer_url.clone();
+    stdout(harness.run(&["identity", "insert", "--mock-sequencer", &sequencer_url]).await);
+    assert!(harness.sequencer.inclusion_proof(commitment).is_some());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pbh_transactions_spread_over_identities() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let second = WorldID::random();
+    let mut config = std::fs::read_to_string(harness.config_path()).unwrap();
+    config.push_str(&format!("world_ids = [\"{}\"]\n", second.secret()));
+    std::fs::write(harness.config_path(), config).unwrap();
+
+    let entry_point = harness.entry_point.to_string();
+    let output = harness
+        .run(&[
+            "--use-pbh",
+            "--pbh-entry-point",
+            &entry_point,
+            "--count",
+            "3",
+            "--iterations",
+            "1",
+            "--gas-limit",
+            "1000000",
+        ])
+        .await;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    let first = WorldID::new(common::WORLD_ID).unwrap();
+    let first_commitment = first.identity().commitment();
+    let second_commitment = second.identity().commitment();
+    assert!(stdout.contains(&format!("{first_commitment}: 2 sent, 28 of 30 left")));
+    assert!(stdout.contains(&format!("{second_commitment}: 1 sent, 29 of 30 left")));
+
+    // Every transaction spent its identity's nullifier
+    for (world_id, pbh_nonce) in [(&first, 0), (&second, 0), (&first, 1)] {
+        let (_, _, nullifier_hash) = world_id.pbh_ext_nullifier(pbh_nonce);
+        let used = harness
+            .call(
+                harness.entry_point,
+                IPBHEntryPoint::nullifierHashesCall { _0: nullifier_hash },
+            )
+            .await
+            ._0;
+        assert!(used);
+    }
+}
@@ -0,0 +1,139 @@
+use alloy_primitives::{address, b256, hex, Address, Log, U256};
+use alloy_sol_types::{SolCall, SolEvent};
+use clap::ValueEnum;
+use gas_test::bindings::GasConsumer;
+use gas_test::workload::{consume_gas_calldata, Workload};
+use std::collections::HashMap;
+
+// Selectors of the compiled contract, written by build.rs from the forge artifact
+const METHOD_IDENTIFIERS: &str = include_str!(concat!(env!("OUT_DIR"), "/GasConsumer.methods.json"));
+
+#[test]
+fn consume_gas_selector() {
+    assert_eq!(GasConsumer::consumeGasCall::SELECTOR, hex!("be9175bf"));
+}
+
+#[test]
+fn consume_gas_encoding_is_selector_and_padded_words() {
+    let target = address!("bA9FfCB4cc50eE2EC2F89740f5d6841cC8A74030");
+    let calldata = consume_gas_calldata(target, U256::from(1000));
+
+    assert_eq!(calldata.len(), 4 + 32 + 32);
+    assert_eq!(calldata[..4], hex!("be9175bf"));
+    assert_eq!(calldata[4..16], [0u8; 12]);
+    assert_eq!(calldata[16..36], *target.as_slice());
+    assert_eq!(U256::from_be_slice(&calldata[36..68]), U256::from(1000));
+
+    let decoded = GasConsumer::consumeGasCall::abi_decode(&calldata, true).unwrap();
+    assert_eq!(decoded._address, target);
+    assert_eq!(decoded._iterations, U256::from(1000));
+}
+
+#[test]
+fn workload_calldata_decodes() {
+    let calldata = Workload::Calldata.calldata(Address::ZERO, 100);
+    let decoded = GasConsumer::consumeCalldataCall::abi_decode(&calldata, true).unwrap();
+    assert_eq!(decoded._data.len(), 100);
+
+    let calldata = Workload::StorageCold.calldata(Address::ZERO, 3);
+    let decoded = GasConsumer::storeColdCall::abi_decode(&calldata, true).unwrap();
+    assert_eq!(decoded._count, U256::from(3));
+}
+
+#[test]
+fn every_workload_calls_its_function() {
+    let target = address!("bA9FfCB4cc50eE2EC2F89740f5d6841cC8A74030");
+    for workload in Workload::value_variants() {
+        let calldata = workload.calldata(target, 7);
+        let (selector, size) = match workload {
+            Workload::Keccak => {
+                let call = GasConsumer::consumeGasCall::abi_decode(&calldata, true).unwrap();
+                assert_eq!(call._address, target);
+                (GasConsumer::consumeGasCall::SELECTOR, call._iterations)
+            }
+            Workload::StorageCold => (
+                GasConsumer::storeColdCall::SELECTOR,
+                GasConsumer::storeColdCall::abi_decode(&calldata, true).unwrap()._count,
+            ),
+            Workload::StorageWarm => (
+                GasConsumer::storeWarmCall::SELECTOR,
+                GasConsumer::storeWarmCall::abi_decode(&calldata, true).unwrap()._count,
+            ),
+            Workload::Memory => (
+                GasConsumer::expandMemoryCall::SELECTOR,
+                GasConsumer::expandMemoryCall::abi_decode(&calldata, true).unwrap()._words,
+            ),
+            Workload::Calldata => {
+                let call = GasConsumer::consumeCalldataCall::abi_decode(&calldata, true).unwrap();
+                assert!(call._data.iter().all(|byte| *byte == 0xff));
+                (
+                    GasConsumer::consumeCalldataCall::SELECTOR,
+                    U256::from(call._data.len()),
+                )
+            }
+            Workload::Logs => (
+                GasConsumer::emitLogsCall::SELECTOR,
+                GasConsumer::emitLogsCall::abi_decode(&calldata, true).unwrap()._count,
+            ),
+            Workload::Ecrecover => (
+                GasConsumer::callEcrecoverCall::SELECTOR,
+                GasConsumer::callEcrecoverCall::abi_decode(&calldata, true).unwrap()._count,
+            ),
+            Workload::Sha256 => (
+                GasConsumer::callSha256Call::SELECTOR,
+                GasConsumer::callSha256Call::abi_decode(&calldata, true).unwrap()._count,
+            ),
+        };
+        assert_eq!(calldata[..4], selector, "{workload:?}");
+        assert_eq!(size, U256::from(7), "{workload:?}");
+    }
+}
+
+#[test]
+fn selectors_match_compiled_contract() {
+    let methods: HashMap<String, String> = serde_json::from_str(METHOD_IDENTIFIERS).unwrap();
+    if methods.is_empty() {
+        eprintln!("GasConsumer artifact not available, install foundry to check selectors");
+        return;
+    }
+
+    let bindings = [
+        (GasConsumer::consumeGasCall::SIGNATURE, GasConsumer::consumeGasCall::SELECTOR),
+        (GasConsumer::storeColdCall::SIGNATURE, GasConsumer::storeColdCall::SELECTOR),
+        (GasConsumer::storeWarmCall::SIGNATURE, GasConsumer::storeWarmCall::SELECTOR),
+        (GasConsumer::expandMemoryCall::SIGNATURE, GasConsumer::expandMemoryCall::SELECTOR),
+        (GasConsumer::consumeCalldataCall::SIGNATURE, GasConsumer::consumeCalldataCall::SELECTOR),
+        (GasConsumer::emitLogsCall::SIGNATURE, GasConsumer::emitLogsCall::SELECTOR),
+        (GasConsumer::callEcrecoverCall::SIGNATURE, GasConsumer::callEcrecoverCall::SELECTOR),
+        (GasConsumer::callSha256Call::SIGNATURE, GasConsumer::callSha256Call::SELECTOR),
+        (GasConsumer::nextSlotCall::SIGNATURE, GasConsumer::nextSlotCall::SELECTOR),
+        (GasConsumer::slotsCall::SIGNATURE, GasConsumer::slotsCall::SELECTOR),
+        (GasConsumer::warmSlotCall::SIGNATURE, GasConsumer::warmSlotCall::SELECTOR),
+    ];
+    assert_eq!(methods.len(), bindings.len(), "bindings are missing functions");
+
+    for (signature, selector) in bindings {
+        let compiled = methods
+            .get(signature)
+            .unwrap_or_else(|| panic!("{signature} is not in the compiled contract"));
+        assert_eq!(compiled, &hex::encode(selector), "selector mismatch for {signature}");
+    }
+}
+
+#[test]
+fn consumed_event_roundtrip() {
+    let event = GasConsumer::Consumed {
+        sender: address!("1111111111111111111111111111111111111111"),
+        index: U256::from(2),
+        data: b256!("00000000000000000000000000000000000000000000000000000000000000ff"),
+    };
+    let log = Log {
+        address: Address::ZERO,
+        data: event.encode_log_data(),
+    };
+
+    let decoded = GasConsumer::Consumed::decode_log(&log, true).unwrap();
+    assert_eq!(decoded.data.sender, event.sender);
+    assert_eq!(decoded.data.index, event.index);
+    assert_eq!(decoded.data.data, event.data);
+}
@@ -0,0 +1,149 @@
+mod common;
+
+use alloy_primitives::U256;
+use alloy_sol_types::SolValue;
+use common::Harness;
+use gas_test::client::TxType;
+use gas_test::identity::{pbh_quota, IdentityScheduler, NonceLedger, PbhQuota, QUOTA_BATCH_SIZE};
+use gas_test::transaction::single_call_multicall;
+use gas_test::workload::consume_gas_calldata;
+use gas_test::world_id::WorldID;
+
+fn ledger(limit: u16, used: Vec<u16>) -> NonceLedger {
+    NonceLedger {
+        world_id: WorldID::random(),
+        quota: PbhQuota { limit, used },
+        sent: 0,
+    }
+}
+
+#[test]
+fn quota_counts_unused_nonces() {
+    let quota = PbhQuota {
+        limit: 3,
+        used: vec![0, 2],
+    };
+    assert_eq!(quota.remaining(), 1);
+    assert_eq!(quota.next_nonce(), Some(1));
+
+    // Nonces over the limit don't count against it
+    let quota = PbhQuota {
+        limit: 3,
+        used: vec![5],
+    };
+    assert_eq!(quota.remaining(), 3);
+}
+
+#[test]
+fn scheduler_picks_identity_with_most_quota_left() {
+    let mut scheduler = IdentityScheduler::from_ledgers(vec![
+        ledger(3, vec![0]),
+        ledger(3, vec![]),
+        ledger(3, vec![1, 2]),
+    ]);
+
+    assert_eq!(scheduler.next(), Some((1, 0)));
+    // Identities 0 and 1 are tied now, the first one wins
+    assert_eq!(scheduler.next(), Some((0, 1)));
+    assert_eq!(scheduler.next(), Some((1, 1)));
+    assert_eq!(scheduler.next(), Some((0, 2)));
+    assert_eq!(scheduler.next(), Some((1, 2)));
+    assert_eq!(scheduler.next(), Some((2, 0)));
+    assert_eq!(scheduler.next(), None);
+
+    let usage = scheduler.usage();
+    assert_eq!(usage.iter().map(|usage| usage.sent).collect::<Vec<_>>(), [2, 3, 1]);
+    assert!(usage.iter().all(|usage| usage.remaining == 0));
+    assert_eq!(usage[1].commitment, scheduler.world_id(1).identity().commitment());
+}
+
+#[test]
+fn plans_use_the_scheduler_or_consecutive_nonces() {
+    let mut scheduler =
+        IdentityScheduler::from_ledgers(vec![ledger(3, vec![0]), ledger(3, vec![])]);
+    assert_eq!(scheduler.plan(3, None).unwrap(), [(1, 0), (0, 1), (1, 1)]);
+    assert!(scheduler.plan(3, None).is_err());
+
+    let mut scheduler =
+        IdentityScheduler::from_ledgers(vec![ledger(30, vec![]), ledger(30, vec![])]);
+    assert_eq!(scheduler.plan(3, Some(5)).unwrap(), [(0, 5), (0, 6), (0, 7)]);
+    assert_eq!(scheduler.usage()[0].sent, 3);
+
+    // Nothing is reserved when the last nonce doesn't fit
+    let err = scheduler.plan(2, Some(29)).unwrap_err();
+    assert!(err.to_string().contains("PBH nonce 30 is not below numPbhPerMonth (30)"), "{err}");
+    let err = scheduler.plan(2, Some(u16::MAX)).unwrap_err();
+    assert!(err.to_string().contains("PBH nonce 65535 + 1 does not fit in a u16"), "{err}");
+    assert_eq!(scheduler.usage()[0].sent, 3);
+}
+
+#[test]
+fn invalid_secrets_are_rejected() {
+    assert!(WorldID::new("").is_err());
+    assert!(WorldID::new("not base64!").is_err());
+    // 32 bytes instead of 64
+    assert!(WorldID::new("AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=").is_err());
+
+    let world_id = WorldID::random();
+    let parsed = WorldID::new(&world_id.secret()).unwrap();
+    assert_eq!(parsed.identity.commitment(), world_id.identity.commitment());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn quota_reads_spent_nullifiers() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let world_id = WorldID::new(common::WORLD_ID).unwrap();
+    let quota = pbh_quota(&harness.provider, harness.entry_point, &world_id)
+        .await
+        .unwrap();
+    assert_eq!(
+        quota,
+        PbhQuota {
+            limit: common::NUM_PBH_PER_MONTH,
+            used: vec![],
+        }
+    );
+
+    let client = harness.client().await;
+    let calls = single_call_multicall(
+        harness.gas_consumer,
+        consume_gas_calldata(harness.gas_consumer, U256::from(10)),
+    );
+    for pbh_nonce in [1, 4] {
+        let tx = client
+            .build_pbh_as(&world_id, calls.clone(), pbh_nonce)
+            .await
+            .unwrap();
+        let sent = client.send_raw(&tx, TxType::Pbh).await.unwrap();
+        assert!(harness.receipt(sent.tx_hash).await.status());
+    }
+
+    let quota = pbh_quota(&harness.provider, harness.entry_point, &world_id)
+        .await
+        .unwrap();
+    assert_eq!(quota.used, [1, 4]);
+    assert_eq!(quota.remaining(), common::NUM_PBH_PER_MONTH - 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn quota_reads_limits_past_one_batch() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let limit = QUOTA_BATCH_SIZE as u16 + 44;
+    let entry_point = harness
+        .deploy(
+            common::MOCK_PBH_ENTRY_POINT_BYTECODE,
+            (limit, harness.multicall3).abi_encode_params().into(),
+        )
+        .await;
+
+    let world_id = WorldID::new(common::WORLD_ID).unwrap();
+    let quota = pbh_quota(&harness.provider, entry_point, &world_id)
+        .await
+        .unwrap();
+    assert_eq!(quota, PbhQuota { limit, used: vec![] });
+    assert_eq!(quota.remaining(), limit);
+}
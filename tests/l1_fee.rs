@@ -0,0 +1,233 @@
+//! L1 fee estimates and reporting. The fork tests are ignored by default, run them with
+//! `OP_FORK_URL=<op stack rpc> cargo test --test l1_fee -- --ignored`.
+
+mod common;
+
+use alloy_consensus::{SignableTransaction, TxEip7702, TxEnvelope};
+use alloy_network::eip2718::Encodable2718;
+use alloy_network::TxSignerSync;
+use alloy_primitives::{address, Address, Bytes, TxHash, U256};
+use alloy_rpc_types_eth::TransactionInput;
+use alloy_signer_local::PrivateKeySigner;
+use common::Anvil;
+use gas_test::client::{FeeSettings, GasTestClient, TxType};
+use gas_test::config::Network;
+use gas_test::fastlz;
+use gas_test::l1_fee::{self, fjord_estimated_size, CostReport, FjordParams, OpReceiptFields};
+use gas_test::transaction::GasTestTransactionBuilder;
+use gas_test::workload::consume_gas_calldata;
+use std::time::Duration;
+
+#[test]
+fn op_receipt_fields_parse_from_receipt_json() {
+    // Trimmed receipt of an OP stack transaction after Ecotone
+    let receipt = serde_json::json!({
+        "transactionHash": "0x2f8a4b9d1c7e6f5a4b3c2d1e0f9a8b7c6d5e4f3a2b1c0d9e8f7a6b5c4d3e2f1a",
+        "gasUsed": "0x5208",
+        "effectiveGasPrice": "0xf4240",
+        "l1Fee": "0x1dcd6500",
+        "l1GasUsed": "0x640",
+        "l1GasPrice": "0x3b9aca00",
+        "l1BaseFeeScalar": "0x558",
+        "l1BlobBaseFee": "0x1",
+        "l1BlobBaseFeeScalar": "0xc5fc5"
+    });
+
+    let fields: OpReceiptFields = serde_json::from_value(receipt).unwrap();
+    assert_eq!(fields.l1_fee, Some(U256::from(500_000_000u64)));
+    assert_eq!(fields.l1_gas_used, Some(U256::from(1600)));
+    assert_eq!(fields.l1_base_fee_scalar, Some(U256::from(1368)));
+    assert_eq!(fields.l1_blob_base_fee_scalar, Some(U256::from(810_949)));
+}
+
+#[test]
+fn receipts_without_l1_fields_have_no_l1_cost() {
+    let fields: OpReceiptFields = serde_json::from_value(serde_json::json!({
+        "gasUsed": "0x5208",
+    }))
+    .unwrap();
+    assert_eq!(fields, OpReceiptFields::default());
+
+    let report = CostReport {
+        tx_hash: TxHash::ZERO,
+        gas_used: 21_000,
+        effective_gas_price: 1_000_000,
+        l1: fields,
+    };
+    assert_eq!(report.execution_cost(), U256::from(21_000_000_000u64));
+    assert_eq!(report.l1_cost(), U256::ZERO);
+    assert_eq!(report.total_cost(), report.execution_cost());
+}
+
+#[test]
+fn fastlz_sizes() {
+    assert_eq!(fastlz::compress_len(&[]), 0);
+    // Too short to search for matches, a single literal run
+    assert_eq!(fastlz::compress_len(&[0; 10]), 11);
+    // No repeated three byte sequence, one full literal run
+    assert_eq!(fastlz::compress_len(&(0..32).collect::<Vec<u8>>()), 33);
+    // Two literals, one long match and the five bytes the matcher never looks at
+    assert_eq!(fastlz::compress_len(&[0; 64]), 12);
+    assert_eq!(fastlz::compress_len(&[0; 1000]), 21);
+}
+
+#[test]
+fn fjord_cost_function() {
+    let params = FjordParams {
+        l1_base_fee: U256::from(1_000_000_000u64),
+        l1_blob_base_fee: U256::from(1),
+        base_fee_scalar: 1368,
+        blob_base_fee_scalar: 810_949,
+    };
+
+    // Small transactions are charged for the minimum size of 100 bytes
+    assert_eq!(fjord_estimated_size(0), U256::from(100_000_000));
+    let small = params.l1_fee(10);
+    assert_eq!(small.l1_gas_used, U256::from(1600));
+    assert_eq!(small.l1_fee, U256::from(2_188_800_081u64));
+
+    assert_eq!(fjord_estimated_size(1000), U256::from(793_914_400));
+    let large = params.l1_fee(1000);
+    assert_eq!(large.l1_gas_used, U256::from(12_702));
+    assert_eq!(large.l1_fee, U256::from(17_377_199_031u64));
+}
+
+#[tokio::test]
+async fn unsigned_encodings_drop_the_signature() {
+    for tx in sample_transactions().await {
+        let unsigned = l1_fee::unsigned_encoding(&tx).unwrap();
+        assert!(unsigned.len() < tx.encoded_2718().len());
+        assert_eq!(unsigned[0], tx.encoded_2718()[0]);
+    }
+
+    // Set code transactions aren't quoted rather than quoted with their signature
+    let signer = PrivateKeySigner::random();
+    let mut tx = TxEip7702 {
+        chain_id: common::CHAIN_ID,
+        to: address!("5FbDB2315678afecb367f032d93F642f64180aa3"),
+        ..Default::default()
+    };
+    let signature = signer.sign_transaction_sync(&mut tx).unwrap();
+    let tx: TxEnvelope = tx.into_signed(signature).into();
+    let err = l1_fee::unsigned_encoding(&tx).unwrap_err();
+    assert!(err.to_string().contains("No unsigned encoding"), "{err}");
+}
+
+/// Transactions of a few sizes and shapes, signed by a random key
+async fn sample_transactions() -> Vec<TxEnvelope> {
+    let target = address!("5FbDB2315678afecb367f032d93F642f64180aa3");
+    let calldata = [
+        Bytes::new(),
+        consume_gas_calldata(target, U256::from(100)),
+        Bytes::from(vec![0; 4000]),
+        Bytes::from((0..2000).map(|i| (i * 7919 % 251) as u8).collect::<Vec<_>>()),
+    ];
+
+    let signer = PrivateKeySigner::random();
+    let mut txs = Vec::new();
+    for (nonce, input) in calldata.into_iter().enumerate() {
+        let tx = GasTestTransactionBuilder::new(Some(10.0), Some(1.0), None)
+            .nonce(nonce as u64)
+            .to(target)
+            .input(TransactionInput::new(input))
+            .build(signer.clone())
+            .await
+            .unwrap();
+        txs.push(tx);
+    }
+    txs
+}
+
+/// Compares the offline estimate with the GasPriceOracle of a fork of an OP stack chain.
+/// Needs `OP_FORK_URL` pointing at an RPC of a chain past Fjord.
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "needs OP_FORK_URL"]
+async fn fjord_estimate_matches_gas_price_oracle() {
+    let fork_url = std::env::var("OP_FORK_URL").expect("OP_FORK_URL is not set");
+    let Some(anvil) = Anvil::spawn_with(&["--optimism", "--fork-url", &fork_url]) else {
+        return;
+    };
+    let provider = common::wait_for_rpc(&anvil.endpoint).await;
+    let params = FjordParams::from_oracle(&provider).await.unwrap();
+
+    for tx in sample_transactions().await {
+        let unsigned = l1_fee::unsigned_encoding(&tx).unwrap();
+        let oracle = l1_fee::estimate_l1_fee(&provider, &unsigned).await.unwrap();
+        assert_eq!(params.estimate_unsigned(&unsigned), oracle);
+
+        // The signed estimate compresses the real signature instead of adding 68 bytes for it
+        let signed = params.estimate_signed(&tx);
+        let signed_size = fastlz::compress_len(&tx.encoded_2718());
+        let unsigned_size = fastlz::compress_len(&unsigned) + l1_fee::SIGNATURE_OVERHEAD;
+        assert!(signed_size.abs_diff(unsigned_size) <= 8, "{signed_size} vs {unsigned_size}");
+        assert!(signed.l1_fee.abs_diff(oracle.l1_fee) <= oracle.l1_fee / U256::from(20));
+    }
+}
+
+/// Sends a transaction with L1 fees reported through a fork of an OP stack chain. The quote taken
+/// before sending is the oracle's fee of the unsigned transaction, not of the signed bytes.
+/// Needs `OP_FORK_URL` pointing at an RPC of an OP stack chain.
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "needs OP_FORK_URL"]
+async fn client_quotes_the_unsigned_transaction() {
+    let fork_url = std::env::var("OP_FORK_URL").expect("OP_FORK_URL is not set");
+    // Transactions are signed for the World Chain id, whichever chain is forked
+    let Some(anvil) = Anvil::spawn_with(&[
+        "--optimism",
+        "--fork-url",
+        &fork_url,
+        "--chain-id",
+        &common::CHAIN_ID.to_string(),
+    ]) else {
+        return;
+    };
+    let provider = common::wait_for_rpc(&anvil.endpoint).await;
+    let target = address!("5FbDB2315678afecb367f032d93F642f64180aa3");
+    let network = Network {
+        name: None,
+        rpc_address: anvil.endpoint.clone(),
+        contract_address: target,
+        pbh_entry_point: Address::ZERO,
+    };
+    let fees = FeeSettings {
+        gas_fee: Some(10.0),
+        priority_gas_fee: Some(1.0),
+        gas_limit: Some(1_000_000),
+    };
+    let signer: PrivateKeySigner = common::DEV_PRIVATE_KEY.parse().unwrap();
+    let client = GasTestClient::connect(network, signer, None, fees)
+        .await
+        .unwrap()
+        .with_l1_fees(true);
+
+    let tx = client
+        .build_direct(consume_gas_calldata(target, U256::from(100)))
+        .await
+        .unwrap();
+    let sent = client.send_raw(&tx, TxType::Direct).await.unwrap();
+    let quoted = sent.l1_fee_estimate.unwrap();
+
+    let unsigned = l1_fee::unsigned_encoding(&tx).unwrap();
+    assert_eq!(quoted, l1_fee::estimate_l1_fee(&provider, &unsigned).await.unwrap());
+    // Quoting the signed bytes would count the signature twice
+    let signed = l1_fee::estimate_l1_fee(&provider, &tx.encoded_2718().into())
+        .await
+        .unwrap();
+    assert!(quoted.l1_gas_used < signed.l1_gas_used);
+    assert!(quoted.l1_fee < signed.l1_fee);
+
+    let receipt = client
+        .wait_for_receipt(sent.tx_hash, Duration::from_secs(30))
+        .await
+        .unwrap();
+    let report = client.cost_report(&receipt).await.unwrap();
+    assert_eq!(report.tx_hash, sent.tx_hash);
+    assert_eq!(
+        report.execution_cost(),
+        U256::from(receipt.gas_used) * U256::from(receipt.effective_gas_price)
+    );
+    // Nodes that charge the L1 fee in their receipts charge what was quoted
+    if let Some(l1_fee) = report.l1.l1_fee {
+        assert_eq!(l1_fee, quoted.l1_fee);
+    }
+}
@@ -0,0 +1,145 @@
+mod common;
+
+use alloy_provider::Provider;
+use common::Harness;
+use gas_test::pbh_fuzz::{FuzzOutcome, FuzzResult, PbhFault};
+use gas_test::world_id::WorldID;
+
+#[test]
+fn results_pass_on_the_expected_reason_only() {
+    let result = |fault, outcome| FuzzResult { fault, outcome };
+
+    assert!(result(PbhFault::UnknownRoot, FuzzOutcome::Reverted("InvalidRoot".into())).passed());
+    // Builder errors are matched regardless of case and spacing
+    assert!(result(
+        PbhFault::ReusedNullifier,
+        FuzzOutcome::Rejected("Duplicate nullifier hash".into())
+    )
+    .passed());
+    assert!(!result(
+        PbhFault::WrongMonth,
+        FuzzOutcome::Reverted("InvalidPbhNonce".into())
+    )
+    .passed());
+    assert!(!result(PbhFault::CorruptedProof, FuzzOutcome::Accepted).passed());
+}
+
+/// Runs `pbh-fuzz` against the mock entry point with the local prover, so roots are checked
+async fn run_fuzz(harness: &Harness, faults: &[&str], flags: &[&str]) -> std::process::Output {
+    let entry_point = harness.entry_point.to_string();
+    let cache_dir = harness.dir.join("inclusion-proofs").display().to_string();
+    let mut args = vec![
+        "--pbh-entry-point",
+        &entry_point,
+        "--prover",
+        "local",
+        "--sequencer-url",
+        &harness.sequencer_url,
+        "--proof-cache-dir",
+        &cache_dir,
+        "--gas-limit",
+        "1000000",
+        "pbh-fuzz",
+    ];
+    for fault in faults {
+        args.extend(["--fault", fault]);
+    }
+    args.extend(flags);
+    harness.run(&args).await
+}
+
+async fn start() -> Option<Harness> {
+    let harness = Harness::start().await?;
+    let commitment = WorldID::new(common::WORLD_ID).unwrap().identity.commitment();
+    harness.sequencer.insert(commitment).unwrap();
+    harness.accept_sequencer_root().await;
+    let _ = std::fs::remove_dir_all(harness.dir.join("inclusion-proofs"));
+    Some(harness)
+}
+
+/// Faults the mock entry point catches with proofs of the local prover, which it doesn't verify
+const ENTRY_POINT_FAULTS: [&str; 4] = ["unknown-root", "reused-nullifier", "wrong-month", "nonce-over-limit"];
+
+#[tokio::test(flavor = "multi_thread")]
+async fn faulty_payloads_are_rejected() {
+    let Some(harness) = start().await else {
+        return;
+    };
+
+    for flags in [&["--simulate", "--spend-nullifier"][..], &[]] {
+        let output = run_fuzz(&harness, &ENTRY_POINT_FAULTS, flags).await;
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert_eq!(stdout.lines().filter(|line| line.ends_with("PASS")).count(), 4);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn mock_proofs_are_checked_against_the_signal() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+
+    // Mock proofs carry their signal hash, so the mock entry point catches proof faults
+    let entry_point = harness.entry_point.to_string();
+    let output = harness
+        .run(&[
+            "--pbh-entry-point",
+            &entry_point,
+            "--gas-limit",
+            "1000000",
+            "pbh-fuzz",
+            "--fault",
+            "corrupted-proof",
+            "--fault",
+            "wrong-sender",
+            "--simulate",
+        ])
+        .await;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.lines().filter(|line| line.ends_with("PASS")).count(), 2, "{stdout}");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn uncaught_faults_fail_the_run() {
+    let Some(harness) = start().await else {
+        return;
+    };
+
+    let output = run_fuzz(&harness, &["corrupted-proof"], &["--simulate"]).await;
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.lines().any(|line| line.starts_with("corrupted-proof") && line.ends_with("FAIL")));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn simulating_a_reused_nullifier_needs_a_spent_one() {
+    let Some(harness) = start().await else {
+        return;
+    };
+    let nonce = harness
+        .provider
+        .get_transaction_count(harness.signer.address())
+        .await
+        .unwrap();
+
+    // Nothing is spent yet, and a simulation doesn't send the PBH transaction it would need
+    let output = run_fuzz(&harness, &["reused-nullifier"], &["--simulate"]).await;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--spend-nullifier"), "{stderr}");
+    assert_eq!(
+        harness
+            .provider
+            .get_transaction_count(harness.signer.address())
+            .await
+            .unwrap(),
+        nonce
+    );
+
+    let output = run_fuzz(&harness, &["reused-nullifier"], &["--simulate", "--spend-nullifier"]).await;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.lines().any(|line| line.starts_with("reused-nullifier") && line.ends_with("PASS")));
+}
@@ -0,0 +1,279 @@
+mod common;
+
+use alloy_primitives::{Address, U256};
+use alloy_provider::Provider;
+use async_trait::async_trait;
+use common::Harness;
+use eyre::Result;
+use gas_test::proof::{
+    LocalProver, ProofPool, ProofProvider, RandomProofProvider, BASE_FIELD_MODULUS,
+    SCALAR_FIELD_MODULUS,
+};
+use gas_test::proof_cache::{InclusionProofCache, WorldIdRoots};
+use gas_test::rpc;
+use gas_test::sequencer::{MockSequencer, DEFAULT_TREE_DEPTH};
+use gas_test::world_id::InclusionProof;
+use gas_test::world_id::WorldID;
+use semaphore_rs::identity::Identity;
+use semaphore_rs::protocol::Proof;
+use semaphore_rs::Field;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+const SEQUENCER_URL: &str = "http://127.0.0.1:8080";
+
+/// Provider that counts its calls and puts the signal hash into the root
+#[derive(Default)]
+struct CountingProvider {
+    calls: AtomicUsize,
+}
+
+#[async_trait]
+impl ProofProvider for CountingProvider {
+    async fn prove(
+        &self,
+        _identity: &Identity,
+        signal_hash: Field,
+        _external_nullifier_hash: Field,
+    ) -> Result<(Proof, Field)> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Ok((Proof::from_flat([U256::ZERO; 8]), signal_hash))
+    }
+}
+
+#[tokio::test]
+async fn pool_hands_out_prefetched_proofs() {
+    let world_id = WorldID::new(common::WORLD_ID).unwrap();
+    let provider = Arc::new(CountingProvider::default());
+    let pool = ProofPool::new(provider.clone());
+
+    for pbh_nonce in 0..3 {
+        pool.prefetch(&world_id, pbh_nonce, U256::from(pbh_nonce))
+            .unwrap();
+    }
+    // Prefetching the same payload twice proves it once
+    pool.prefetch(&world_id, 0, U256::ZERO).unwrap();
+    assert_eq!(pool.len(), 3);
+
+    let payload = world_id.pbh_payload(&pool, 1, U256::from(1)).await.unwrap();
+    assert_eq!(payload.root, U256::from(1));
+    assert_eq!(pool.len(), 2);
+
+    for pbh_nonce in [0, 2] {
+        world_id
+            .pbh_payload(&pool, pbh_nonce, U256::from(pbh_nonce))
+            .await
+            .unwrap();
+    }
+    assert!(pool.is_empty());
+    assert_eq!(provider.calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn pool_falls_back_to_provider() {
+    let world_id = WorldID::new(common::WORLD_ID).unwrap();
+    let provider = Arc::new(CountingProvider::default());
+    let pool = ProofPool::new(provider.clone());
+
+    pool.prefetch(&world_id, 0, U256::from(1)).unwrap();
+
+    // A different signal was prefetched, so this one is proven on demand
+    let payload = world_id.pbh_payload(&pool, 0, U256::from(2)).await.unwrap();
+    assert_eq!(payload.root, U256::from(2));
+    assert_eq!(pool.len(), 1);
+
+    world_id.pbh_payload(&pool, 0, U256::from(1)).await.unwrap();
+    assert!(pool.is_empty());
+    assert_eq!(provider.calls.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn prefetching_needs_a_runtime() {
+    let world_id = WorldID::new(common::WORLD_ID).unwrap();
+    let pool = ProofPool::new(Arc::new(CountingProvider::default()));
+    assert!(pool.prefetch(&world_id, 0, U256::ZERO).is_err());
+    assert!(pool.is_empty());
+}
+
+#[tokio::test]
+async fn random_proofs_are_full_field_elements() {
+    let identity = WorldID::new(common::WORLD_ID).unwrap().identity().clone();
+    let (proof, root) = RandomProofProvider
+        .prove(&identity, U256::from(1), U256::from(2))
+        .await
+        .unwrap();
+    let (other, _) = RandomProofProvider
+        .prove(&identity, U256::from(1), U256::from(2))
+        .await
+        .unwrap();
+    assert_ne!(proof.flatten(), other.flatten());
+
+    // No coordinate is left short, so the proof compresses like a real one
+    for coordinate in proof.flatten() {
+        assert!(coordinate < BASE_FIELD_MODULUS);
+        assert!(coordinate.bit_len() > 200, "{coordinate}");
+    }
+    assert!(root < SCALAR_FIELD_MODULUS);
+}
+
+fn inclusion_proof(commitment: Field) -> InclusionProof {
+    let sequencer = MockSequencer::new(DEFAULT_TREE_DEPTH).unwrap();
+    sequencer.insert(commitment).unwrap();
+    let response = sequencer.inclusion_proof(commitment).unwrap();
+    InclusionProof {
+        root: response.root,
+        proof: response.proof,
+    }
+}
+
+#[test]
+fn unsupported_depths_are_rejected() {
+    for depth in [4, 64, 1000] {
+        assert!(MockSequencer::new(depth).is_err());
+    }
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_gas-test"))
+        .args(["mock-sequencer", "--depth", "64"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Unsupported tree depth 64"), "{stderr}");
+}
+
+fn cache_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("gas-test-proof-cache-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn cache_returns_stored_proofs() {
+    let commitment = WorldID::new(common::WORLD_ID).unwrap().identity.commitment();
+    let proof = inclusion_proof(commitment);
+    let cache = InclusionProofCache::new(cache_dir("stored"));
+
+    assert!(cache.get(SEQUENCER_URL, commitment).is_none());
+    cache.put(SEQUENCER_URL, commitment, &proof).unwrap();
+
+    let cached = cache.get(SEQUENCER_URL, commitment).unwrap();
+    assert_eq!(cached.root(), proof.root);
+    assert_eq!(cached.sequencer_url, SEQUENCER_URL);
+    assert_eq!(cached.inclusion_proof.proof.root(commitment), proof.root);
+
+    // Another identity has no entry
+    assert!(cache.get(SEQUENCER_URL, U256::from(1)).is_none());
+
+    cache.remove(SEQUENCER_URL, commitment).unwrap();
+    assert!(cache.get(SEQUENCER_URL, commitment).is_none());
+    // Removing a missing entry is fine
+    cache.remove(SEQUENCER_URL, commitment).unwrap();
+}
+
+#[test]
+fn cache_is_per_sequencer() {
+    let commitment = WorldID::new(common::WORLD_ID).unwrap().identity.commitment();
+    let dir = cache_dir("sequencer");
+    let cache = InclusionProofCache::new(&dir);
+    cache
+        .put(SEQUENCER_URL, commitment, &inclusion_proof(commitment))
+        .unwrap();
+
+    assert!(cache.get("http://127.0.0.1:9090", commitment).is_none());
+    // A trailing slash is the same sequencer
+    assert!(cache
+        .get(&format!("{SEQUENCER_URL}/"), commitment)
+        .is_some());
+
+    // Entries recorded for another sequencer are rejected
+    let path = std::fs::read_dir(&dir)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    let contents = std::fs::read_to_string(&path).unwrap();
+    std::fs::write(
+        &path,
+        contents.replace(SEQUENCER_URL, "http://127.0.0.1:9090"),
+    )
+    .unwrap();
+    assert!(cache.get(SEQUENCER_URL, commitment).is_none());
+}
+
+#[tokio::test]
+async fn failed_root_checks_keep_the_cached_proof() {
+    let commitment = WorldID::new(common::WORLD_ID).unwrap().identity.commitment();
+    let cache = InclusionProofCache::new(cache_dir("unreachable"));
+    cache
+        .put(SEQUENCER_URL, commitment, &inclusion_proof(commitment))
+        .unwrap();
+
+    // Nothing listens on either port, so neither the root nor a new proof can be fetched
+    let provider = rpc::connect("http://127.0.0.1:1").await.unwrap();
+    let roots = WorldIdRoots::new(provider, Address::ZERO);
+    let prover = LocalProver::new(SEQUENCER_URL).with_cache(cache.clone(), roots);
+    assert!(prover.cached_inclusion_proof(commitment).await.is_err());
+    assert!(cache.get(SEQUENCER_URL, commitment).is_some());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn cached_roots_are_checked_with_world_id() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let commitment = WorldID::new(common::WORLD_ID)
+        .unwrap()
+        .identity
+        .commitment();
+    harness.sequencer.insert(commitment).unwrap();
+    harness.accept_sequencer_root().await;
+    let first_root = harness.sequencer.root();
+
+    let cache = InclusionProofCache::new(harness.dir.join("root-checks"));
+    let roots = WorldIdRoots::new(harness.provider.clone(), harness.entry_point);
+    let prover = LocalProver::new(harness.sequencer_url.clone()).with_cache(cache, roots.clone());
+    assert_eq!(
+        prover
+            .cached_inclusion_proof(commitment)
+            .await
+            .unwrap()
+            .root,
+        first_root
+    );
+
+    // A replaced root is still accepted for a while, so the cached proof is kept
+    harness.sequencer.insert(U256::from(1)).unwrap();
+    harness.accept_sequencer_root().await;
+    assert_eq!(roots.is_valid(first_root).await.unwrap(), Some(true));
+    assert_eq!(
+        prover
+            .cached_inclusion_proof(commitment)
+            .await
+            .unwrap()
+            .root,
+        first_root
+    );
+
+    // Once the World ID contract expires it, the proof is fetched again
+    let expiry = 60 * 60 + 1;
+    let _: serde_json::Value = harness
+        .provider
+        .raw_request("evm_increaseTime".into(), (expiry,))
+        .await
+        .unwrap();
+    let _: serde_json::Value = harness
+        .provider
+        .raw_request("evm_mine".into(), ())
+        .await
+        .unwrap();
+    assert_eq!(roots.is_valid(first_root).await.unwrap(), Some(false));
+    assert_eq!(
+        prover
+            .cached_inclusion_proof(commitment)
+            .await
+            .unwrap()
+            .root,
+        harness.sequencer.root()
+    );
+}
@@ -0,0 +1,297 @@
+mod common;
+
+use alloy_primitives::TxHash;
+use chrono::{TimeZone, Utc};
+use common::Harness;
+use gas_test::client::TxType;
+use gas_test::results::{self, GroupBy, ResultsDb, ResultsFilter, Run, TxRecord};
+use gas_test::workload::Workload;
+use std::time::Duration;
+
+fn open_db(name: &str) -> ResultsDb {
+    let path = std::env::temp_dir().join(format!(
+        "gas-test-results-{}-{}.db",
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    ResultsDb::open(&path).unwrap()
+}
+
+fn run(network: &str, iterations: u64) -> Run {
+    Run {
+        started_at: Utc.with_ymd_and_hms(2026, 10, 1, 12, 0, 0).unwrap(),
+        network: network.into(),
+        workload: Some(Workload::Keccak),
+        iterations: Some(iterations),
+    }
+}
+
+fn record(nonce: u64, tx_type: TxType, gas_used: Option<u64>) -> TxRecord {
+    TxRecord {
+        run_id: 0,
+        tx_hash: TxHash::with_last_byte(nonce as u8),
+        tx_type,
+        nonce,
+        retries: 0,
+        sent_at: Utc
+            .with_ymd_and_hms(2026, 10, 1, 12, 0, nonce as u32)
+            .unwrap(),
+        max_fee_per_gas: Some(10_000_000_000),
+        max_priority_fee_per_gas: Some(1_000_000_000),
+        gas_limit: Some(1_000_000),
+        status: gas_used.map(|_| true),
+        block_number: gas_used.map(|_| 100 + nonce),
+        gas_used,
+        effective_gas_price: gas_used.map(|_| 2_000_000_000),
+        l1_fee: None,
+        l1_gas_used: None,
+        blocks_waited: gas_used.map(|_| 1),
+        latency: gas_used.map(|_| Duration::from_millis(500 + nonce)),
+    }
+}
+
+#[test]
+fn runs_are_recorded_and_aggregated() {
+    let mut db = open_db("aggregate");
+    let first = db
+        .record(
+            &run("local", 10),
+            &[
+                record(0, TxType::Direct, Some(30_000)),
+                record(1, TxType::Pbh, Some(90_000)),
+            ],
+        )
+        .unwrap();
+    let second = db
+        .record(
+            &run("devnet", 100),
+            &[
+                record(2, TxType::Pbh, Some(120_000)),
+                record(3, TxType::Pbh, None),
+            ],
+        )
+        .unwrap();
+    assert_eq!((first, second), (1, 2));
+    assert_eq!(db.run(second).unwrap().unwrap(), run("devnet", 100));
+    assert!(db.run(3).unwrap().is_none());
+
+    // Records read back as written, with their run
+    let stored = db.transactions(&ResultsFilter::default()).unwrap();
+    assert_eq!(stored.len(), 4);
+    assert_eq!(
+        stored[1],
+        TxRecord {
+            run_id: first,
+            ..record(1, TxType::Pbh, Some(90_000))
+        }
+    );
+    assert_eq!(stored[3].status, None);
+
+    let by_run = db
+        .aggregate(&ResultsFilter::default(), GroupBy::Run)
+        .unwrap();
+    assert_eq!(by_run.len(), 2);
+    assert_eq!(by_run[1].key, "2");
+    assert_eq!(
+        (
+            by_run[1].transactions,
+            by_run[1].included,
+            by_run[1].succeeded
+        ),
+        (2, 1, 1)
+    );
+    assert_eq!(by_run[1].avg_gas_used, Some(120_000.0));
+
+    let pbh = ResultsFilter {
+        tx_type: Some(TxType::Pbh),
+        ..ResultsFilter::default()
+    };
+    let by_network = db.aggregate(&pbh, GroupBy::Network).unwrap();
+    let keys: Vec<&str> = by_network.iter().map(|a| a.key.as_str()).collect();
+    assert_eq!(keys, ["local", "devnet"]);
+    assert_eq!(by_network[0].min_gas_used, Some(90_000));
+
+    let filter = ResultsFilter {
+        network: Some("devnet".into()),
+        since: Some(Utc.with_ymd_and_hms(2026, 10, 1, 12, 0, 3).unwrap()),
+        ..ResultsFilter::default()
+    };
+    let late = db.transactions(&filter).unwrap();
+    assert_eq!(late.len(), 1);
+    assert_eq!(late[0].nonce, 3);
+
+    let table = results::query_table(&by_run, GroupBy::Run);
+    assert!(table.starts_with("Run"), "{table}");
+    assert_eq!(table.lines().count(), 3);
+}
+
+#[test]
+fn runs_are_compared() {
+    let mut db = open_db("diff");
+    let a = db
+        .record(
+            &run("local", 10),
+            &[record(0, TxType::Direct, Some(40_000))],
+        )
+        .unwrap();
+    let b = db
+        .record(
+            &run("local", 10),
+            &[record(1, TxType::Direct, Some(50_000))],
+        )
+        .unwrap();
+
+    let diffs = results::diff(&db.run_aggregate(a).unwrap(), &db.run_aggregate(b).unwrap());
+    let gas = diffs
+        .iter()
+        .find(|diff| diff.metric == "Avg Gas Used")
+        .unwrap();
+    assert_eq!(gas.delta(), Some(10_000.0));
+    assert_eq!(gas.percent(), Some(25.0));
+    let l1_fee = diffs
+        .iter()
+        .find(|diff| diff.metric == "Avg L1 Fee (wei)")
+        .unwrap();
+    assert_eq!(l1_fee.delta(), None);
+
+    let table = results::diff_table("Run 1", "Run 2", &diffs);
+    let row = table
+        .lines()
+        .find(|line| line.starts_with("Avg Gas Used"))
+        .unwrap();
+    assert!(
+        row.contains("+10000.0") && row.ends_with("+25.00%"),
+        "{table}"
+    );
+
+    assert!(db.run_aggregate(3).is_err());
+}
+
+#[test]
+fn wei_amounts_beyond_i64_are_kept() {
+    let mut db = open_db("wei");
+    let expensive = TxRecord {
+        max_fee_per_gas: Some(u128::MAX),
+        effective_gas_price: Some(20_000_000_000_000_000_000),
+        l1_fee: Some(30_000_000_000_000_000_000),
+        ..record(0, TxType::Direct, Some(21_000))
+    };
+    let run_id = db.record(&run("local", 10), &[expensive.clone()]).unwrap();
+
+    let stored = db.transactions(&ResultsFilter::default()).unwrap();
+    assert_eq!(
+        stored,
+        [TxRecord {
+            run_id,
+            ..expensive
+        }]
+    );
+    let aggregate = db.run_aggregate(run_id).unwrap();
+    assert_eq!(aggregate.avg_gas_price, Some(2e19));
+    assert_eq!(aggregate.avg_l1_fee, Some(3e19));
+}
+
+#[test]
+fn transactions_are_recorded_once_per_run() {
+    let mut db = open_db("duplicates");
+    // A transaction the node already knew is reported as sent again
+    let first = record(0, TxType::Direct, None);
+    let again = TxRecord {
+        retries: 1,
+        ..first.clone()
+    };
+    let run_id = db
+        .record(&run("local", 10), &[first.clone(), again])
+        .unwrap();
+    let stored = db.transactions(&ResultsFilter::default()).unwrap();
+    assert_eq!(
+        stored,
+        [TxRecord {
+            run_id,
+            ..first.clone()
+        }]
+    );
+
+    // Another run may send the same transaction
+    db.record(&run("local", 10), &[first]).unwrap();
+    assert_eq!(db.transactions(&ResultsFilter::default()).unwrap().len(), 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn sent_transactions_are_recorded() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+
+    for iterations in ["10", "100"] {
+        let output = harness.run(&["--iterations", iterations]).await;
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("Recorded run"), "{stdout}");
+    }
+    let output = harness.run(&["--iterations", "10", "--no-results"]).await;
+    assert!(output.status.success());
+
+    let db = ResultsDb::open(&harness.results_db()).unwrap();
+    let stored = db.transactions(&ResultsFilter::default()).unwrap();
+    assert_eq!(stored.len(), 2);
+    assert!(stored.iter().all(|tx| tx.status == Some(true)));
+    assert_eq!(stored[0].max_fee_per_gas, Some(10_000_000_000));
+
+    let output = harness
+        .run(&[
+            "results",
+            "query",
+            "--workload",
+            "keccak",
+            "--iterations",
+            "100",
+        ])
+        .await;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.lines().count(), 2, "{stdout}");
+    assert!(stdout.lines().nth(1).unwrap().starts_with("2 "), "{stdout}");
+
+    let output = harness.run(&["results", "diff", "1", "2"]).await;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let row = stdout
+        .lines()
+        .find(|line| line.starts_with("Avg Gas Used"))
+        .unwrap();
+    assert!(row.contains('+') && !row.contains("+0.0 "), "{stdout}");
+
+    assert!(!harness
+        .run(&["results", "diff", "1", "9"])
+        .await
+        .status
+        .success());
+
+    // Sends outside of a run are recorded too, as a run of their own
+    let output = harness.run(&["deploy"]).await;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Recorded run 3"), "{stdout}");
+    let deployment = db
+        .transactions(&ResultsFilter {
+            run_id: Some(3),
+            ..ResultsFilter::default()
+        })
+        .unwrap();
+    assert_eq!(deployment.len(), 1);
+    assert_eq!(deployment[0].status, Some(true));
+    assert_eq!(db.run(3).unwrap().unwrap().workload, None);
+
+    // A database that can't be opened only costs the record, not the send
+    drop(db);
+    let path = harness.results_db();
+    std::fs::remove_file(&path).unwrap();
+    std::fs::create_dir(&path).unwrap();
+    let output = harness.run(&["--iterations", "10"]).await;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Failed to record results"), "{stdout}");
+    assert!(!stdout.contains("Recorded run"), "{stdout}");
+}
@@ -0,0 +1,287 @@
+mod common;
+
+use alloy_provider::Provider;
+use common::{Harness, NUM_PBH_PER_MONTH};
+use gas_test::scenario::{Scenario, ScenarioReport, Step, StepResult};
+use gas_test::workload::Workload;
+
+fn parse(toml: &str) -> eyre::Result<Scenario> {
+    let scenario: Scenario = toml::from_str(toml)?;
+    scenario.validate()?;
+    Ok(scenario)
+}
+
+#[test]
+fn scenarios_parse() {
+    let scenario = parse(
+        r#"
+name = "direct"
+
+[[steps]]
+action = "deploy"
+
+[[steps]]
+action = "send-direct"
+label = "direct"
+workload = "storage-cold"
+iterations = 3
+
+[[steps]]
+action = "assert-gas"
+label = "direct"
+max = 200000
+"#,
+    )
+    .unwrap();
+
+    assert_eq!(scenario.name.as_deref(), Some("direct"));
+    assert_eq!(scenario.steps[0], Step::Deploy { salt: None });
+    assert_eq!(
+        scenario.steps[1],
+        Step::SendDirect {
+            label: "direct".into(),
+            count: 1,
+            workload: Workload::StorageCold,
+            iterations: 3,
+        }
+    );
+    assert!(!scenario.sends_pbh());
+}
+
+#[test]
+fn invalid_scenarios_are_refused() {
+    // Typos in assertions would otherwise make them pass silently
+    assert!(parse("[[steps]]\naction = \"assert-gas\"\nlabel = \"a\"\nmaximum = 1\n").is_err());
+    assert!(parse("[[steps]]\naction = \"explode\"\n").is_err());
+
+    // Assertions need an earlier send with the label
+    let err = parse(
+        "[[steps]]\naction = \"assert-status\"\nlabel = \"pbh\"\nsuccess = true\n\n\
+         [[steps]]\naction = \"send-pbh\"\nlabel = \"pbh\"\niterations = 1\n",
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("Step 1"), "{err}");
+
+    let err = parse(
+        "[[steps]]\naction = \"send-direct\"\nlabel = \"a\"\niterations = 1\n\n\
+         [[steps]]\naction = \"assert-gas\"\nlabel = \"a\"\n",
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("min, max"), "{err}");
+}
+
+#[test]
+fn reports_count_assertions() {
+    let report = ScenarioReport {
+        results: vec![
+            StepResult::Sent(Vec::new()),
+            StepResult::Asserted { failures: Vec::new() },
+            StepResult::Asserted {
+                failures: vec!["gas used 90000 is above the maximum".into()],
+            },
+            StepResult::Waited { block_number: 7 },
+            StepResult::Asserted { failures: Vec::new() },
+        ],
+    };
+    assert_eq!(report.results[0].passed(), None);
+    assert_eq!(report.passed(), 2);
+    assert_eq!(report.failed(), 1);
+}
+
+/// Runs `scenario` against the harness and returns whether it passed, with its output
+async fn run_scenario(harness: &Harness, name: &str, scenario: &str) -> (bool, String) {
+    let path = harness.dir.join(format!("{name}.toml"));
+    std::fs::write(&path, scenario).unwrap();
+    let entry_point = harness.entry_point.to_string();
+    let output = harness
+        .run(&[
+            "--pbh-entry-point",
+            &entry_point,
+            "--gas-limit",
+            "1000000",
+            "run",
+            &path.display().to_string(),
+        ])
+        .await;
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    (output.status.success(), stdout)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn scenario_steps_run_in_order() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+
+    let scenario = r#"
+name = "pbh then direct"
+
+[[steps]]
+action = "deploy"
+
+[[steps]]
+action = "fund"
+accounts = ["0x000000000000000000000000000000000000dEaD"]
+amount = "1000000000000000"
+
+[[steps]]
+action = "send-pbh"
+label = "pbh"
+iterations = 10
+pbh_nonce = 1
+
+[[steps]]
+action = "send-direct"
+label = "direct"
+count = 2
+iterations = 10
+
+[[steps]]
+action = "send-pbh"
+label = "reused"
+iterations = 10
+pbh_nonce = 1
+
+[[steps]]
+action = "assert-status"
+label = "direct"
+success = true
+
+[[steps]]
+action = "assert-gas"
+label = "pbh"
+min = 21000
+max = 1000000
+
+[[steps]]
+action = "assert-order"
+first = "pbh"
+then = "direct"
+
+[[steps]]
+action = "assert-revert"
+label = "reused"
+reason = "InvalidNullifier"
+"#;
+    let (passed, stdout) = run_scenario(&harness, "pass", scenario).await;
+    assert!(passed, "{stdout}");
+    assert!(
+        stdout.contains("Assertions: 4 passed, 0 failed"),
+        "{stdout}"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn failed_assertions_fail_the_run() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+
+    let scenario = r#"
+[[steps]]
+action = "send-direct"
+label = "direct"
+iterations = 10
+
+[[steps]]
+action = "assert-gas"
+label = "direct"
+max = 21000
+
+[[steps]]
+action = "assert-status"
+label = "direct"
+success = true
+"#;
+    let (passed, stdout) = run_scenario(&harness, "fail", scenario).await;
+    assert!(!passed);
+    assert!(stdout.contains("[2] assert-gas: FAIL"), "{stdout}");
+    assert!(stdout.contains("[3] assert-status: PASS"), "{stdout}");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pbh_nonces_past_u16_fail_the_step() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let account_nonce = harness
+        .provider
+        .get_transaction_count(harness.signer.address())
+        .await
+        .unwrap();
+
+    let scenario = r#"
+[[steps]]
+action = "send-pbh"
+label = "overflow"
+count = 2
+iterations = 10
+pbh_nonce = 65535
+"#;
+    let path = harness.dir.join("overflow.toml");
+    std::fs::write(&path, scenario).unwrap();
+    let entry_point = harness.entry_point.to_string();
+    let output = harness
+        .run(&["--pbh-entry-point", &entry_point, "run", &path.display().to_string()])
+        .await;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("PBH nonce 65535 + 1 does not fit in a u16"), "{stderr}");
+    // Nothing of the step was sent
+    assert_eq!(
+        harness
+            .provider
+            .get_transaction_count(harness.signer.address())
+            .await
+            .unwrap(),
+        account_nonce
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pbh_nonces_past_the_monthly_limit_fail_the_step() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let account_nonce = harness
+        .provider
+        .get_transaction_count(harness.signer.address())
+        .await
+        .unwrap();
+
+    // The last nonce of the step is NUM_PBH_PER_MONTH, one past the limit
+    let scenario = format!(
+        r#"
+[[steps]]
+action = "send-pbh"
+label = "over-limit"
+count = 2
+iterations = 10
+pbh_nonce = {}
+"#,
+        NUM_PBH_PER_MONTH - 1
+    );
+    let path = harness.dir.join("over-limit.toml");
+    std::fs::write(&path, scenario).unwrap();
+    let entry_point = harness.entry_point.to_string();
+    let output = harness
+        .run(&["--pbh-entry-point", &entry_point, "run", &path.display().to_string()])
+        .await;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(&format!(
+            "PBH nonce {NUM_PBH_PER_MONTH} is not below numPbhPerMonth ({NUM_PBH_PER_MONTH})"
+        )),
+        "{stderr}"
+    );
+    // Nothing of the step was sent
+    assert_eq!(
+        harness
+            .provider
+            .get_transaction_count(harness.signer.address())
+            .await
+            .unwrap(),
+        account_nonce
+    );
+}
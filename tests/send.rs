@@ -0,0 +1,131 @@
+mod common;
+
+use alloy_primitives::{address, U256};
+use common::{sent_tx_hash, ConditionalBuilder, Harness, WORLD_ID};
+use gas_test::abi::{encode_call, parse_function};
+use gas_test::bindings::GasConsumer;
+use gas_test::workload::consume_gas_calldata;
+use std::time::Duration;
+
+#[test]
+fn signature_encoding_matches_the_bindings() {
+    let target = address!("bA9FfCB4cc50eE2EC2F89740f5d6841cC8A74030");
+    let function = parse_function("consumeGas(address,uint256)").unwrap();
+    let calldata = encode_call(&function, &[target.to_string(), "1000".to_string()]).unwrap();
+    assert_eq!(calldata, consume_gas_calldata(target, U256::from(1000)));
+
+    let function = parse_function("consumeCalldata(bytes)").unwrap();
+    assert!(encode_call(&function, &["0x0102".to_string()]).is_ok());
+    assert!(encode_call(&function, &["not hex".to_string()]).is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn any_function_can_be_called() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let gas_consumer = harness.gas_consumer.to_string();
+
+    let output = harness
+        .run(&["send", "--to", &gas_consumer, "--sig", "storeCold(uint256)", "--args", "3"])
+        .await;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let receipt = harness.receipt(sent_tx_hash(&stdout)).await;
+    assert!(receipt.status());
+    let next_slot = harness
+        .call(harness.gas_consumer, GasConsumer::nextSlotCall {})
+        .await;
+    assert_eq!(next_slot._0, U256::from(3));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn simulation_decodes_return_values() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let gas_consumer = harness.gas_consumer.to_string();
+
+    let output = harness
+        .run(&[
+            "send",
+            "--to",
+            &gas_consumer,
+            "--sig",
+            "expandMemory(uint256) returns (uint256)",
+            "--args",
+            "4",
+            "--simulate",
+        ])
+        .await;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Simulation: succeeded, returned ("));
+    assert!(!stdout.contains("Transaction sent"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reverting_calls_are_not_sent() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let gas_consumer = harness.gas_consumer.to_string();
+
+    // storeCold isn't payable
+    let output = harness
+        .run(&[
+            "send",
+            "--to",
+            &gas_consumer,
+            "--sig",
+            "storeCold(uint256)",
+            "--args",
+            "1",
+            "--value",
+            "1",
+        ])
+        .await;
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("Transaction sent"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Simulation reverted"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn sends_share_the_run_pipeline() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let builder = ConditionalBuilder::start(&harness).await;
+    builder.build_every(Duration::from_millis(200));
+    // send calls any contract, so GasConsumer doesn't have to be configured
+    std::fs::write(
+        harness.config_path(),
+        format!("world_id = \"{}\"\nrpc_address = \"{}\"\n", WORLD_ID, harness.anvil.endpoint),
+    )
+    .unwrap();
+
+    let gas_consumer = harness.gas_consumer.to_string();
+    let output = harness
+        .run(&[
+            "--provider-uri",
+            &builder.url,
+            "send",
+            "--to",
+            &gas_consumer,
+            "--sig",
+            "storeCold(uint256)",
+            "--args",
+            "2",
+            "--count",
+            "2",
+            "--conditional",
+        ])
+        .await;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let count = |pattern: &str| stdout.lines().filter(|line| line.contains(pattern)).count();
+    assert_eq!(count("Transaction sent: "), 2, "{stdout}");
+    assert_eq!(count(": included in block"), 2, "{stdout}");
+    assert!(stdout.contains("Recorded run"), "{stdout}");
+}
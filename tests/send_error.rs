@@ -0,0 +1,153 @@
+mod common;
+
+use alloy_consensus::Transaction;
+use alloy_primitives::U256;
+use alloy_provider::Provider;
+use alloy_rpc_types_eth::TransactionInput;
+use alloy_sol_types::SolError;
+use common::Harness;
+use gas_test::bindings::IPBHEntryPoint;
+use gas_test::client::{GasTestClient, TxType};
+use gas_test::send_error::{entry_point_error, rejection_reason, SendError, SendErrorKind};
+use gas_test::workload::consume_gas_calldata;
+
+#[test]
+fn rpc_messages_are_classified() {
+    let cases = [
+        ("nonce too low: next nonce 5, tx nonce 3", SendErrorKind::NonceTooLow),
+        ("nonce too high", SendErrorKind::NonceTooHigh),
+        ("transaction underpriced", SendErrorKind::Underpriced),
+        (
+            "max fee per gas less than block base fee: address 0xf39F, maxFeePerGas: 1, baseFee: 7",
+            SendErrorKind::Underpriced,
+        ),
+        ("replacement transaction underpriced", SendErrorKind::ReplacementUnderpriced),
+        (
+            "insufficient funds for gas * price + value: balance 0, tx cost 1300000",
+            SendErrorKind::InsufficientFunds,
+        ),
+        ("exceeds block gas limit", SendErrorKind::GasLimitExceeded),
+        ("already known", SendErrorKind::AlreadyKnown),
+        ("Invalid proof", SendErrorKind::PbhInvalidProof),
+        ("Duplicate nullifier hash", SendErrorKind::PbhDuplicateNullifier),
+        ("PBH Transaction gas limit exceeded", SendErrorKind::PbhGasLimitExceeded),
+        ("Invalid root", SendErrorKind::PbhInvalidRoot),
+        ("Invalid external nullifier period", SendErrorKind::PbhInvalidExternalNullifier),
+        ("failed conditional: storage root mismatch", SendErrorKind::ConditionFailed),
+        ("header not found", SendErrorKind::Other),
+        // Messages sharing a word with a known error, not its phrase
+        (
+            "the method eth_sendRawTransactionConditional does not exist/is not available",
+            SendErrorKind::Other,
+        ),
+        ("intrinsic gas too low: have 21000, want 53000", SendErrorKind::Other),
+        ("invalid rootfs path", SendErrorKind::Other),
+        ("max priority fee per gas higher than max fee per gas", SendErrorKind::Other),
+    ];
+    for (message, kind) in cases {
+        assert_eq!(SendError::new(message).kind, kind, "{message}");
+    }
+}
+
+#[test]
+fn entry_point_errors_are_named() {
+    let data = IPBHEntryPoint::InvalidRoot {
+        root: Default::default(),
+    }
+    .abi_encode();
+    assert_eq!(entry_point_error(&data), Some("InvalidRoot"));
+    assert_eq!(entry_point_error(&[0xde, 0xad, 0xbe, 0xef]), None);
+    assert_eq!(entry_point_error(&[]), None);
+}
+
+#[test]
+fn revert_data_is_classified() {
+    // Builders that simulate the transaction answer with the entry point's revert
+    let data = IPBHEntryPoint::InvalidRoot {
+        root: U256::from(1),
+    }
+    .abi_encode();
+    let err = SendError::with_data("execution reverted", Some(data.into()));
+    assert_eq!(err.kind, SendErrorKind::PbhInvalidRoot);
+    assert_eq!(err.reason(), "InvalidRoot");
+    assert_eq!(rejection_reason(&err.into()), "InvalidRoot");
+
+    // Unknown revert data leaves the message to classify
+    let err = SendError::with_data("nonce too low", Some(vec![0xde, 0xad, 0xbe, 0xef].into()));
+    assert_eq!(err.kind, SendErrorKind::NonceTooLow);
+    assert_eq!(err.reason(), "nonce too low");
+}
+
+#[test]
+fn only_nonce_errors_are_retried() {
+    assert!(SendErrorKind::NonceTooLow.is_retryable());
+    assert!(!SendErrorKind::ReplacementUnderpriced.is_retryable());
+    assert!(!SendErrorKind::Underpriced.is_retryable());
+    assert!(!SendErrorKind::InsufficientFunds.is_retryable());
+    assert!(!SendErrorKind::PbhDuplicateNullifier.is_retryable());
+}
+
+async fn client(harness: &Harness, max_retries: u32) -> GasTestClient {
+    harness.client().await.with_max_retries(max_retries)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn stale_nonce_is_retried() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let calldata = consume_gas_calldata(harness.gas_consumer, U256::from(10));
+
+    // Nonce 0 was used by the first deployment
+    let tx = client(&harness, 0)
+        .await
+        .tx_builder()
+        .await
+        .unwrap()
+        .nonce(0)
+        .to(harness.gas_consumer)
+        .input(TransactionInput::new(calldata))
+        .build(harness.signer.clone())
+        .await
+        .unwrap();
+
+    let err = client(&harness, 0)
+        .await
+        .send_raw(&tx, TxType::Direct)
+        .await
+        .unwrap_err();
+    let err = err.downcast_ref::<SendError>().expect("not a send error");
+    assert_eq!(err.kind, SendErrorKind::NonceTooLow);
+
+    let sent = client(&harness, 1)
+        .await
+        .send_raw(&tx, TxType::Direct)
+        .await
+        .unwrap();
+    assert_eq!(sent.retries, 1);
+    assert!(harness.receipt(sent.tx_hash).await.status());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn known_transactions_are_not_signed_again() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let client = client(&harness, 1).await;
+    let tx = client
+        .build_direct(consume_gas_calldata(harness.gas_consumer, U256::from(10)))
+        .await
+        .unwrap();
+    let sent = client.send_raw(&tx, TxType::Direct).await.unwrap();
+    assert!(harness.receipt(sent.tx_hash).await.status());
+
+    // The nonce is now taken by the transaction itself, so sending it again is not retried
+    let again = client.send_raw(&tx, TxType::Direct).await.unwrap();
+    assert_eq!((again.tx_hash, again.retries), (sent.tx_hash, 0));
+    let account_nonce = harness
+        .provider
+        .get_transaction_count(harness.signer.address())
+        .await
+        .unwrap();
+    assert_eq!(account_nonce, tx.nonce() + 1);
+}
@@ -0,0 +1,60 @@
+use alloy_primitives::{address, Bytes, U256};
+use alloy_sol_types::SolValue;
+use gas_test::bindings;
+use gas_test::userop::{aggregated_signature, pack_u128s, pbh_signature, userop_signal_hash};
+use gas_test::proof::MockProofProvider;
+use gas_test::world_id::WorldID;
+use semaphore_rs::hash_to_field;
+
+#[test]
+fn signal_hash_is_packed_sender_nonce_calldata() {
+    let sender = address!("1111111111111111111111111111111111111111");
+    let nonce = U256::from(7);
+    let call_data = Bytes::from(vec![0xde, 0xad, 0xbe, 0xef]);
+
+    let mut packed = sender.to_vec();
+    packed.extend_from_slice(&nonce.to_be_bytes::<32>());
+    packed.extend_from_slice(&call_data);
+
+    assert_eq!(userop_signal_hash(sender, nonce, &call_data), hash_to_field(&packed));
+}
+
+#[test]
+fn gas_words_pack_high_and_low_halves() {
+    let packed = pack_u128s(1, 2);
+    assert_eq!(packed[15], 1);
+    assert_eq!(packed[31], 2);
+    assert!(packed[..15].iter().all(|b| *b == 0));
+}
+
+#[tokio::test]
+async fn signature_carries_abi_encoded_payload() {
+    let world_id = WorldID::random();
+    let payload = world_id
+        .pbh_payload(&MockProofProvider::default(), 0, U256::from(1))
+        .await
+        .unwrap();
+
+    let signature = pbh_signature(&[0xab; 65], &payload);
+    assert_eq!(signature.len(), 12 + 65 + 32 * 11);
+    assert!(signature[..12].iter().all(|b| *b == 0));
+
+    let decoded = bindings::PBHPayload::abi_decode(&signature[77..], true).unwrap();
+    assert_eq!(decoded.nullifierHash, payload.nullifier_hash);
+    assert_eq!(decoded.root, payload.root);
+}
+
+#[tokio::test]
+async fn aggregated_signature_is_payload_array() {
+    let world_id = WorldID::random();
+    let prover = MockProofProvider::default();
+    let payloads = vec![
+        world_id.pbh_payload(&prover, 0, U256::from(1)).await.unwrap(),
+        world_id.pbh_payload(&prover, 1, U256::from(2)).await.unwrap(),
+    ];
+
+    let signature = aggregated_signature(&payloads);
+    let decoded = Vec::<bindings::PBHPayload>::abi_decode(&signature, true).unwrap();
+    assert_eq!(decoded.len(), 2);
+    assert_eq!(decoded[1].nullifierHash, payloads[1].nullifier_hash);
+}